@@ -0,0 +1,238 @@
+// Headless mode, for scripted debloating of many devices.
+// It goes through the same functions as the GUI: only the presentation differs.
+
+use crate::core::config::{Config, DeviceSettings, GeneralSettings};
+use crate::core::debloat::{self, Job, Progress};
+use crate::core::save::{write_report, PackageReport, RunReport};
+use crate::core::sync::{action_handler, allow_adb_restart, get_devices_list, CorePackage, User};
+use crate::core::uad_lists::{load_debloat_lists, PackageState, Removal};
+use crate::core::utils::fetch_packages;
+use crate::gui::views::list::PackageInfo;
+use iced::futures::executor::block_on;
+use std::sync::atomic::AtomicBool;
+
+const USAGE: &str = "Usage: uad --cli --apply-list <recommended|advanced|expert|unsafe> \
+    [--serial <id>] [--report <file.json>] [--expert]";
+
+struct CliArgs {
+    removal: Removal,
+    serial: Option<String>,
+    report: Option<String>,
+    expert: bool, // expert mode for this run only, like the GUI's session toggle
+}
+
+fn parse_args(args: &[String]) -> Result<CliArgs, String> {
+    let mut removal = None;
+    let mut serial = None;
+    let mut report = None;
+    let mut expert = false;
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--cli" => {}
+            "--apply-list" => {
+                let name = args.next().ok_or("--apply-list needs a value")?;
+                removal = Some(
+                    Removal::ALL
+                        .iter()
+                        .filter(|r| !matches!(r, Removal::All | Removal::Unlisted))
+                        .find(|r| r.to_string().eq_ignore_ascii_case(name))
+                        .copied()
+                        .ok_or(format!("Unknown removal list: {}", name))?,
+                );
+            }
+            "--serial" => serial = Some(args.next().ok_or("--serial needs a value")?.clone()),
+            "--report" => report = Some(args.next().ok_or("--report needs a value")?.clone()),
+            "--expert" => expert = true,
+            other => return Err(format!("Unknown argument: {}", other)),
+        }
+    }
+
+    Ok(CliArgs {
+        removal: removal.ok_or("--apply-list is required")?,
+        serial,
+        report,
+        expert,
+    })
+}
+
+/// Runs the command line mode and returns the exit code of the process
+pub fn run(args: &[String]) -> i32 {
+    let args = match parse_args(args) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{}\n{}", e, USAGE);
+            return 2;
+        }
+    };
+
+    // Same settings as the GUI
+    let config = Config::load_configuration_file();
+    let general = GeneralSettings {
+        session_expert_mode: args.expert,
+        ..config.general
+    };
+    if !general.can_remove(args.removal) {
+        if general.safe_mode {
+            eprintln!("Safe mode is on: only the Recommended list can be applied");
+        } else {
+            eprintln!(
+                "The {} list needs expert mode: turn it on or pass --expert",
+                args.removal
+            );
+        }
+        return 1;
+    }
+
+    println!("Looking for devices...");
+    let devices = block_on(get_devices_list());
+    let phone = match (&args.serial, devices.as_slice()) {
+        (Some(serial), devices) => match devices.iter().find(|d| d.adb_id == *serial) {
+            Some(phone) => phone.clone(),
+            None => {
+                eprintln!("Device {} not found", serial);
+                return 1;
+            }
+        },
+        (None, [phone]) => phone.clone(),
+        (None, []) => {
+            eprintln!("No device found");
+            return 1;
+        }
+        (None, _) => {
+            eprintln!("Several devices are connected: pick one with --serial");
+            return 1;
+        }
+    };
+    println!("Device: {} ({})", phone.model, phone.adb_id);
+
+    let uad_lists = match load_debloat_lists(true, None, None).0 {
+        Ok(list) => list,
+        Err(local_list) => {
+            println!("Could not download the latest UAD lists, using a cached or embedded one");
+            local_list
+        }
+    };
+
+    let device_settings = config
+        .devices
+        .into_iter()
+        .find(|d| d.device_id == phone.adb_id)
+        .unwrap_or_else(|| DeviceSettings {
+            device_id: phone.adb_id.clone(),
+            ..DeviceSettings::default()
+        });
+
+    let user = phone.user_list.first().copied().unwrap_or(User::default());
+    let packages = if phone.user_list.len() <= 1 {
+        fetch_packages(&uad_lists, None, &phone.adb_id)
+    } else {
+        fetch_packages(&uad_lists, Some(&user), &phone.adb_id)
+    };
+
+    let (targets, skipped): (Vec<(usize, _)>, Vec<(usize, _)>) = packages
+        .iter()
+        .enumerate()
+        .filter(|(_, p)| p.removal == args.removal)
+        .partition(|(_, p)| p.state == PackageState::Enabled);
+    println!(
+        "{} enabled packages in the {} list ({} skipped: not enabled)",
+        targets.len(),
+        args.removal,
+        skipped.len()
+    );
+
+    let mut jobs = vec![];
+    let mut package_reports = vec![];
+    for (index, package) in &targets {
+        package_reports.push(PackageReport {
+            name: package.name.clone(),
+            action: package.action_label(&device_settings).to_string(),
+            success: true,
+            error: None,
+        });
+        let actions = action_handler(
+            &user,
+            &CorePackage::from(*package),
+            package.disables(&device_settings),
+            &phone,
+            &device_settings,
+        );
+        jobs.extend(actions.into_iter().map(|(i_user, action)| Job {
+            package: package.name.clone(),
+            info: PackageInfo {
+                i_user,
+                index: *index,
+                removal: package.removal.to_string(),
+                force_disable: false,
+                suspend: None,
+                uninstalled_instead: false,
+            },
+            action,
+            change_state: false, // nothing to show
+        }));
+    }
+
+    allow_adb_restart();
+    let mut failed = 0;
+    let mut current = None;
+    // Nothing cancels a command line run
+    let cancel = AtomicBool::new(false);
+    debloat::run(&phone.adb_id, jobs, &cancel, |progress| match progress {
+        Progress::Started(job) => {
+            // Packages can have several commands
+            if current.as_ref() != Some(&job.package) {
+                let position = targets
+                    .iter()
+                    .position(|(_, p)| p.name == job.package)
+                    .unwrap_or_default();
+                println!("[{}/{}] {}", position + 1, targets.len(), job.package);
+                current = Some(job.package);
+            }
+        }
+        Progress::Completed(job) if job.info.uninstalled_instead => {
+            println!(
+                "  ok: {} (Android doesn't allow disabling it: uninstalled instead)",
+                job.action
+            );
+            if let Some(report) = package_reports.iter_mut().find(|r| r.name == job.package) {
+                report.action = "uninstall".to_string();
+            }
+        }
+        Progress::Completed(job) => println!("  ok: {}", job.action),
+        Progress::Failed(job, e) => {
+            println!("  failed: {}", e);
+            if let Some(report) = package_reports.iter_mut().find(|r| r.name == job.package) {
+                report.success = false;
+                report.error = Some(e.stderr);
+            }
+        }
+        Progress::Finishing(_) | Progress::Cancelled(_) => {}
+        Progress::Finished {
+            succeeded,
+            failed: failures,
+            ..
+        } => {
+            failed = failures;
+            println!(
+                "Done: {} commands succeeded, {} failed ({} packages)",
+                succeeded,
+                failures,
+                targets.len()
+            );
+        }
+    });
+    if let Some(path) = &args.report {
+        let report = RunReport::new(&phone.adb_id, package_reports, skipped.len());
+        match write_report(std::path::Path::new(path), &report) {
+            Ok(_) => println!("Report written to {}", path),
+            Err(e) => eprintln!("Could not write the report to {}: {}", path, e),
+        }
+    }
+    if failed > 0 {
+        1
+    } else {
+        0
+    }
+}
@@ -0,0 +1,42 @@
+use crate::CONFIG_DIR;
+use serde::{Deserialize, Serialize};
+use static_init::dynamic;
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+// Like notes, categories follow a package id from one device to another
+#[dynamic]
+static CATEGORIES_FILE: PathBuf = CONFIG_DIR.join("categories.json");
+
+/// Removal categories defined by the user, replacing the one of the UAD lists
+/// for the packages assigned to them
+#[derive(Default, Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Categories {
+    pub labels: Vec<String>,
+    pub packages: HashMap<String, String>, // package name -> label
+}
+
+pub fn load_categories() -> Categories {
+    match fs::read_to_string(&*CATEGORIES_FILE) {
+        Ok(data) => serde_json::from_str(&data).unwrap_or_else(|e| {
+            error!(
+                "[CATEGORIES]: Unable to parse {}: {}",
+                CATEGORIES_FILE.display(),
+                e
+            );
+            Categories::default()
+        }),
+        Err(_) => Categories::default(),
+    }
+}
+
+pub fn save_categories(categories: &Categories) -> Result<(), String> {
+    match serde_json::to_string_pretty(categories) {
+        Ok(json) => fs::write(&*CATEGORIES_FILE, json).map_err(|e| e.to_string()),
+        Err(e) => {
+            error!("[CATEGORIES]: {}", e);
+            Err(e.to_string())
+        }
+    }
+}
@@ -1,4 +1,5 @@
-use crate::core::sync::{get_android_sdk, User};
+use crate::core::sync::{User, LOG_ADB_COMMANDS};
+use crate::core::uad_lists::{PackageState, Removal, UadList};
 use crate::core::utils::DisplayablePath;
 use crate::gui::views::settings::Settings;
 use crate::CONFIG_DIR;
@@ -6,6 +7,7 @@ use serde::{Deserialize, Serialize};
 use static_init::dynamic;
 use std::fs;
 use std::path::PathBuf;
+use std::sync::atomic::Ordering;
 
 #[derive(Default, Debug, Serialize, Deserialize, Clone)]
 pub struct Config {
@@ -14,10 +16,324 @@ pub struct Config {
     pub devices: Vec<DeviceSettings>,
 }
 
-#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct GeneralSettings {
     pub theme: String,
     pub expert_mode: bool,
+    /// Expert mode until UAD is closed, without touching the saved `expert_mode`
+    #[serde(skip)]
+    pub session_expert_mode: bool,
+    #[serde(default = "default_description_height")]
+    pub description_height: u16,
+    /// Names of the packages always listed first, whatever the device
+    #[serde(default)]
+    pub pinned_packages: Vec<String>,
+    /// Names of the packages left out of the list, whatever the device
+    #[serde(default)]
+    pub allow_listed_packages: Vec<String>,
+    /// Leave uninstalled packages out of the list unless they are explicitly asked for
+    #[serde(default)]
+    pub hide_uninstalled: bool,
+    /// Show a single count for disabled and uninstalled packages in the selection buttons
+    #[serde(default)]
+    pub count_disabled_as_uninstalled: bool,
+    #[serde(default)]
+    pub log_level: LogLevel,
+    /// Log the full adb command line of every action (debug level)
+    #[serde(default)]
+    pub log_adb_commands: bool,
+    /// Number of packages from which an action needs to be confirmed (0 = always)
+    #[serde(default = "default_confirm_threshold")]
+    pub confirm_threshold: usize,
+    /// Battery level (percent) under which a bulk action needs to be confirmed (0 = never)
+    #[serde(default = "default_battery_threshold")]
+    pub battery_threshold: u8,
+    /// Filters applied when the packages of a device are loaded
+    #[serde(default = "default_package_state")]
+    pub default_package_state: PackageState,
+    #[serde(default = "default_list")]
+    pub default_list: UadList,
+    #[serde(default = "default_removal")]
+    pub default_removal: Removal, // `Removal::All` selects all of them
+    /// Only recommended packages can be removed, whatever the other settings
+    #[serde(default)]
+    pub safe_mode: bool,
+    #[serde(default)]
+    pub list_density: ListDensity,
+    /// Controls shown above the package list, in this order
+    #[serde(default = "default_filter_controls")]
+    pub filter_controls: Vec<FilterControl>,
+    /// Ask to reboot the device once a bulk action succeeded
+    #[serde(default)]
+    pub reboot_after_batch: bool,
+    /// Select the packages the last list update newly recommends for removal
+    #[serde(default)]
+    pub auto_select_new_recommended: bool,
+    /// Show the state the device reports after each action instead of assuming it succeeded
+    #[serde(default)]
+    pub verify_package_state: bool,
+    /// Once a bulk action is over, check the state of every package it changed
+    #[serde(default)]
+    pub verify_batch: bool,
+    /// Hovering a package previews its description
+    #[serde(default)]
+    pub hover_preview: bool,
+    /// Packages stay selected after a successful action (bulk or single)
+    #[serde(default)]
+    pub keep_selection_after_action: bool,
+    /// Keep the window above the other ones (from the next launch)
+    #[serde(default)]
+    pub always_on_top: bool,
+    /// Ask before switching to another device while packages are selected
+    #[serde(default = "default_confirm_device_switch")]
+    pub confirm_device_switch: bool,
+    /// Debloat list to use instead of downloading it (download if empty)
+    #[serde(default)]
+    pub local_list_path: String,
+    /// SHA-256 the downloaded debloat list must match (checksum published with it if empty)
+    #[serde(default)]
+    pub list_sha256: String,
+    /// Base directory of the backups, exports, snapshots, reports and logs
+    /// (the cache directory if empty). Read at startup.
+    #[serde(default)]
+    pub data_dir: String,
+    /// JSON file describing the outcome of the last bulk action (none if empty).
+    /// A relative path goes in the `reports` folder of `data_dir`.
+    #[serde(default)]
+    pub report_path: String,
+    /// Append a line to `status_path` every time the state of a package changes
+    #[serde(default)]
+    pub status_sink: bool,
+    #[serde(default)]
+    pub status_path: String,
+    /// Profile whose packages are selected when a device is connected (none if empty)
+    #[serde(default)]
+    pub connect_profile: String,
+    /// Apply `connect_profile` right away instead of only selecting its packages
+    #[serde(default)]
+    pub auto_apply_profile: bool,
+}
+
+impl Default for GeneralSettings {
+    fn default() -> Self {
+        Self {
+            theme: String::default(),
+            expert_mode: false,
+            session_expert_mode: false,
+            description_height: default_description_height(),
+            pinned_packages: vec![],
+            allow_listed_packages: vec![],
+            hide_uninstalled: false,
+            count_disabled_as_uninstalled: false,
+            log_level: LogLevel::default(),
+            log_adb_commands: false,
+            confirm_threshold: default_confirm_threshold(),
+            battery_threshold: default_battery_threshold(),
+            default_package_state: default_package_state(),
+            default_list: default_list(),
+            default_removal: default_removal(),
+            safe_mode: false,
+            list_density: ListDensity::default(),
+            filter_controls: default_filter_controls(),
+            reboot_after_batch: false,
+            auto_select_new_recommended: false,
+            verify_package_state: false,
+            verify_batch: false,
+            hover_preview: false,
+            always_on_top: false,
+            confirm_device_switch: default_confirm_device_switch(),
+            keep_selection_after_action: false,
+            local_list_path: String::new(),
+            list_sha256: String::new(),
+            data_dir: String::new(),
+            report_path: String::new(),
+            status_sink: false,
+            status_path: String::new(),
+            connect_profile: String::new(),
+            auto_apply_profile: false,
+        }
+    }
+}
+
+impl GeneralSettings {
+    /// Whether packages of this removal category can be selected and acted upon
+    pub fn can_remove(&self, removal: Removal) -> bool {
+        if self.safe_mode {
+            removal == Removal::Recommended
+        } else {
+            removal != Removal::Unsafe || self.expert_mode || self.session_expert_mode
+        }
+    }
+
+    /// Reconfigure the logger according to these settings
+    pub fn apply_logging(&self) {
+        log::set_max_level(self.log_level.level_filter());
+        LOG_ADB_COMMANDS.store(self.log_adb_commands, Ordering::Relaxed);
+    }
+}
+
+/// Spacing of the package list
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ListDensity {
+    #[default]
+    Comfortable,
+    Compact,
+}
+
+impl ListDensity {
+    pub const ALL: [ListDensity; 2] = [ListDensity::Comfortable, ListDensity::Compact];
+
+    /// Padding inside a package row
+    pub fn row_padding(&self) -> u16 {
+        match self {
+            ListDensity::Comfortable => 8,
+            ListDensity::Compact => 2,
+        }
+    }
+
+    /// Spacing between the elements of a package row
+    pub fn row_spacing(&self) -> u16 {
+        match self {
+            ListDensity::Comfortable => 10,
+            ListDensity::Compact => 6,
+        }
+    }
+
+    /// Spacing between package rows
+    pub fn list_spacing(&self) -> u16 {
+        match self {
+            ListDensity::Comfortable => 6,
+            ListDensity::Compact => 1,
+        }
+    }
+}
+
+impl std::fmt::Display for ListDensity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                ListDensity::Comfortable => "Comfortable",
+                ListDensity::Compact => "Compact",
+            }
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FilterControl {
+    Search,
+    User,
+    PackageState,
+    List,
+    Restorability,
+    JumpToLetter,
+    Refresh,
+}
+
+impl FilterControl {
+    pub const ALL: [FilterControl; 7] = [
+        FilterControl::Search,
+        FilterControl::User,
+        FilterControl::PackageState,
+        FilterControl::List,
+        FilterControl::Restorability,
+        FilterControl::JumpToLetter,
+        FilterControl::Refresh,
+    ];
+}
+
+impl std::fmt::Display for FilterControl {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                FilterControl::Search => "Search",
+                FilterControl::User => "User",
+                FilterControl::PackageState => "Package state",
+                FilterControl::List => "UAD list",
+                FilterControl::Restorability => "Restorability",
+                FilterControl::JumpToLetter => "Jump to letter",
+                FilterControl::Refresh => "Refresh button",
+            }
+        )
+    }
+}
+
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    #[default]
+    Debug,
+}
+
+impl LogLevel {
+    pub const ALL: [LogLevel; 4] = [
+        LogLevel::Error,
+        LogLevel::Warn,
+        LogLevel::Info,
+        LogLevel::Debug,
+    ];
+
+    pub fn level_filter(&self) -> log::LevelFilter {
+        match self {
+            LogLevel::Error => log::LevelFilter::Error,
+            LogLevel::Warn => log::LevelFilter::Warn,
+            LogLevel::Info => log::LevelFilter::Info,
+            LogLevel::Debug => log::LevelFilter::Debug,
+        }
+    }
+}
+
+impl std::fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                LogLevel::Error => "Error",
+                LogLevel::Warn => "Warning",
+                LogLevel::Info => "Info",
+                LogLevel::Debug => "Debug",
+            }
+        )
+    }
+}
+
+fn default_description_height() -> u16 {
+    150
+}
+
+fn default_confirm_threshold() -> usize {
+    10
+}
+
+fn default_battery_threshold() -> u8 {
+    20
+}
+
+fn default_confirm_device_switch() -> bool {
+    true
+}
+
+fn default_package_state() -> PackageState {
+    PackageState::Enabled
+}
+
+fn default_list() -> UadList {
+    UadList::All
+}
+
+fn default_removal() -> Removal {
+    Removal::Recommended
+}
+
+fn default_filter_controls() -> Vec<FilterControl> {
+    FilterControl::ALL.to_vec()
 }
 
 #[derive(Default, Debug, Clone)]
@@ -34,6 +350,14 @@ pub struct DeviceSettings {
     pub device_id: String,
     pub disable_mode: bool,
     pub multi_user_mode: bool,
+    #[serde(default)]
+    pub collapse_multi_user: bool,
+    /// Removal categories whose packages are disabled instead of uninstalled
+    #[serde(default)]
+    pub disable_removals: Vec<Removal>,
+    /// The automatic application of `connect_profile` was confirmed once on this device
+    #[serde(default)]
+    pub auto_apply_confirmed: bool,
     #[serde(skip)]
     pub backup: BackupSettings,
 }
@@ -42,13 +366,24 @@ impl Default for DeviceSettings {
     fn default() -> Self {
         Self {
             device_id: String::new(),
-            multi_user_mode: get_android_sdk() > 21,
+            // Set according to the device when its settings are loaded
+            multi_user_mode: false,
             disable_mode: false,
+            collapse_multi_user: false,
+            disable_removals: vec![],
+            auto_apply_confirmed: false,
             backup: BackupSettings::default(),
         }
     }
 }
 
+impl DeviceSettings {
+    /// Whether removing a package of this category disables it rather than uninstalling it
+    pub fn disables(&self, removal: Removal) -> bool {
+        self.disable_mode || self.disable_removals.contains(&removal)
+    }
+}
+
 #[dynamic]
 static CONFIG_FILE: PathBuf = CONFIG_DIR.join("config.toml");
 
@@ -74,6 +409,14 @@ impl Config {
         fs::write(&*CONFIG_FILE, toml).expect("Could not write config file to disk!");
     }
 
+    /// Saves the settings that don't depend on a device, even when none is connected
+    pub fn save_general(general: &GeneralSettings) {
+        let mut config = Self::load_configuration_file();
+        config.general = general.clone();
+        let toml = toml::to_string(&config).unwrap();
+        fs::write(&*CONFIG_FILE, toml).expect("Could not write config file to disk!");
+    }
+
     pub fn load_configuration_file() -> Self {
         match fs::read_to_string(&*CONFIG_FILE) {
             Ok(s) => match toml::from_str(&s) {
@@ -0,0 +1,108 @@
+// Debloat runs, whatever the front-end: the GUI and the CLI both follow them
+// through the same progress events instead of their own messages.
+
+use crate::core::sync::{perform_adb_commands, AdbError, CommandType};
+use crate::gui::views::list::PackageInfo;
+use iced::futures::channel::mpsc::{self, UnboundedReceiver};
+use iced::futures::executor::block_on;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+/// A single adb command of a run
+#[derive(Debug, Clone)]
+pub struct Job {
+    pub package: String,
+    pub info: PackageInfo, // package index, and user index for multi-user commands
+    pub action: String,
+    pub change_state: bool, // only the first command of each user changes the package state
+}
+
+#[derive(Debug, Clone)]
+pub enum Progress {
+    Started(Job),
+    Completed(Job),
+    Failed(Job, AdbError),
+    /// The run was cancelled in the middle of the commands of this package:
+    /// the remaining ones are run anyway
+    Finishing(Job),
+    /// Not run, the run was cancelled before this package
+    Cancelled(Job),
+    Finished {
+        succeeded: usize,
+        failed: usize,
+        cancelled: usize,
+    },
+}
+
+/// Runs the jobs one after the other, reporting each step to `on_progress`.
+///
+/// The consecutive jobs of a package are a unit (e.g. enable then force-stop): once `cancel`
+/// is set, the current unit still goes to the end so no package is left half-applied,
+/// and the following ones are reported as cancelled.
+pub fn run(
+    serial: &str,
+    jobs: Vec<Job>,
+    cancel: &AtomicBool,
+    mut on_progress: impl FnMut(Progress),
+) {
+    let (mut succeeded, mut failed, mut cancelled) = (0, 0, 0);
+    let mut unit: Option<String> = None; // package of the jobs being run
+    let mut stopped = false;
+    let mut finishing = false;
+    for mut job in jobs {
+        if unit.as_ref() != Some(&job.package) {
+            unit = Some(job.package.clone());
+            stopped = stopped || cancel.load(Ordering::Relaxed);
+            finishing = false;
+        } else if !stopped && !finishing && cancel.load(Ordering::Relaxed) {
+            finishing = true;
+            on_progress(Progress::Finishing(job.clone()));
+        }
+        if stopped {
+            cancelled += 1;
+            on_progress(Progress::Cancelled(job));
+            continue;
+        }
+        on_progress(Progress::Started(job.clone()));
+        match block_on(perform_adb_commands(
+            job.action.clone(),
+            CommandType::PackageManager(job.info.clone()),
+            serial.to_string(),
+        )) {
+            Ok(command_type) => {
+                // What the command actually did (e.g. `uninstalled_instead`)
+                if let CommandType::PackageManager(info) = command_type {
+                    job.info = info;
+                }
+                succeeded += 1;
+                on_progress(Progress::Completed(job));
+            }
+            Err(e) => {
+                failed += 1;
+                on_progress(Progress::Failed(job, e));
+            }
+        }
+    }
+    on_progress(Progress::Finished {
+        succeeded,
+        failed,
+        cancelled,
+    });
+}
+
+/// Same as `run`, on another thread. The channel is closed after `Progress::Finished`.
+pub fn spawn(
+    serial: String,
+    jobs: Vec<Job>,
+    cancel: Arc<AtomicBool>,
+) -> UnboundedReceiver<Progress> {
+    let (sender, receiver) = mpsc::unbounded();
+    thread::spawn(move || {
+        run(&serial, jobs, &cancel, |progress| {
+            // Nobody is listening anymore: the run still goes to the end
+            let _ = sender.unbounded_send(progress);
+        })
+    });
+    receiver
+}
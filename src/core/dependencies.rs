@@ -0,0 +1,112 @@
+// Small built-in knowledge of packages other apps rely on.
+// Removing them is not always fatal (hence they're not all marked `Removal::Unsafe`)
+// but it often breaks things in a way that is hard to link back to the removal.
+
+/// Packages whose removal affects a large part of the system, whatever is installed
+const CRITICAL_PACKAGES: [(&str, &str); 7] = [
+    (
+        "com.google.android.gms",
+        "Google Play services: most Google apps and many third-party apps need it (login, notifications, location)",
+    ),
+    (
+        "com.google.android.gsf",
+        "Google Services Framework: Google Play services and the Play Store won't work without it",
+    ),
+    (
+        "com.google.android.webview",
+        "Android System WebView: apps displaying web content will crash or show blank pages",
+    ),
+    (
+        "com.android.webview",
+        "Android System WebView: apps displaying web content will crash or show blank pages",
+    ),
+    (
+        "com.android.providers.downloads",
+        "Download Manager: browsers and app stores use it to download files",
+    ),
+    (
+        "com.android.systemui",
+        "System UI: status bar, notifications and navigation can become unusable",
+    ),
+    (
+        "com.android.phone",
+        "Phone services: calls, SMS and mobile data can stop working",
+    ),
+];
+
+/// (package, packages known to depend on it)
+const DEPENDENTS: [(&str, &[&str]); 7] = [
+    (
+        "com.google.android.gms",
+        &[
+            "com.android.vending",
+            "com.google.android.gm",
+            "com.google.android.apps.maps",
+            "com.google.android.youtube",
+            "com.google.android.apps.photos",
+        ],
+    ),
+    (
+        "com.google.android.gsf",
+        &["com.android.vending", "com.google.android.gms"],
+    ),
+    (
+        "com.android.providers.contacts",
+        &[
+            "com.android.contacts",
+            "com.google.android.contacts",
+            "com.google.android.dialer",
+            "com.samsung.android.contacts",
+        ],
+    ),
+    (
+        "com.android.providers.telephony",
+        &[
+            "com.android.mms",
+            "com.google.android.apps.messaging",
+            "com.samsung.android.messaging",
+        ],
+    ),
+    (
+        "com.android.providers.calendar",
+        &["com.android.calendar", "com.google.android.calendar"],
+    ),
+    (
+        "com.android.providers.media",
+        &[
+            "com.android.gallery3d",
+            "com.google.android.apps.photos",
+            "com.sec.android.gallery3d",
+        ],
+    ),
+    (
+        "com.android.providers.downloads",
+        &["com.android.vending", "com.android.chrome"],
+    ),
+];
+
+/// Warnings to show before removing (or disabling) `package`.
+/// `is_installed` tells if a package is currently enabled on the device.
+pub fn dependency_warnings(package: &str, is_installed: impl Fn(&str) -> bool) -> Vec<String> {
+    let mut warnings = vec![];
+
+    if let Some((_, reason)) = CRITICAL_PACKAGES.iter().find(|(p, _)| *p == package) {
+        warnings.push(format!("{} is a critical package. {}", package, reason));
+    }
+
+    if let Some((_, dependents)) = DEPENDENTS.iter().find(|(p, _)| *p == package) {
+        let installed: Vec<&str> = dependents
+            .iter()
+            .copied()
+            .filter(|d| is_installed(d))
+            .collect();
+        if !installed.is_empty() {
+            warnings.push(format!(
+                "{} is likely used by: {}",
+                package,
+                installed.join(", ")
+            ));
+        }
+    }
+    warnings
+}
@@ -1,4 +1,9 @@
+pub mod categories;
 pub mod config;
+pub mod debloat;
+pub mod dependencies;
+pub mod notes;
+pub mod profiles;
 pub mod save;
 pub mod sync;
 pub mod theme;
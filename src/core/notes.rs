@@ -0,0 +1,31 @@
+use crate::CONFIG_DIR;
+use static_init::dynamic;
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+// Notes don't depend on the device: the same package often behaves the same way on
+// every device of a brand.
+#[dynamic]
+static NOTES_FILE: PathBuf = CONFIG_DIR.join("notes.json");
+
+/// Notes of the user, by package name
+pub fn load_notes() -> HashMap<String, String> {
+    match fs::read_to_string(&*NOTES_FILE) {
+        Ok(data) => serde_json::from_str(&data).unwrap_or_else(|e| {
+            error!("[NOTES]: Unable to parse {}: {}", NOTES_FILE.display(), e);
+            HashMap::new()
+        }),
+        Err(_) => HashMap::new(),
+    }
+}
+
+pub fn save_notes(notes: &HashMap<String, String>) -> Result<(), String> {
+    match serde_json::to_string_pretty(notes) {
+        Ok(json) => fs::write(&*NOTES_FILE, json).map_err(|e| e.to_string()),
+        Err(e) => {
+            error!("[NOTES]: {}", e);
+            Err(e.to_string())
+        }
+    }
+}
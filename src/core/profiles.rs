@@ -0,0 +1,119 @@
+use crate::CONFIG_DIR;
+use serde::{Deserialize, Serialize};
+use static_init::dynamic;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[dynamic]
+pub static PROFILE_DIR: PathBuf = CONFIG_DIR.join("profiles");
+
+/// Named set of packages that can be selected on any device
+#[derive(Default, Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Profile {
+    pub packages: Vec<String>,
+}
+
+fn profile_path(name: &str) -> PathBuf {
+    PROFILE_DIR.join(format!("{}.json", name))
+}
+
+/// Profile names are used as file names
+pub fn is_valid_profile_name(name: &str) -> bool {
+    !name.trim().is_empty()
+        && !name
+            .chars()
+            .any(|c| matches!(c, '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|'))
+}
+
+pub fn list_profiles() -> Vec<String> {
+    let mut profiles: Vec<String> = match fs::read_dir(&*PROFILE_DIR) {
+        Ok(files) => files
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|p| p.extension().map_or(false, |ext| ext == "json"))
+            .filter_map(|p| p.file_stem().map(|s| s.to_string_lossy().to_string()))
+            .collect(),
+        Err(_) => vec![],
+    };
+    profiles.sort();
+    profiles
+}
+
+pub fn save_profile(name: &str, profile: &Profile) -> Result<(), String> {
+    if let Err(e) = fs::create_dir_all(&*PROFILE_DIR) {
+        error!("[PROFILE]: could not create profile dir: {}", e);
+        return Err(e.to_string());
+    }
+
+    match serde_json::to_string_pretty(profile) {
+        Ok(json) => fs::write(profile_path(name), json).map_err(|e| e.to_string()),
+        Err(e) => {
+            error!("[PROFILE]: {}", e);
+            Err(e.to_string())
+        }
+    }
+}
+
+pub fn load_profile(name: &str) -> Result<Profile, String> {
+    let data = fs::read_to_string(profile_path(name)).map_err(|e| e.to_string())?;
+    serde_json::from_str(&data).map_err(|e| {
+        error!("[PROFILE]: Unable to parse profile {}: {}", name, e);
+        e.to_string()
+    })
+}
+
+/// Plain text list of package ids, as found in debloat guides: one id per line,
+/// blank lines and `#` comments ignored
+pub fn load_package_id_list(path: &Path) -> Result<Profile, String> {
+    let data = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let packages: Vec<String> = data
+        .lines()
+        .map(|l| l.split('#').next().unwrap_or_default().trim())
+        .filter(|l| !l.is_empty())
+        .map(String::from)
+        .collect();
+    if packages.is_empty() {
+        return Err("no package id in the file".to_string());
+    }
+    Ok(Profile { packages })
+}
+
+pub fn delete_profile(name: &str) -> Result<(), String> {
+    fs::remove_file(profile_path(name)).map_err(|e| e.to_string())
+}
+
+/// Closest package to `name` among `candidates`, for profiles made on another device
+/// model where some package ids differ slightly (e.g. regional variants).
+/// Only packages of the same vendor (first 2 components of the id) are considered.
+pub fn closest_package<'a>(
+    name: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+) -> Option<&'a str> {
+    let vendor = |id: &'a str| id.splitn(3, '.').take(2).collect::<Vec<_>>();
+    let name_vendor = name.splitn(3, '.').take(2).collect::<Vec<_>>();
+    let max_distance = (name.len() / 5).max(1);
+
+    candidates
+        .into_iter()
+        .filter(|c| *c != name && vendor(*c) == name_vendor)
+        .map(|c| (edit_distance(name, c), c))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, c)| c)
+}
+
+/// Levenshtein distance
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut previous = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous + usize::from(ca != *cb);
+            previous = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(previous + 1);
+        }
+    }
+    row[b.len()]
+}
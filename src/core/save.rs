@@ -1,15 +1,58 @@
-use crate::core::config::DeviceSettings;
-use crate::core::sync::{apply_pkg_state_commands, CorePackage, Phone, User};
-use crate::core::utils::DisplayablePath;
+use crate::core::config::{Config, DeviceSettings};
+use crate::core::sync::{
+    adb_file_command, apply_pkg_state_commands, get_apk_paths, short_serial, CorePackage, Phone,
+    User,
+};
+use crate::core::uad_lists::{PackageState, UadListState};
+use crate::core::utils::{last_modified_date, DisplayablePath};
 use crate::gui::widgets::package_row::PackageRow;
 use crate::CACHE_DIR;
 use serde::{Deserialize, Serialize};
 use static_init::dynamic;
+use std::collections::HashMap;
 use std::fs;
+use std::io::Write;
 use std::path::{Path, PathBuf};
 
+/// Where the files made for the user go: the directory set in the settings (read once)
+/// or the cache directory. Each kind of file has its own folder in there.
 #[dynamic]
-pub static BACKUP_DIR: PathBuf = CACHE_DIR.join("backups");
+pub static DATA_DIR: PathBuf = data_dir(&Config::load_configuration_file().general.data_dir);
+
+#[dynamic]
+pub static BACKUP_DIR: PathBuf = DATA_DIR.join("backups");
+
+#[dynamic]
+pub static SNAPSHOT_DIR: PathBuf = DATA_DIR.join("snapshots");
+
+#[dynamic]
+pub static LAST_SESSION_DIR: PathBuf = CACHE_DIR.join("last_session");
+
+#[dynamic]
+pub static EXPORT_DIR: PathBuf = DATA_DIR.join("exports");
+
+#[dynamic]
+pub static APK_DIR: PathBuf = DATA_DIR.join("apks");
+
+#[dynamic]
+pub static REPORT_DIR: PathBuf = DATA_DIR.join("reports");
+
+#[dynamic]
+pub static LOG_DIR: PathBuf = DATA_DIR.join("logs");
+
+#[dynamic]
+pub static RECOVERY_DIR: PathBuf = CACHE_DIR.join("recovery");
+
+#[dynamic]
+pub static HISTORY_DIR: PathBuf = CACHE_DIR.join("history");
+
+/// A package removed (disabled or uninstalled) from a device
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HistoryEntry {
+    pub package: String,
+    pub state: PackageState, // state after the removal
+    pub timestamp: String,
+}
 
 #[derive(Default, Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
 struct PhoneBackup {
@@ -91,7 +134,10 @@ pub fn list_available_backup_user(backup: DisplayablePath) -> Vec<User> {
 
             let mut users = vec![];
             for u in phone_backup.users {
-                users.push(User { id: u.id, index: 0 });
+                users.push(User {
+                    id: u.id,
+                    ..User::default()
+                });
             }
             users
         }
@@ -165,3 +211,577 @@ pub fn restore_backup(
         Err(e) => Err("[BACKUP]: ".to_owned() + &e.to_string()),
     }
 }
+
+fn apk_backup_dir(device_id: &str, package: &str) -> PathBuf {
+    APK_DIR.join(device_id).join(package)
+}
+
+/// APK files of the last backup of the package
+fn apk_backup_files(device_id: &str, package: &str) -> Vec<PathBuf> {
+    match fs::read_dir(apk_backup_dir(device_id, package)) {
+        Ok(files) => {
+            let mut apks: Vec<PathBuf> = files
+                .filter_map(|e| e.ok())
+                .map(|e| e.path())
+                .filter(|p| p.extension().map_or(false, |ext| ext == "apk"))
+                .collect();
+            apks.sort();
+            apks
+        }
+        Err(_) => vec![],
+    }
+}
+
+pub fn has_apk_backup(device_id: &str, package: &str) -> bool {
+    !apk_backup_files(device_id, package).is_empty()
+}
+
+/// Pulls every APK of the package (base and splits) and returns how many there are.
+/// The previous backup of the package is replaced.
+pub async fn backup_apks(serial: String, package: String) -> Result<usize, String> {
+    let count = pull_apks(&serial, &package, &apk_backup_dir(&serial, &package))?;
+    info!("[APK BACKUP] {} APK(s) of {} pulled", count, package);
+    Ok(count)
+}
+
+/// Same as a backup, in `<dir>/<package>`, for archiving outside of UAD
+pub async fn export_apks(serial: String, package: String, dir: PathBuf) -> Result<usize, String> {
+    let count = pull_apks(&serial, &package, &dir.join(&package))?;
+    info!(
+        "[APK EXPORT] {} APK(s) of {} pulled to {}",
+        count,
+        package,
+        dir.display()
+    );
+    Ok(count)
+}
+
+/// Pulls every APK of the package in `dir`, replacing its content.
+/// The APKs are pulled next to it first: a failed pull leaves the previous ones untouched.
+fn pull_apks(serial: &str, package: &str, dir: &Path) -> Result<usize, String> {
+    let paths = get_apk_paths(package, serial);
+    if paths.is_empty() {
+        return Err(format!("no APK found for {}", package));
+    }
+
+    let pulling = dir.with_file_name(format!("{}.pulling", package));
+    if pulling.exists() {
+        fs::remove_dir_all(&pulling).map_err(|e| e.to_string())?;
+    }
+    if let Err(e) = fs::create_dir_all(&pulling) {
+        error!(
+            "[APK BACKUP]: could not create {}: {}",
+            pulling.display(),
+            e
+        );
+        return Err(e.to_string());
+    }
+
+    let pulled = paths.iter().try_for_each(|path| {
+        // Splits of a package have distinct names (base.apk, split_config.en.apk, ...)
+        let file_name = Path::new(path)
+            .file_name()
+            .ok_or_else(|| format!("invalid APK path: {}", path))?;
+        let local = pulling.join(file_name).to_string_lossy().to_string();
+        adb_file_command(serial, &["pull", path.as_str(), local.as_str()]).map(|_| ())
+    });
+    if let Err(e) = pulled {
+        let _ = fs::remove_dir_all(&pulling);
+        return Err(e);
+    }
+
+    if dir.exists() {
+        fs::remove_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    fs::rename(&pulling, dir).map_err(|e| e.to_string())?;
+    Ok(paths.len())
+}
+
+/// Installs the package back from its APK backup.
+/// Split APKs must be installed together, in a single session.
+pub async fn restore_apks(serial: String, package: String) -> Result<usize, String> {
+    let apks: Vec<String> = apk_backup_files(&serial, &package)
+        .iter()
+        .map(|p| p.to_string_lossy().to_string())
+        .collect();
+
+    let install = match apks.len() {
+        0 => return Err(format!("no APK backup of {}", package)),
+        1 => "install",
+        _ => "install-multiple",
+    };
+    let mut args = vec![install, "-r"];
+    args.extend(apks.iter().map(|a| a.as_str()));
+    adb_file_command(&serial, &args)?;
+    info!(
+        "[APK RESTORE] {} reinstalled from {} APK(s)",
+        package,
+        apks.len()
+    );
+    Ok(apks.len())
+}
+
+/// Shell script bringing another device to the package states of this one
+pub fn export_sync_script(
+    device_id: &str,
+    source_id: &str,
+    script: &str,
+) -> Result<PathBuf, String> {
+    let export_path = EXPORT_DIR.join(device_id);
+    if let Err(e) = fs::create_dir_all(&export_path) {
+        error!("[EXPORT]: could not create export dir: {}", e);
+        return Err(e.to_string());
+    }
+
+    let export_file = export_path.join(format!(
+        "sync_from_{}_{}.sh",
+        short_serial(source_id),
+        chrono::Local::now().format("%Y-%m-%d_%H-%M-%S")
+    ));
+    fs::write(&export_file, script).map_err(|e| e.to_string())?;
+    Ok(export_file)
+}
+
+/// Writes `packages` to a CSV file (in the given order) and returns its path
+pub fn export_packages(device_id: &str, packages: &[&PackageRow]) -> Result<PathBuf, String> {
+    let export_path = EXPORT_DIR.join(device_id);
+    if let Err(e) = fs::create_dir_all(&export_path) {
+        error!("[EXPORT]: could not create export dir: {}", e);
+        return Err(e.to_string());
+    }
+
+    let mut csv = String::from("package,state,list,removal,description\n");
+    for p in packages {
+        csv.push_str(&format!(
+            "{},{},{},{},{}\n",
+            csv_field(&p.name),
+            p.state,
+            p.uad_list,
+            p.removal,
+            csv_field(&p.description)
+        ));
+    }
+
+    let export_file = export_path.join(format!(
+        "{}.csv",
+        chrono::Local::now().format("%Y-%m-%d_%H-%M-%S")
+    ));
+    fs::write(&export_file, csv).map_err(|e| e.to_string())?;
+    Ok(export_file)
+}
+
+/// Outcome of a bulk action (or a command line run), for other tools
+#[derive(Serialize, Debug, Clone)]
+pub struct RunReport {
+    pub device: String, // serial
+    pub timestamp: String,
+    pub succeeded: usize,
+    pub failed: usize,
+    pub skipped: usize,
+    pub packages: Vec<PackageReport>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct PackageReport {
+    pub name: String,
+    pub action: String,
+    pub success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl RunReport {
+    pub fn new(device: &str, packages: Vec<PackageReport>, skipped: usize) -> Self {
+        Self {
+            device: device.to_string(),
+            timestamp: chrono::Local::now().to_rfc3339(),
+            succeeded: packages.iter().filter(|p| p.success).count(),
+            failed: packages.iter().filter(|p| !p.success).count(),
+            skipped,
+            packages,
+        }
+    }
+}
+
+pub fn write_report(path: &Path, report: &RunReport) -> Result<(), String> {
+    write_json(path, report)
+}
+
+/// A package state change, as written to the status file
+#[derive(Serialize, Debug, Clone)]
+pub struct StatusEvent {
+    pub serial: String,
+    pub package: String,
+    pub state: PackageState,
+    pub timestamp: String,
+}
+
+impl StatusEvent {
+    pub fn new(serial: &str, package: &str, state: PackageState) -> Self {
+        Self {
+            serial: serial.to_string(),
+            package: package.to_string(),
+            state,
+            timestamp: chrono::Local::now().to_rfc3339(),
+        }
+    }
+}
+
+/// Appends the event as a single JSON line, so the file can be followed (`tail -f`)
+pub fn append_status(path: &Path, event: &StatusEvent) -> Result<(), String> {
+    append_json_line(path, event)
+}
+
+fn append_json_line<T: Serialize>(path: &Path, value: &T) -> Result<(), String> {
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    let line = serde_json::to_string(value).map_err(|e| e.to_string())?;
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| e.to_string())?;
+    writeln!(file, "{}", line).map_err(|e| e.to_string())
+}
+
+fn write_json<T: Serialize>(path: &Path, value: &T) -> Result<(), String> {
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    let json = serde_json::to_string_pretty(value).map_err(|e| e.to_string())?;
+    fs::write(path, json).map_err(|e| e.to_string())
+}
+
+/// Everything needed to reproduce an issue, in a single file to attach to a bug report
+#[derive(Serialize, Debug, Clone)]
+pub struct SupportBundle {
+    pub uad_version: String,
+    pub timestamp: String,
+    pub lists_version: String,
+    pub lists_state: String,
+    pub device: Option<DeviceReport>,
+    pub users: Vec<UserReport>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct DeviceReport {
+    pub serial: String, // "redacted" if asked
+    pub model: String,
+    pub android_sdk: u8,
+    pub build: String, // fingerprint, fetched separately
+    pub root: String,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct UserReport {
+    pub id: u16,
+    pub packages: Vec<PackageStateReport>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct PackageStateReport {
+    pub name: String,
+    pub state: PackageState,
+    pub list: String,
+    pub removal: String,
+}
+
+impl SupportBundle {
+    pub fn new(
+        device: Option<&Phone>,
+        users_packages: &[Vec<PackageRow>],
+        lists_state: &UadListState,
+        redact_serial: bool,
+    ) -> Self {
+        let lists_date = last_modified_date(CACHE_DIR.join("uad_lists.json"));
+        Self {
+            uad_version: env!("CARGO_PKG_VERSION").to_string(),
+            timestamp: chrono::Local::now().to_rfc3339(),
+            lists_version: format!("v{}", lists_date.format("%Y%m%d")),
+            lists_state: lists_state.to_string(),
+            device: device.map(|phone| DeviceReport {
+                serial: if redact_serial {
+                    "redacted".to_string()
+                } else {
+                    phone.adb_id.clone()
+                },
+                model: phone.model.clone(),
+                android_sdk: phone.android_sdk,
+                build: String::new(),
+                root: phone.root.to_string(),
+            }),
+            users: device
+                .map(|phone| {
+                    phone
+                        .user_list
+                        .iter()
+                        .zip(users_packages)
+                        .map(|(user, packages)| UserReport {
+                            id: user.id,
+                            packages: packages
+                                .iter()
+                                .map(|p| PackageStateReport {
+                                    name: p.name.clone(),
+                                    state: p.state,
+                                    list: p.uad_list.to_string(),
+                                    removal: p.removal.to_string(),
+                                })
+                                .collect(),
+                        })
+                        .collect()
+                })
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// Writes the bundle next to the exports and returns its path
+pub fn write_support_bundle(bundle: &SupportBundle) -> Result<PathBuf, String> {
+    let path = EXPORT_DIR.join(format!(
+        "support_bundle_{}.json",
+        chrono::Local::now().format("%Y-%m-%d_%H-%M-%S")
+    ));
+    write_json(&path, bundle)?;
+    Ok(path)
+}
+
+/// Descriptions contain commas, quotes and line breaks
+fn csv_field(value: &str) -> String {
+    format!("\"{}\"", value.trim().replace('"', "\"\""))
+}
+
+/// Package states of a user of the device at some point in time
+#[derive(Default, Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    pub user_id: u16,
+    packages: Vec<CorePackage>,
+}
+
+/// `dir` if set, the cache directory otherwise
+pub fn data_dir(dir: &str) -> PathBuf {
+    match dir.trim() {
+        "" => CACHE_DIR.to_path_buf(),
+        dir => PathBuf::from(dir),
+    }
+}
+
+/// Path of a file set in the settings: a relative path goes in `dir`
+pub fn artifact_path(dir: &Path, path: &str) -> PathBuf {
+    match Path::new(path) {
+        path if path.is_absolute() => path.to_path_buf(),
+        path => dir.join(path),
+    }
+}
+
+/// One JSON line per removal
+fn history_path(device_id: &str) -> PathBuf {
+    HISTORY_DIR.join(format!("{}.jsonl", device_id))
+}
+
+/// Last removal of each package. The file is rewritten that way once it holds
+/// as many outdated lines as useful ones.
+pub fn load_history(device_id: &str) -> Vec<HistoryEntry> {
+    let path = history_path(device_id);
+    let data = match fs::read_to_string(&path) {
+        Ok(data) => data,
+        Err(_) => return vec![],
+    };
+    let mut nb_lines = 0;
+    let mut history: Vec<HistoryEntry> = vec![];
+    for line in data.lines().filter(|l| !l.trim().is_empty()) {
+        nb_lines += 1;
+        match serde_json::from_str::<HistoryEntry>(line) {
+            Ok(entry) => {
+                history.retain(|e| e.package != entry.package);
+                history.push(entry);
+            }
+            Err(e) => error!(
+                "[HISTORY]: Unable to parse a line of the debloat history: {}",
+                e
+            ),
+        }
+    }
+    if nb_lines > 2 * history.len() {
+        let lines: Result<Vec<String>, String> = history
+            .iter()
+            .map(|entry| serde_json::to_string(entry).map_err(|e| e.to_string()))
+            .collect();
+        let compacted = lines
+            .and_then(|lines| fs::write(&path, lines.join("\n") + "\n").map_err(|e| e.to_string()));
+        if let Err(e) = compacted {
+            error!("[HISTORY]: Unable to compact the debloat history: {}", e);
+        }
+    }
+    history
+}
+
+pub fn record_history(device_id: &str, package: &str, state: PackageState) {
+    let entry = HistoryEntry {
+        package: package.to_string(),
+        state,
+        timestamp: chrono::Local::now().to_rfc3339(),
+    };
+    if let Err(e) = append_json_line(&history_path(device_id), &entry) {
+        error!("[HISTORY]: Unable to save the debloat history: {}", e);
+    }
+}
+
+fn recovery_path(device_id: &str) -> PathBuf {
+    RECOVERY_DIR.join(format!("{}.json", device_id))
+}
+
+/// Names of the selected packages, kept in case UAD closes before they're applied
+pub fn save_recovery(device_id: &str, packages: &[String]) -> Result<(), String> {
+    fs::create_dir_all(&*RECOVERY_DIR).map_err(|e| e.to_string())?;
+    let json = serde_json::to_string(packages).map_err(|e| e.to_string())?;
+    fs::write(recovery_path(device_id), json).map_err(|e| e.to_string())
+}
+
+pub fn load_recovery(device_id: &str) -> Option<Vec<String>> {
+    let data = fs::read_to_string(recovery_path(device_id)).ok()?;
+    serde_json::from_str(&data)
+        .map_err(|e| error!("[RECOVERY]: Unable to parse the recovery file: {}", e))
+        .ok()
+}
+
+pub fn clear_recovery(device_id: &str) {
+    let path = recovery_path(device_id);
+    if path.exists() {
+        if let Err(e) = fs::remove_file(path) {
+            error!("[RECOVERY]: Unable to delete the recovery file: {}", e);
+        }
+    }
+}
+
+pub fn save_snapshot(device_id: &str, user: &User, packages: &[PackageRow]) -> Result<(), String> {
+    let snapshot = Snapshot {
+        user_id: user.id,
+        packages: packages.iter().map(CorePackage::from).collect(),
+    };
+
+    let snapshot_path = &*SNAPSHOT_DIR.join(device_id);
+    if let Err(e) = fs::create_dir_all(snapshot_path) {
+        error!("[SNAPSHOT]: could not create snapshot dir: {}", e);
+        return Err(e.to_string());
+    }
+
+    let snapshot_filename = format!(
+        "{}_user{}.json",
+        chrono::Local::now().format("%Y-%m-%d_%H-%M-%S"),
+        user.id
+    );
+    match serde_json::to_string_pretty(&snapshot) {
+        Ok(json) => {
+            fs::write(snapshot_path.join(snapshot_filename), json).map_err(|e| e.to_string())
+        }
+        Err(e) => {
+            error!("[SNAPSHOT]: {}", e);
+            Err(e.to_string())
+        }
+    }
+}
+
+pub fn load_snapshot(path: &Path) -> Result<Snapshot, String> {
+    let data = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    serde_json::from_str(&data).map_err(|e| {
+        error!("[SNAPSHOT]: Unable to parse {}: {}", path.display(), e);
+        e.to_string()
+    })
+}
+
+fn last_session_path(device_id: &str, user: &User) -> PathBuf {
+    LAST_SESSION_DIR.join(format!("{}_user{}.json", device_id, user.id))
+}
+
+/// Package states of a user when UAD last saw the device, to find out what changed since
+pub fn save_last_session(device_id: &str, user: &User, packages: &[PackageRow]) {
+    let snapshot = Snapshot {
+        user_id: user.id,
+        packages: packages.iter().map(CorePackage::from).collect(),
+    };
+    let saved = fs::create_dir_all(&*LAST_SESSION_DIR)
+        .map_err(|e| e.to_string())
+        .and_then(|_| serde_json::to_string(&snapshot).map_err(|e| e.to_string()))
+        .and_then(|json| {
+            fs::write(last_session_path(device_id, user), json).map_err(|e| e.to_string())
+        });
+    if let Err(e) = saved {
+        error!(
+            "[SNAPSHOT]: Unable to save the state of the last session: {}",
+            e
+        );
+    }
+}
+
+pub fn load_last_session(device_id: &str, user: &User) -> Option<Snapshot> {
+    load_snapshot(&last_session_path(device_id, user)).ok()
+}
+
+/// Packages that were removed in the snapshot but are enabled again (e.g. after an OTA)
+pub fn reenabled_packages(snapshot: &Snapshot, packages: &[PackageRow]) -> Vec<usize> {
+    let (changes, _) = diff_snapshot(snapshot, packages);
+    let mut reenabled: Vec<usize> = changes
+        .into_iter()
+        .filter(|(i, old_state)| {
+            packages[*i].state == PackageState::Enabled
+                && matches!(
+                    old_state,
+                    Some(PackageState::Disabled | PackageState::Uninstalled)
+                )
+        })
+        .map(|(i, _)| i)
+        .collect();
+    reenabled.sort_unstable();
+    reenabled
+}
+
+/// Differences between a snapshot and the current packages:
+/// - the packages that changed, with their state in the snapshot (`None` if they were not there)
+/// - the number of packages of the snapshot that are not on the device anymore
+pub fn diff_snapshot(
+    snapshot: &Snapshot,
+    packages: &[PackageRow],
+) -> (HashMap<usize, Option<PackageState>>, usize) {
+    let old_states: HashMap<&str, PackageState> = snapshot
+        .packages
+        .iter()
+        .map(|p| (p.name.as_str(), p.state))
+        .collect();
+
+    let changes = packages
+        .iter()
+        .enumerate()
+        .filter_map(|(i, p)| match old_states.get(p.name.as_str()) {
+            Some(state) if *state == p.state => None,
+            old_state => Some((i, old_state.copied())),
+        })
+        .collect();
+
+    let removed = snapshot
+        .packages
+        .iter()
+        .filter(|old| !packages.iter().any(|p| p.name == old.name))
+        .count();
+
+    (changes, removed)
+}
+
+/// Packages whose state differs on another device, with the state they have there.
+/// Packages missing from one of the devices or in an unknown state are left out.
+pub fn sync_changes(source: &[PackageRow], target: &[PackageRow]) -> Vec<(usize, PackageState)> {
+    let snapshot = Snapshot {
+        user_id: 0,
+        packages: source.iter().map(CorePackage::from).collect(),
+    };
+    let (changes, _) = diff_snapshot(&snapshot, target);
+    let mut changes: Vec<(usize, PackageState)> = changes
+        .into_iter()
+        .filter_map(|(i, state)| Some((i, state?)))
+        .filter(|(i, state)| {
+            *state != PackageState::Unknown && target[*i].state != PackageState::Unknown
+        })
+        .collect();
+    changes.sort_unstable_by_key(|(i, _)| *i);
+    changes
+}
@@ -7,12 +7,47 @@ use retry::{delay::Fixed, retry, OperationResult};
 use serde::{Deserialize, Serialize};
 use static_init::dynamic;
 use std::collections::HashSet;
-use std::env;
 use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
 
 #[cfg(target_os = "windows")]
 use std::os::windows::process::CommandExt;
 
+/// Set from the general settings (see `GeneralSettings::apply_logging`)
+pub static LOG_ADB_COMMANDS: AtomicBool = AtomicBool::new(false);
+
+/// The adb server is restarted at most once per bulk action (see `allow_adb_restart`)
+static ADB_RESTARTED: AtomicBool = AtomicBool::new(false);
+
+/// Errors of adb itself, not of the command it was running
+const ADB_FAILURES: [&str; 5] = [
+    "cannot connect to daemon",
+    "daemon not running",
+    "device offline",
+    "protocol fault",
+    "error: closed",
+];
+
+/// Errors of Android 13+ refusing to let the shell change some system apps
+const OS_RESTRICTIONS: [&str; 3] = [
+    "Shell cannot change component state",
+    "Cannot disable a protected package",
+    "DELETE_FAILED_USER_RESTRICTED",
+];
+
+/// Printed when Android 13+ refused a disable and the package was uninstalled instead
+/// (see `action_handler`)
+pub const UNINSTALL_FALLBACK: &str = "uad:uninstalled-instead";
+
+/// Errors of packages that are active device admins (or protected by a device policy)
+const DEVICE_ADMIN_ERRORS: [&str; 4] = [
+    "DELETE_FAILED_DEVICE_POLICY_MANAGER",
+    "DELETE_FAILED_OWNER_BLOCKED",
+    "is an active device admin",
+    "Cannot uninstall a protected package",
+];
+
 #[dynamic]
 static RE: Regex = Regex::new(r"\n(\S+)\s+device").unwrap();
 
@@ -22,6 +57,7 @@ pub struct Phone {
     pub android_sdk: u8,
     pub user_list: Vec<User>,
     pub adb_id: String,
+    pub root: RootEnvironment,
 }
 
 impl Default for Phone {
@@ -31,34 +67,253 @@ impl Default for Phone {
             android_sdk: 0,
             user_list: vec![],
             adb_id: "".to_string(),
+            root: RootEnvironment::default(),
+        }
+    }
+}
+
+impl Phone {
+    pub fn connection(&self) -> Connection {
+        parse_connection(&self.adb_id)
+    }
+
+    pub fn short_serial(&self) -> String {
+        short_serial(&self.adb_id)
+    }
+}
+
+/// Wireless connections can drop at any time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Connection {
+    Usb,
+    Tcp,
+}
+
+impl std::fmt::Display for Connection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Usb => write!(f, "USB"),
+            Self::Tcp => write!(f, "Wi-Fi"),
         }
     }
 }
 
+/// Devices connected with `adb connect` have an `ip:port` serial, and
+/// devices paired over Wi-Fi (Android 11+) an `adb-<id>._adb-tls-connect._tcp` one
+pub fn parse_connection(serial: &str) -> Connection {
+    let is_ip_port = serial.rsplit_once(':').map_or(false, |(host, port)| {
+        !host.is_empty() && port.parse::<u16>().is_ok()
+    });
+    if is_ip_port || serial.ends_with("._tcp") || serial.ends_with("._tcp.") {
+        Connection::Tcp
+    } else {
+        Connection::Usb
+    }
+}
+
+/// Enough of a serial to tell apart devices of the same model: the end of a USB serial,
+/// the address of a device connected with `adb connect`, the end of the id of a paired one
+pub fn short_serial(serial: &str) -> String {
+    let id = match serial
+        .strip_prefix("adb-")
+        .and_then(|s| s.split_once("._adb-tls"))
+    {
+        // adb-<serial>-<random suffix>
+        Some((id, _)) => id.rsplit_once('-').map_or(id, |(serial, _)| serial),
+        None if parse_connection(serial) == Connection::Tcp => return serial.to_string(),
+        None => serial,
+    };
+    let chars: Vec<char> = id.chars().collect();
+    chars[chars.len().saturating_sub(4)..].iter().collect()
+}
+
+/// Only for TCP/IP devices
+pub fn disconnect_device(serial: &str) -> Result<String, String> {
+    adb_file_command("", &["disconnect", serial])
+}
+
+/// Device advertising wireless debugging on the local network (Android 11+)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MdnsService {
+    pub name: String,
+    pub address: String, // host:port
+    pub pairing: bool,   // waiting to be paired with a code rather than connectable
+}
+
+/// Devices found with `adb mdns services`.
+/// Older adb versions don't know the command: an error is returned.
+pub fn discover_mdns_services() -> Result<Vec<MdnsService>, String> {
+    let output = adb_file_command("", &["mdns", "services"])?;
+    Ok(parse_mdns_services(&output))
+}
+
+/// Parses `<name>\t<_adb-tls-connect._tcp.|_adb-tls-pairing._tcp.>\t<host:port>` lines
+pub fn parse_mdns_services(output: &str) -> Vec<MdnsService> {
+    output
+        .lines()
+        .filter_map(|l| {
+            let mut fields = l.split_whitespace();
+            let (name, kind, address) = (fields.next()?, fields.next()?, fields.next()?);
+            if !kind.starts_with("_adb") || !address.contains(':') {
+                return None;
+            }
+            Some(MdnsService {
+                name: name.to_string(),
+                address: address.to_string(),
+                pairing: kind.contains("pairing"),
+            })
+        })
+        .collect()
+}
+
+/// `adb connect` exits successfully even when it couldn't connect
+pub fn connect_device(address: &str) -> Result<String, String> {
+    let output = adb_file_command("", &["connect", address])?;
+    if output.contains("connected to") {
+        Ok(output)
+    } else {
+        Err(output)
+    }
+}
+
+pub fn pair_device(address: &str, code: &str) -> Result<String, String> {
+    let output = adb_file_command("", &["pair", address, code])?;
+    if output.contains("Successfully paired") {
+        Ok(output)
+    } else {
+        Err(output)
+    }
+}
+
 impl std::fmt::Display for Phone {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", self.model,)
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Copy)]
+/// Root solution found on the device.
+/// UAD doesn't use root: this only tells what to expect from the removals.
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub enum RootEnvironment {
+    #[default]
+    None,
+    Su,             // rooted, with an unknown solution
+    Magisk(String), // version
+}
+
+impl std::fmt::Display for RootEnvironment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::None => write!(f, "Not rooted"),
+            Self::Su => write!(f, "Rooted (su)"),
+            Self::Magisk(version) => write!(f, "Rooted with Magisk {}", version),
+        }
+    }
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Eq, Copy)]
 pub struct User {
     pub id: u16,
     pub index: usize,
+    pub kind: UserKind,
+    pub paused: bool, // quiet mode (work profile turned off), disabled or stopped profile
 }
 
 impl std::fmt::Display for User {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "user {}", self.id)
+        write!(f, "user {} — {}", self.id, self.kind)?;
+        if self.paused {
+            write!(f, " (paused)")?;
+        }
+        Ok(())
     }
 }
 
-pub fn adb_shell_command(shell: bool, args: &str) -> Result<String, String> {
-    let adb_command = match shell {
-        true => vec!["shell", args],
-        false => vec![args],
-    };
+#[derive(Default, Debug, Clone, PartialEq, Eq, Copy)]
+pub enum UserKind {
+    #[default]
+    Owner,
+    Secondary,
+    WorkProfile,
+    Guest,
+    Restricted,
+}
+
+impl UserKind {
+    // https://android.googlesource.com/platform/frameworks/base/+/refs/heads/master/core/java/android/content/pm/UserInfo.java
+    const FLAG_PRIMARY: u32 = 0x1;
+    const FLAG_GUEST: u32 = 0x4;
+    const FLAG_RESTRICTED: u32 = 0x8;
+    const FLAG_MANAGED_PROFILE: u32 = 0x20;
+    const FLAG_DISABLED: u32 = 0x40;
+    const FLAG_QUIET_MODE: u32 = 0x80;
+
+    fn from_flags(id: u16, flags: Option<u32>) -> Self {
+        match flags {
+            Some(f) if f & Self::FLAG_MANAGED_PROFILE != 0 => Self::WorkProfile,
+            Some(f) if f & Self::FLAG_GUEST != 0 => Self::Guest,
+            Some(f) if f & Self::FLAG_RESTRICTED != 0 => Self::Restricted,
+            Some(f) if f & Self::FLAG_PRIMARY != 0 => Self::Owner,
+            _ if id == 0 => Self::Owner,
+            _ => Self::Secondary,
+        }
+    }
 
+    /// Commands on such a user fail or don't do anything until it is turned on.
+    /// Stopped secondary users are fine: only stopped profiles are considered paused.
+    fn paused(self, flags: Option<u32>, running: Option<bool>) -> bool {
+        let turned_off = flags.map_or(false, |f| {
+            f & (Self::FLAG_DISABLED | Self::FLAG_QUIET_MODE) != 0
+        });
+        turned_off || (self == Self::WorkProfile && running == Some(false))
+    }
+}
+
+impl std::fmt::Display for UserKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let kind = match self {
+            Self::Owner => "Owner",
+            Self::Secondary => "Secondary user",
+            Self::WorkProfile => "Work profile",
+            Self::Guest => "Guest",
+            Self::Restricted => "Restricted profile",
+        };
+        write!(f, "{}", kind)
+    }
+}
+
+/// Runs an adb command on the device with the `serial` serial number.
+/// An empty `serial` lets adb pick the device (only works with a single device).
+pub fn adb_shell_command(serial: &str, shell: bool, args: &str) -> Result<String, String> {
+    adb_command(serial, shell, args).map_err(|(_, err)| err)
+}
+
+/// Same as [`adb_shell_command`] but the error also carries the exit code of adb
+/// (`None` if adb could not be run)
+fn adb_command(serial: &str, shell: bool, args: &str) -> Result<String, (Option<i32>, String)> {
+    let mut adb_command = vec![];
+    if !serial.is_empty() {
+        adb_command.extend(["-s", serial]);
+    }
+    if shell {
+        adb_command.push("shell");
+    }
+    adb_command.push(args);
+    run_adb(&adb_command)
+}
+
+/// Runs a non-shell adb command whose arguments can't be given as a single string
+/// (e.g. paths given to `pull` or `install-multiple`)
+pub fn adb_file_command(serial: &str, args: &[&str]) -> Result<String, String> {
+    let mut adb_command = vec![];
+    if !serial.is_empty() {
+        adb_command.extend(["-s", serial]);
+    }
+    adb_command.extend(args);
+    run_adb(&adb_command).map_err(|(_, err)| err)
+}
+
+fn run_adb(adb_command: &[&str]) -> Result<String, (Option<i32>, String)> {
     #[cfg(target_os = "windows")]
     let output = Command::new("adb")
         .args(adb_command)
@@ -74,7 +329,7 @@ pub fn adb_shell_command(shell: bool, args: &str) -> Result<String, String> {
     match output {
         Err(e) => {
             error!("ADB: {}", e);
-            Err("ADB was not found".to_string())
+            Err((None, "ADB was not found".to_string()))
         }
         Ok(o) => {
             if !o.status.success() {
@@ -83,7 +338,7 @@ pub fn adb_shell_command(shell: bool, args: &str) -> Result<String, String> {
 
                 // ADB does really weird things. Some errors are not redirected to stderr
                 let err = if stdout.is_empty() { stderr } else { stdout };
-                Err(err)
+                Err((o.status.code(), err))
             } else {
                 Ok(String::from_utf8(o.stdout).unwrap().trim_end().to_string())
             }
@@ -91,21 +346,98 @@ pub fn adb_shell_command(shell: bool, args: &str) -> Result<String, String> {
     }
 }
 
+/// Oldest adb known to support everything UAD does (platform-tools 28)
+const MIN_ADB_VERSION: (u32, u32, u32) = (1, 0, 40);
+
+/// Checks the adb binary UAD runs and lists the devices it sees, for troubleshooting
+pub async fn test_adb() -> String {
+    let version = match run_adb(&["version"]) {
+        Ok(output) => output,
+        Err((_, e)) => return format!("adb doesn't work: {}", e),
+    };
+    let mut report = match parse_adb_version(&version) {
+        Some(v) if v < MIN_ADB_VERSION => format!(
+            "adb {}.{}.{} is too old (at least {}.{}.{} needed): update the platform-tools",
+            v.0, v.1, v.2, MIN_ADB_VERSION.0, MIN_ADB_VERSION.1, MIN_ADB_VERSION.2
+        ),
+        Some(v) => format!("adb {}.{}.{} works", v.0, v.1, v.2),
+        None => format!("Unknown adb version:\n{}", version),
+    };
+    match run_adb(&["devices", "-l"]) {
+        Ok(devices) => {
+            report.push('\n');
+            report.push_str(&devices);
+        }
+        Err((_, e)) => report.push_str(&format!("\nCould not list the devices: {}", e)),
+    }
+    report
+}
+
+/// `Android Debug Bridge version 1.0.41` -> (1, 0, 41)
+pub fn parse_adb_version(output: &str) -> Option<(u32, u32, u32)> {
+    let version = output
+        .lines()
+        .find_map(|l| l.trim().strip_prefix("Android Debug Bridge version "))?;
+    let mut numbers = version.trim().split('.').map(|n| n.parse::<u32>().ok());
+    Some((numbers.next()??, numbers.next()??, numbers.next()??))
+}
+
 #[derive(Debug, Clone)]
 pub enum CommandType {
     PackageManager(PackageInfo),
     Shell,
 }
+
+/// Failed adb command
+#[derive(Debug, Clone)]
+pub struct AdbError {
+    pub command_type: CommandType,
+    pub command: String,
+    /// `None` if adb could not be run or if it reported a success despite the error
+    pub exit_code: Option<i32>,
+    pub stderr: String,
+}
+
+impl std::fmt::Display for AdbError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.exit_code {
+            Some(code) => write!(f, "{} (exit code {}): {}", self.command, code, self.stderr),
+            None => write!(f, "{}: {}", self.command, self.stderr),
+        }
+    }
+}
+
 pub async fn perform_adb_commands(
     action: String,
     command_type: CommandType,
-) -> Result<CommandType, ()> {
+    serial: String,
+) -> Result<CommandType, AdbError> {
     let label = match command_type {
         CommandType::PackageManager(ref p) => p.removal.to_string(),
         CommandType::Shell => "Shell".to_string(),
     };
 
-    match adb_shell_command(true, &action) {
+    if LOG_ADB_COMMANDS.load(Ordering::Relaxed) {
+        if serial.is_empty() {
+            debug!("[{}] adb shell {}", label, action);
+        } else {
+            debug!("[{}] adb -s {} shell {}", label, serial, action);
+        }
+    }
+
+    let mut result = adb_command(&serial, true, &action);
+    if let Err((_, err)) = &result {
+        if ADB_FAILURES.iter().any(|e| err.contains(e)) {
+            // Only the first failing command restarts adb, the others just retry
+            if !ADB_RESTARTED.swap(true, Ordering::SeqCst) {
+                restart_adb_server(&serial, err);
+            }
+            warn!("[{}] Retrying {} after an adb failure", label, action);
+            result = adb_command(&serial, true, &action);
+        }
+    }
+
+    match result {
         Ok(o) => {
             // On old devices, adb commands can return the '0' exit code even if there
             // is an error. On Android 4.4, ADB doesn't check if the package exists.
@@ -114,52 +446,205 @@ pub async fn perform_adb_commands(
             // the output.
             if ["Error", "Failure"].iter().any(|&e| o.contains(e)) {
                 error!("[{}] {} -> {}", label, action, o);
-                Err(())
+                Err(AdbError {
+                    command_type,
+                    command: action,
+                    exit_code: None,
+                    stderr: o,
+                })
             } else {
                 info!("[{}] {} -> {}", label, action, o);
-                Ok(command_type)
+                match command_type {
+                    CommandType::PackageManager(mut p) if o.contains(UNINSTALL_FALLBACK) => {
+                        warn!("[{}] {}: uninstalled instead of disabled", label, action);
+                        p.uninstalled_instead = true;
+                        Ok(CommandType::PackageManager(p))
+                    }
+                    command_type => Ok(command_type),
+                }
             }
         }
-        Err(err) => {
+        Err((exit_code, err)) => {
             if !err.contains("[not installed for") {
                 error!("[{}] {} -> {}", label, action, err);
             }
-            Err(())
+            let err = if OS_RESTRICTIONS.iter().any(|e| err.contains(e)) {
+                format!(
+                    "Android doesn't allow adb to change this system package \
+                    (restriction of recent Android versions): {}",
+                    err
+                )
+            } else {
+                err
+            };
+            Err(AdbError {
+                command_type,
+                command: action,
+                exit_code,
+                stderr: err,
+            })
         }
     }
 }
 
-pub fn list_all_system_packages(user_id: Option<&User>) -> String {
+/// Whether a package command failed because the package is protected by a device admin
+pub fn is_device_admin_error(stderr: &str) -> bool {
+    DEVICE_ADMIN_ERRORS.iter().any(|e| stderr.contains(e))
+}
+
+/// Allows the next adb failure to restart the adb server. Called when a bulk action starts.
+pub fn allow_adb_restart() {
+    ADB_RESTARTED.store(false, Ordering::SeqCst);
+}
+
+fn restart_adb_server(serial: &str, err: &str) {
+    warn!("adb failed ({}): restarting the adb server", err);
+    if let Err(e) = adb_shell_command("", false, "start-server") {
+        error!("Could not restart the adb server: {}", e);
+        return;
+    }
+    if err.contains("offline") {
+        let _ = adb_shell_command(serial, false, "reconnect");
+    }
+    // The device takes a moment to show up again
+    match retry(
+        Fixed::from_millis(500).take(20),
+        || match adb_shell_command(serial, false, "get-state") {
+            Ok(state) if state.trim() == "device" => OperationResult::Ok(()),
+            _ => OperationResult::Retry(()),
+        },
+    ) {
+        Ok(_) => info!("adb server restarted, device {} is back", serial),
+        Err(_) => error!("Device {} did not come back after restarting adb", serial),
+    }
+}
+
+/// A device that just connected is "offline" for a few seconds before it answers
+pub const CONNECT_WAIT: Duration = Duration::from_secs(8);
+/// A rebooting device is gone until it booted again
+pub const REBOOT_WAIT: Duration = Duration::from_secs(180);
+
+/// Waits for the device to be ready, twice as long after every attempt (4 seconds at most),
+/// and gives up once the next delay would go over `budget`
+pub fn wait_for_device(serial: &str, budget: Duration) -> Result<(), String> {
+    wait_until_ready(backoff(budget), || {
+        adb_shell_command(serial, false, "get-state")
+    })
+}
+
+fn backoff(budget: Duration) -> impl Iterator<Item = Duration> {
+    let mut total = Duration::ZERO;
+    (0..)
+        .map(|i: u32| Duration::from_millis(250 << i.min(4)))
+        .take_while(move |delay| {
+            total += *delay;
+            total <= budget
+        })
+}
+
+fn wait_until_ready(
+    delays: impl IntoIterator<Item = Duration>,
+    mut get_state: impl FnMut() -> Result<String, String>,
+) -> Result<(), String> {
+    let mut attempt = 0;
+    retry(delays, || {
+        attempt += 1;
+        let err = match get_state() {
+            Ok(state) if state.trim() == "device" => return OperationResult::Ok(()),
+            Ok(state) => format!("device {}", state.trim()),
+            Err(err) => err,
+        };
+        warn!("Device not ready (attempt {}): {}", attempt, err);
+        OperationResult::Retry(err)
+    })
+    .map_err(|e| e.to_string())
+}
+
+/// Extracts package names from the output of `pm list packages`
+pub fn parse_package_list(output: &str) -> impl Iterator<Item = &str> {
+    output
+        .lines()
+        .map(|l| l.trim())
+        .map(|l| l.strip_prefix("package:").unwrap_or(l))
+        .filter(|l| !l.is_empty())
+}
+
+/// System packages and installed apps alike: sideloaded or OEM apps missing
+/// from the UAD lists are still worth debloating.
+pub fn list_all_packages(user_id: Option<&User>, serial: &str) -> String {
     let action = match user_id {
-        Some(user_id) => format!("pm list packages -s -u --user {}", user_id.id),
-        None => "pm list packages -s -u".to_string(),
+        Some(user_id) => format!("pm list packages -u --user {}", user_id.id),
+        None => "pm list packages -u".to_string(),
     };
 
-    adb_shell_command(true, &action)
-        .unwrap_or_else(|_| "".to_string())
-        .replace("package:", "")
+    adb_shell_command(serial, true, &action).unwrap_or_else(|_| "".to_string())
 }
 
-pub fn hashset_system_packages(state: PackageState, user_id: Option<&User>) -> HashSet<String> {
+/// Apps installed on top of the system. Once uninstalled for every user, they are
+/// gone from the device and can't be restored.
+pub fn hashset_third_party_packages(user_id: Option<&User>, serial: &str) -> HashSet<String> {
+    let action = match user_id {
+        Some(user_id) => format!("pm list packages -3 -u --user {}", user_id.id),
+        None => "pm list packages -3 -u".to_string(),
+    };
+
+    parse_package_list(&adb_shell_command(serial, true, &action).unwrap_or_default())
+        .map(String::from)
+        .collect()
+}
+
+pub fn hashset_packages(
+    state: PackageState,
+    user_id: Option<&User>,
+    serial: &str,
+) -> HashSet<String> {
     let user = match user_id {
         Some(user_id) => format!(" --user {}", user_id.id),
         None => "".to_string(),
     };
 
     let action = match state {
-        PackageState::Enabled => format!("pm list packages -s -e{}", user),
-        PackageState::Disabled => format!("pm list package -s -d{}", user),
+        PackageState::Enabled => format!("pm list packages -e{}", user),
+        PackageState::Disabled => format!("pm list package -d{}", user),
         _ => "".to_string(), // You probably don't need to use this function for anything else
     };
 
-    adb_shell_command(true, &action)
-        .unwrap_or_default()
-        .replace("package:", "")
-        .lines()
+    parse_package_list(&adb_shell_command(serial, true, &action).unwrap_or_default())
         .map(String::from)
         .collect()
 }
 
+/// State of a single package, straight from the device.
+/// `None` if the device didn't answer or doesn't know the package.
+pub fn get_package_state(
+    package: &str,
+    user_id: Option<&User>,
+    serial: &str,
+) -> Option<PackageState> {
+    let user = match user_id {
+        Some(user_id) => format!(" --user {}", user_id.id),
+        None => "".to_string(),
+    };
+    // The package name is only a filter: `pm` lists every package containing it
+    let is_listed = |flag: &str| {
+        adb_shell_command(
+            serial,
+            true,
+            &format!("pm list packages {}{} {}", flag, user, package),
+        )
+        .ok()
+        .map(|output| parse_package_list(&output).any(|p| p == package))
+    };
+
+    match (is_listed("-e")?, is_listed("-d")?) {
+        (true, true) => Some(PackageState::Unknown),
+        (true, false) => Some(PackageState::Enabled),
+        (false, true) => Some(PackageState::Disabled),
+        (false, false) if is_listed("-u")? => Some(PackageState::Uninstalled),
+        (false, false) => None,
+    }
+}
+
 // Minimum information for processing adb commands
 #[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
 pub struct CorePackage {
@@ -230,9 +715,12 @@ pub fn apply_pkg_state_commands(
         .collect()
 }
 
+/// `disable`: disable an enabled package instead of uninstalling it
+/// (see `DeviceSettings::disables`)
 pub fn action_handler(
     selected_user: &User,
     package: &CorePackage,
+    disable: bool,
     phone: &Phone,
     settings: &DeviceSettings,
 ) -> Vec<(Option<usize>, String)> {
@@ -240,7 +728,7 @@ pub fn action_handler(
     // ALWAYS PUT THE COMMAND THAT CHANGES THE PACKAGE STATE FIRST!
     let commands = match package.state {
         PackageState::Enabled => {
-            let commands = match settings.disable_mode {
+            let commands = match disable {
                 true => vec!["pm disable-user", "am force-stop", "pm clear"],
                 false => vec!["pm uninstall"],
             };
@@ -267,14 +755,71 @@ pub fn action_handler(
             _ => vec!["pm enable"],
         },
         PackageState::All => vec![], // This can't happen (like... never)
+        PackageState::Unknown => vec![], // Impossible action already prevented by the GUI
     };
 
+    let actions = user_requests(commands, &package.name, selected_user, phone, settings);
+
+    // Android 13+ refuses to disable some system apps from the shell:
+    // they are uninstalled for the user instead, which the output tells
+    if phone.android_sdk >= 33 {
+        actions
+            .into_iter()
+            .map(
+                |(user, command)| match command.strip_prefix("pm disable-user") {
+                    Some(args) => (
+                        user,
+                        format!(
+                            "{} || (pm uninstall{} && echo {})",
+                            command, args, UNINSTALL_FALLBACK
+                        ),
+                    ),
+                    None => (user, command),
+                },
+            )
+            .collect()
+    } else {
+        actions
+    }
+}
+
+/// `pm suspend` appeared in Android 9 (Pie)
+pub const SUSPEND_MIN_SDK: u8 = 28;
+
+/// Suspends or unsuspends a package for the same users as `action_handler`.
+/// A suspended package stays installed and enabled but can't run until unsuspended.
+pub fn suspend_handler(
+    selected_user: &User,
+    package: &str,
+    suspend: bool,
+    phone: &Phone,
+    settings: &DeviceSettings,
+) -> Vec<(Option<usize>, String)> {
+    if phone.android_sdk < SUSPEND_MIN_SDK {
+        return vec![]; // Impossible action already prevented by the GUI
+    }
+    let command = if suspend {
+        "pm suspend"
+    } else {
+        "pm unsuspend"
+    };
+    user_requests(vec![command], package, selected_user, phone, settings)
+}
+
+/// Commands for the selected user, or every user in multi-user mode
+fn user_requests(
+    commands: Vec<&str>,
+    package: &str,
+    selected_user: &User,
+    phone: &Phone,
+    settings: &DeviceSettings,
+) -> Vec<(Option<usize>, String)> {
     if phone.android_sdk < 21 {
-        request_builder(commands, &package.name, &[])
+        request_builder(commands, package, &[])
     } else if settings.multi_user_mode {
-        request_builder(commands, &package.name, &phone.user_list)
+        request_builder(commands, package, &phone.user_list)
     } else {
-        request_builder(commands, &package.name, &[*selected_user])
+        request_builder(commands, package, &[*selected_user])
     }
 }
 
@@ -300,8 +845,371 @@ pub fn request_builder(
     }
 }
 
-pub fn get_phone_model() -> String {
-    match adb_shell_command(true, "getprop ro.product.model") {
+/// Free space (in KB) of the `/data` partition, where removed apps and their data live
+pub fn get_free_storage(serial: &str) -> Option<u64> {
+    let output = adb_shell_command(serial, true, "df /data").ok()?;
+    parse_free_storage(&output)
+}
+
+/// Extracts the `Available` column from the output of `df /data`.
+/// Old toolbox versions print human-readable sizes: they are not supported.
+pub fn parse_free_storage(output: &str) -> Option<u64> {
+    let mut lines = output.lines();
+    let header = lines.next()?;
+    if !header.contains("1K-blocks") {
+        return None;
+    }
+    lines
+        .find(|l| l.trim_end().ends_with("/data"))?
+        .split_whitespace()
+        .nth(3)?
+        .parse()
+        .ok()
+}
+
+/// Why the device may not be ready for package operations
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeviceLock {
+    Unlocked,
+    /// The lock screen is shown
+    LockScreen,
+    /// Not unlocked since the last boot: with file-based encryption, the data of the apps
+    /// is still encrypted and some `pm` commands silently fail
+    NotUnlockedSinceBoot,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Battery {
+    pub level: u8, // percent
+    pub charging: bool,
+}
+
+pub fn get_battery(serial: &str) -> Option<Battery> {
+    let output = adb_shell_command(serial, true, "dumpsys battery").ok()?;
+    parse_battery(&output)
+}
+
+/// `level` is out of `scale` (100 in practice), `status` is 2 when charging and 5 when full
+pub fn parse_battery(output: &str) -> Option<Battery> {
+    let field = |name: &str| {
+        output
+            .lines()
+            .find_map(|l| l.trim().strip_prefix(name))
+            .and_then(|value| value.trim().parse::<u32>().ok())
+    };
+    let level = field("level:")?;
+    let scale = field("scale:").filter(|s| *s > 0).unwrap_or(100);
+    Some(Battery {
+        level: (level * 100 / scale).min(100) as u8,
+        charging: matches!(field("status:"), Some(2 | 5)),
+    })
+}
+
+pub fn get_device_lock(serial: &str) -> DeviceLock {
+    // Only set on devices using file-based encryption
+    if let Ok(ce_available) = adb_shell_command(serial, true, "getprop sys.user.0.ce_available") {
+        if ce_available.trim() == "false" {
+            return DeviceLock::NotUnlockedSinceBoot;
+        }
+    }
+    match adb_shell_command(serial, true, "dumpsys window policy") {
+        Ok(output) if is_lock_screen_shown(&output) => DeviceLock::LockScreen,
+        _ => DeviceLock::Unlocked,
+    }
+}
+
+/// The wording of `dumpsys window policy` changed across Android versions
+pub fn is_lock_screen_shown(output: &str) -> bool {
+    [
+        "mShowingLockscreen=true",
+        "mDreamingLockscreen=true",
+        "isKeyguardShowing=true",
+        "mIsShowing=true",
+    ]
+    .iter()
+    .any(|marker| output.contains(marker))
+}
+
+/// Paths of the APK files of a package on the device.
+/// Apps distributed as app bundles have a base APK and several split APKs.
+pub fn get_apk_paths(package: &str, serial: &str) -> Vec<String> {
+    match adb_shell_command(serial, true, &format!("pm path {}", package)) {
+        Ok(output) => parse_apk_paths(&output),
+        Err(e) => {
+            warn!("Could not get the APK paths of {}: {}", package, e);
+            vec![]
+        }
+    }
+}
+
+pub fn parse_apk_paths(output: &str) -> Vec<String> {
+    output
+        .lines()
+        .filter_map(|l| l.trim().strip_prefix("package:"))
+        .map(|p| p.to_string())
+        .collect()
+}
+
+/// Who disabled a package. `pm list packages -d` lists them all alike.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DisabledBy {
+    User,   // COMPONENT_ENABLED_STATE_DISABLED_USER: UAD, or the settings of the device
+    System, // COMPONENT_ENABLED_STATE_DISABLED(_UNTIL_USED): the OS or the app itself
+}
+
+impl std::fmt::Display for DisabledBy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::User => write!(f, "Disabled by user"),
+            Self::System => write!(f, "Disabled by system"),
+        }
+    }
+}
+
+pub fn get_disabled_by(package: &str, user_id: Option<&User>, serial: &str) -> Option<DisabledBy> {
+    let output = adb_shell_command(serial, true, &format!("dumpsys package {}", package)).ok()?;
+    parse_disabled_by(&output, user_id.map_or(0, |u| u.id))
+}
+
+/// The enabled setting is the `enabled=<n>` field of the `User <id>:` line
+pub fn parse_disabled_by(output: &str, user_id: u16) -> Option<DisabledBy> {
+    let prefix = format!("User {}:", user_id);
+    let setting = output
+        .lines()
+        .find(|l| l.trim_start().starts_with(&prefix))?
+        .split_whitespace()
+        .find_map(|field| field.strip_prefix("enabled="))?
+        .parse::<u8>()
+        .ok()?;
+    match setting {
+        2 | 4 => Some(DisabledBy::System),
+        3 => Some(DisabledBy::User),
+        _ => None, // default or enabled
+    }
+}
+
+pub fn get_suspended(package: &str, user_id: Option<&User>, serial: &str) -> Option<bool> {
+    let output = adb_shell_command(serial, true, &format!("dumpsys package {}", package)).ok()?;
+    parse_suspended(&output, user_id.map_or(0, |u| u.id))
+}
+
+/// The `suspended=<bool>` field of the `User <id>:` line (Android 9+)
+pub fn parse_suspended(output: &str, user_id: u16) -> Option<bool> {
+    let prefix = format!("User {}:", user_id);
+    output
+        .lines()
+        .find(|l| l.trim_start().starts_with(&prefix))?
+        .split_whitespace()
+        .find_map(|field| field.strip_prefix("suspended="))?
+        .parse::<bool>()
+        .ok()
+}
+
+/// Install dates of a package, as printed by `dumpsys package`.
+/// The `yyyy-MM-dd HH:mm:ss` format makes them sortable as strings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackageTimestamps {
+    pub first_install: String,
+    pub last_update: String,
+}
+
+pub fn get_package_timestamps(package: &str, serial: &str) -> Option<PackageTimestamps> {
+    let output = adb_shell_command(serial, true, &format!("dumpsys package {}", package)).ok()?;
+    parse_package_timestamps(&output)
+}
+
+pub fn parse_package_timestamps(output: &str) -> Option<PackageTimestamps> {
+    let field = |name: &str| {
+        output
+            .lines()
+            .find_map(|l| l.trim().strip_prefix(name))
+            .map(|value| value.trim().to_string())
+    };
+    Some(PackageTimestamps {
+        first_install: field("firstInstallTime=")?,
+        last_update: field("lastUpdateTime=")?,
+    })
+}
+
+/// Permissions giving access to private data or sensors
+/// (the "dangerous" protection level of Android)
+const SENSITIVE_PERMISSIONS: [&str; 27] = [
+    "ACCESS_BACKGROUND_LOCATION",
+    "ACCESS_COARSE_LOCATION",
+    "ACCESS_FINE_LOCATION",
+    "ACTIVITY_RECOGNITION",
+    "BODY_SENSORS",
+    "CALL_PHONE",
+    "CAMERA",
+    "GET_ACCOUNTS",
+    "NEARBY_WIFI_DEVICES",
+    "POST_NOTIFICATIONS",
+    "READ_CALENDAR",
+    "READ_CALL_LOG",
+    "READ_CONTACTS",
+    "READ_EXTERNAL_STORAGE",
+    "READ_MEDIA_AUDIO",
+    "READ_MEDIA_IMAGES",
+    "READ_MEDIA_VIDEO",
+    "READ_PHONE_NUMBERS",
+    "READ_PHONE_STATE",
+    "READ_SMS",
+    "RECEIVE_SMS",
+    "RECORD_AUDIO",
+    "SEND_SMS",
+    "WRITE_CALENDAR",
+    "WRITE_CALL_LOG",
+    "WRITE_CONTACTS",
+    "WRITE_EXTERNAL_STORAGE",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackagePermission {
+    pub name: String,
+    pub granted: bool, // for at least one user
+}
+
+impl PackagePermission {
+    pub fn is_sensitive(&self) -> bool {
+        self.name
+            .strip_prefix("android.permission.")
+            .map_or(false, |p| SENSITIVE_PERMISSIONS.contains(&p))
+    }
+}
+
+pub fn get_package_permissions(package: &str, serial: &str) -> Option<Vec<PackagePermission>> {
+    let output = adb_shell_command(serial, true, &format!("dumpsys package {}", package)).ok()?;
+    Some(parse_package_permissions(&output))
+}
+
+/// Requested permissions are listed under `requested permissions:`, grants
+/// (`<permission>: granted=true`) under the install and runtime permissions of each user
+pub fn parse_package_permissions(output: &str) -> Vec<PackagePermission> {
+    let indent = |l: &str| l.len() - l.trim_start().len();
+    let granted: HashSet<&str> = output
+        .lines()
+        .filter(|l| l.contains("granted=true"))
+        .filter_map(|l| l.trim().split(':').next())
+        .collect();
+
+    let mut lines = output.lines();
+    let section_indent = match lines.find(|l| l.trim() == "requested permissions:") {
+        Some(header) => indent(header),
+        None => return vec![],
+    };
+    lines
+        .take_while(|l| !l.trim().is_empty() && indent(l) > section_indent)
+        .filter_map(|l| l.trim().split(':').next())
+        .map(|name| PackagePermission {
+            name: name.to_string(),
+            granted: granted.contains(name),
+        })
+        .collect()
+}
+
+/// Where apps keep their data. Only the owner's directories are checked.
+const APP_DATA_DIRS: [&str; 2] = ["/data/data/", "/sdcard/Android/data/"];
+
+/// App data directory left behind by a package that isn't on the device anymore
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OrphanedDir {
+    pub path: String,
+    pub size_kb: u64,
+}
+
+/// Data directories without a matching package, biggest first. Needs root.
+pub async fn find_orphaned_dirs(serial: String) -> Result<Vec<OrphanedDir>, String> {
+    // Packages uninstalled for the user still count: their data may be in use by another user
+    let packages = adb_shell_command(&serial, true, "pm list packages -u")?;
+    let packages: HashSet<&str> = parse_package_list(&packages).collect();
+    let dirs = APP_DATA_DIRS
+        .iter()
+        .map(|dir| format!("{}*", dir))
+        .collect::<Vec<_>>()
+        .join(" ");
+    // `du` fails on unreadable files but still reports the rest
+    let output = adb_shell_command(
+        &serial,
+        true,
+        &format!("su -c 'du -sk {} 2>/dev/null; true'", dirs),
+    )?;
+    let mut orphans: Vec<OrphanedDir> = parse_dir_sizes(&output)
+        .into_iter()
+        .filter(|dir| {
+            let name = dir.path.rsplit('/').next().unwrap_or_default();
+            !packages.contains(name)
+        })
+        .collect();
+    orphans.sort_by(|a, b| b.size_kb.cmp(&a.size_kb));
+    Ok(orphans)
+}
+
+/// `du -sk` output: `<size in KB>\t<path>`, only for app data directories
+pub fn parse_dir_sizes(output: &str) -> Vec<OrphanedDir> {
+    output
+        .lines()
+        .filter_map(|l| {
+            let (size, path) = l.trim().split_once(char::is_whitespace)?;
+            let path = path.trim();
+            let size_kb = size.parse().ok()?;
+            is_app_data_dir(path).then(|| OrphanedDir {
+                path: path.to_string(),
+                size_kb,
+            })
+        })
+        .collect()
+}
+
+/// A direct child of an app data directory, with nothing that could escape it
+fn is_app_data_dir(path: &str) -> bool {
+    APP_DATA_DIRS.iter().any(|dir| {
+        path.strip_prefix(dir).map_or(false, |name| {
+            !name.is_empty()
+                && name != "."
+                && name != ".."
+                && name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '_')
+        })
+    })
+}
+
+/// Deletes the directories with root and returns how many were deleted.
+/// Anything that isn't an app data directory is refused.
+pub async fn delete_orphaned_dirs(serial: String, paths: Vec<String>) -> Result<usize, String> {
+    if let Some(path) = paths.iter().find(|p| !is_app_data_dir(p)) {
+        return Err(format!("{} is not an app data directory", path));
+    }
+    let mut deleted = 0;
+    for path in &paths {
+        adb_shell_command(&serial, true, &format!("su -c 'rm -rf {}'", path))?;
+        info!("[ORPHANED DATA] {} deleted", path);
+        deleted += 1;
+    }
+    Ok(deleted)
+}
+
+pub fn get_root_environment(serial: &str) -> RootEnvironment {
+    if let Some(version) = adb_shell_command(serial, true, "magisk -v")
+        .ok()
+        .and_then(|output| parse_magisk_version(&output))
+    {
+        return RootEnvironment::Magisk(version);
+    }
+    match adb_shell_command(serial, true, "which su") {
+        Ok(path) if !path.trim().is_empty() => RootEnvironment::Su,
+        _ => RootEnvironment::None,
+    }
+}
+
+/// `magisk -v` prints `<version>:MAGISK[:<flags>]`
+pub fn parse_magisk_version(output: &str) -> Option<String> {
+    let (version, kind) = output.trim().split_once(':')?;
+    kind.starts_with("MAGISK").then(|| version.to_string())
+}
+
+pub fn get_phone_model(serial: &str) -> String {
+    match adb_shell_command(serial, true, "getprop ro.product.model") {
         Ok(model) => model,
         Err(err) => {
             println!("ERROR: {}", err);
@@ -314,56 +1222,130 @@ pub fn get_phone_model() -> String {
     }
 }
 
-pub fn get_android_sdk() -> u8 {
-    match adb_shell_command(true, "getprop ro.build.version.sdk") {
+pub fn get_build_fingerprint(serial: &str) -> String {
+    adb_shell_command(serial, true, "getprop ro.build.fingerprint")
+        .unwrap_or_default()
+        .trim()
+        .to_string()
+}
+
+pub fn get_android_sdk(serial: &str) -> u8 {
+    match adb_shell_command(serial, true, "getprop ro.build.version.sdk") {
         Ok(sdk) => sdk.parse().unwrap(),
         Err(_) => 0,
     }
 }
 
-pub fn get_phone_brand() -> String {
+pub fn get_phone_brand(serial: &str) -> String {
     format!(
         "{} {}",
-        adb_shell_command(true, "getprop ro.product.brand")
+        adb_shell_command(serial, true, "getprop ro.product.brand")
             .unwrap_or_else(|_| "".to_string())
             .trim(),
-        get_phone_model()
+        get_phone_model(serial)
     )
 }
 
-pub fn get_user_list() -> Vec<User> {
+pub fn get_user_list(serial: &str) -> Vec<User> {
+    parse_user_list(&adb_shell_command(serial, true, "pm list users").unwrap_or_default())
+}
+
+/// Parses `UserInfo{<id>:<name>:<hex flags>} [running]` entries of `pm list users`.
+/// Some AOSP/headless builds list no user at all (or in another format):
+/// there is always at least the system user.
+pub fn parse_user_list(output: &str) -> Vec<User> {
     #[dynamic]
-    static RE: Regex = Regex::new(r"\{([0-9]+)").unwrap();
-    match adb_shell_command(true, "pm list users") {
-        Ok(users) => RE
-            .find_iter(&users)
-            .enumerate()
-            .map(|(i, u)| User {
-                id: u.as_str()[1..].parse().unwrap(),
+    static RE: Regex = Regex::new(r"\{([0-9]+)(?::[^:}]*:([0-9a-fA-F]+))?").unwrap();
+    // Older Android versions don't tell which users are running
+    let tells_running = output.contains("running");
+    let users: Vec<User> = RE
+        .captures_iter(output)
+        .filter_map(|u| {
+            let id = u[1].parse().ok()?;
+            let flags = u
+                .get(2)
+                .and_then(|f| u32::from_str_radix(f.as_str(), 16).ok());
+            let rest = output[u.get(0)?.end()..].lines().next().unwrap_or_default();
+            let running = tells_running.then(|| rest.contains("running"));
+            Some((id, flags, running))
+        })
+        .enumerate()
+        .map(|(i, (id, flags, running))| {
+            let kind = UserKind::from_flags(id, flags);
+            User {
+                id,
                 index: i,
-            })
-            .collect(),
-        Err(_) => vec![],
+                kind,
+                paused: kind.paused(flags, running),
+            }
+        })
+        .collect();
+
+    if users.is_empty() {
+        warn!("No user found in `pm list users`, assuming user 0");
+        vec![User::default()]
+    } else {
+        users
     }
 }
 
+/// Starts a paused profile (`am start-user`), so that commands on its packages work
+pub fn start_user(serial: &str, user: &User) -> Result<(), String> {
+    let output = adb_shell_command(serial, true, &format!("am start-user {}", user.id))?;
+    // `am` reports its errors on stdout
+    if output.contains("Success") {
+        Ok(())
+    } else {
+        Err(output.trim().to_string())
+    }
+}
+
+/// Devices plugged in but this computer isn't allowed to debug yet: waiting for the "Allow USB debugging" prompt to be accepted
+pub fn get_unauthorized_devices() -> Vec<String> {
+    adb_file_command("", &["devices", "-l"])
+        .map(|devices| parse_unauthorized_devices(&devices))
+        .unwrap_or_default()
+}
+
+/// Serials of the `<serial> unauthorized [usb:... transport_id:...]` lines of `adb devices -l`
+pub fn parse_unauthorized_devices(output: &str) -> Vec<String> {
+    output
+        .lines()
+        .skip(1) // List of devices attached
+        .filter_map(|l| {
+            let mut fields = l.split_whitespace();
+            match (fields.next(), fields.next()) {
+                (Some(serial), Some("unauthorized")) => Some(serial.to_string()),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// Replugging the device is often not enough for the authorization prompt to show up again
+pub fn restart_adb() -> Result<(), String> {
+    adb_shell_command("", false, "kill-server")?;
+    adb_shell_command("", false, "start-server").map(|_| ())
+}
+
 // getprop ro.serialno
 pub async fn get_devices_list() -> Vec<Phone> {
     match retry(
         Fixed::from_millis(500).take(120),
-        || match adb_shell_command(false, "devices") {
+        || match adb_shell_command("", false, "devices") {
             Ok(devices) => {
                 let mut device_list: Vec<Phone> = vec![];
                 if !RE.is_match(&devices) {
                     return OperationResult::Retry(vec![]);
                 }
                 for device in RE.captures_iter(&devices) {
-                    env::set_var("ANDROID_SERIAL", &device[1]);
+                    let serial = &device[1];
                     device_list.push(Phone {
-                        model: get_phone_brand(),
-                        android_sdk: get_android_sdk(),
-                        user_list: get_user_list(),
-                        adb_id: device[1].to_string(),
+                        model: get_phone_brand(serial),
+                        android_sdk: get_android_sdk(serial),
+                        user_list: get_user_list(serial),
+                        adb_id: serial.to_string(),
+                        root: get_root_environment(serial),
                     });
                 }
                 OperationResult::Ok(device_list)
@@ -379,3 +1361,318 @@ pub async fn get_devices_list() -> Vec<Phone> {
         Err(_) => vec![],
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn phone(android_sdk: u8) -> Phone {
+        Phone {
+            android_sdk,
+            user_list: vec![User::default()],
+            adb_id: "serial".to_string(),
+            ..Phone::default()
+        }
+    }
+
+    fn enabled_package() -> CorePackage {
+        CorePackage {
+            name: "com.example.app".to_string(),
+            state: PackageState::Enabled,
+        }
+    }
+
+    #[test]
+    fn test_disable_before_android_13() {
+        let actions = action_handler(
+            &User::default(),
+            &enabled_package(),
+            true,
+            &phone(32),
+            &DeviceSettings::default(),
+        );
+        assert_eq!(
+            actions[0],
+            (
+                Some(0),
+                "pm disable-user --user 0 com.example.app".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_disable_falls_back_to_uninstall_on_android_13() {
+        let actions = action_handler(
+            &User::default(),
+            &enabled_package(),
+            true,
+            &phone(33),
+            &DeviceSettings::default(),
+        );
+        assert_eq!(
+            actions[0],
+            (
+                Some(0),
+                "pm disable-user --user 0 com.example.app || \
+                    (pm uninstall --user 0 com.example.app && echo uad:uninstalled-instead)"
+                    .to_string()
+            )
+        );
+        // Only the command changing the state has a fallback
+        assert!(actions[1..].iter().all(|(_, c)| !c.contains("||")));
+    }
+
+    #[test]
+    fn test_uninstall_is_unchanged_on_android_13() {
+        let actions = action_handler(
+            &User::default(),
+            &enabled_package(),
+            false,
+            &phone(33),
+            &DeviceSettings::default(),
+        );
+        assert_eq!(
+            actions,
+            vec![(Some(0), "pm uninstall --user 0 com.example.app".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_parse_battery() {
+        let output = "Current Battery Service state:\n\
+            \x20 AC powered: false\n\
+            \x20 USB powered: true\n\
+            \x20 status: 3\n\
+            \x20 level: 15\n\
+            \x20 scale: 100\n";
+        assert_eq!(
+            parse_battery(output),
+            Some(Battery {
+                level: 15,
+                charging: false
+            })
+        );
+        assert_eq!(
+            parse_battery(&output.replace("status: 3", "status: 2")),
+            Some(Battery {
+                level: 15,
+                charging: true
+            })
+        );
+        assert_eq!(parse_battery("Can't find service: battery"), None);
+    }
+
+    #[test]
+    fn test_parse_dir_sizes() {
+        let output = "1234\t/data/data/com.android.chrome\n\
+            8\t/data/data/com.old.app\n\
+            5\t/data/data/\n\
+            12\t/sdcard/Android/data/com.old.app\n\
+            3\t/sdcard/Android/data/..\n\
+            7\t/data/local/tmp\n\
+            du: /data/data/x: Permission denied";
+        let paths: Vec<(String, u64)> = parse_dir_sizes(output)
+            .into_iter()
+            .map(|d| (d.path, d.size_kb))
+            .collect();
+        assert_eq!(
+            paths,
+            vec![
+                ("/data/data/com.android.chrome".to_string(), 1234),
+                ("/data/data/com.old.app".to_string(), 8),
+                ("/sdcard/Android/data/com.old.app".to_string(), 12),
+            ]
+        );
+        assert!(!is_app_data_dir("/data/data/com.old.app/../../system"));
+        assert!(!is_app_data_dir("/data/data/com.old.app; rm -rf /"));
+    }
+
+    #[test]
+    fn test_parse_adb_version() {
+        let output = "Android Debug Bridge version 1.0.41\n\
+            Version 34.0.4-10411341\n\
+            Installed as /usr/bin/adb";
+        assert_eq!(parse_adb_version(output), Some((1, 0, 41)));
+        assert!(parse_adb_version(output).unwrap() >= MIN_ADB_VERSION);
+        assert!(
+            parse_adb_version("Android Debug Bridge version 1.0.32").unwrap() < MIN_ADB_VERSION
+        );
+        assert_eq!(parse_adb_version("adb: command not found"), None);
+    }
+
+    #[test]
+    fn test_parse_disabled_by() {
+        let output = "Packages:\n\
+            \x20 Package [com.example.app] (5f1c2a3):\n\
+            \x20   User 0: ceDataInode=4242 installed=true hidden=false stopped=true enabled=3 instant=false\n\
+            \x20   User 10: ceDataInode=0 installed=true hidden=false stopped=true enabled=2 instant=false\n\
+            \x20   User 11: ceDataInode=0 installed=true hidden=false stopped=false enabled=0 instant=false\n";
+        assert_eq!(parse_disabled_by(output, 0), Some(DisabledBy::User));
+        assert_eq!(parse_disabled_by(output, 10), Some(DisabledBy::System));
+        assert_eq!(parse_disabled_by(output, 11), None);
+        assert_eq!(parse_disabled_by(output, 1), None);
+    }
+
+    #[test]
+    fn test_short_serial() {
+        assert_eq!(short_serial("R58M1234ABC"), "4ABC");
+        assert_eq!(short_serial("emulator-5554"), "5554");
+        assert_eq!(short_serial("XYZ"), "XYZ");
+        assert_eq!(short_serial("192.168.1.20:5555"), "192.168.1.20:5555");
+        assert_eq!(
+            short_serial("adb-R58M1234ABC-aBcD._adb-tls-connect._tcp"),
+            "4ABC"
+        );
+    }
+
+    #[test]
+    fn test_parse_suspended() {
+        let output = "Packages:\n\
+            \x20 Package [com.example.app] (5f1c2a3):\n\
+            \x20   User 0: ceDataInode=4242 installed=true hidden=false suspended=true stopped=false enabled=0 instant=false\n\
+            \x20   User 10: ceDataInode=0 installed=true hidden=false suspended=false stopped=true enabled=0 instant=false\n\
+            \x20   User 11: ceDataInode=0 installed=true hidden=false stopped=false enabled=0 instant=false\n";
+        assert_eq!(parse_suspended(output, 0), Some(true));
+        assert_eq!(parse_suspended(output, 10), Some(false));
+        assert_eq!(parse_suspended(output, 11), None); // before Android 9
+        assert_eq!(parse_suspended(output, 1), None);
+    }
+
+    #[test]
+    fn test_parse_unauthorized_devices() {
+        let output = "List of devices attached\n\
+            R58M1234ABC            unauthorized usb:1-1 transport_id:3\n\
+            emulator-5554          device product:sdk_gphone64 model:sdk transport_id:1\n";
+        assert_eq!(
+            parse_unauthorized_devices(output),
+            vec!["R58M1234ABC".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_parse_apk_paths() {
+        let output = "package:/data/app/~~xyz==/com.example-abc==/base.apk\r\n\
+            package:/data/app/~~xyz==/com.example-abc==/split_config.arm64_v8a.apk\r\n\
+            package:/data/app/~~xyz==/com.example-abc==/split_config.en.apk\n";
+        assert_eq!(
+            parse_apk_paths(output),
+            vec![
+                "/data/app/~~xyz==/com.example-abc==/base.apk".to_string(),
+                "/data/app/~~xyz==/com.example-abc==/split_config.arm64_v8a.apk".to_string(),
+                "/data/app/~~xyz==/com.example-abc==/split_config.en.apk".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_mdns_services() {
+        let services = parse_mdns_services(
+            "List of discovered mdns services\n\
+            adb-R58M1234-aBcD\t_adb-tls-connect._tcp.\t192.168.1.20:37123\n\
+            adb-R58M1234-aBcD\t_adb-tls-pairing._tcp.\t192.168.1.20:40321\n",
+        );
+        assert_eq!(
+            services,
+            vec![
+                MdnsService {
+                    name: "adb-R58M1234-aBcD".to_string(),
+                    address: "192.168.1.20:37123".to_string(),
+                    pairing: false,
+                },
+                MdnsService {
+                    name: "adb-R58M1234-aBcD".to_string(),
+                    address: "192.168.1.20:40321".to_string(),
+                    pairing: true,
+                },
+            ]
+        );
+        assert!(parse_mdns_services("List of discovered mdns services\n").is_empty());
+    }
+
+    #[test]
+    fn test_parse_user_list() {
+        let users = parse_user_list(
+            "Users:\n\tUserInfo{0:Owner:c13} running\n\tUserInfo{10:Work profile:1030} running\n",
+        );
+        assert_eq!(
+            users.iter().map(|u| (u.id, u.index)).collect::<Vec<_>>(),
+            vec![(0, 0), (10, 1)]
+        );
+    }
+
+    #[test]
+    fn test_parse_paused_users() {
+        let users = parse_user_list(
+            "Users:\n\tUserInfo{0:Owner:c13} running\n\
+            \tUserInfo{10:Work profile:10b0}\n\
+            \tUserInfo{11:Work profile:1030}\n\
+            \tUserInfo{12:Secondary:10}\n",
+        );
+        assert_eq!(
+            users.iter().map(|u| (u.id, u.paused)).collect::<Vec<_>>(),
+            vec![(0, false), (10, true), (11, true), (12, false)]
+        );
+        // Without running states, only the flags tell
+        let users = parse_user_list("UserInfo{0:Owner:13}\nUserInfo{10:Work profile:1030}\n");
+        assert!(users.iter().all(|u| !u.paused));
+        assert_eq!(users[1].to_string(), "user 10 — Work profile");
+    }
+
+    #[test]
+    fn test_parse_empty_user_list() {
+        assert_eq!(parse_user_list(""), vec![User::default()]);
+        assert_eq!(parse_user_list("Users:\n"), vec![User::default()]);
+    }
+
+    #[test]
+    fn test_parse_malformed_user_list() {
+        // Unparsable ids are skipped without leaving a gap in the indexes
+        let users = parse_user_list("UserInfo{99999999999:Owner:c13}\nUserInfo{10:Guest:4}\n");
+        assert_eq!(
+            users.iter().map(|u| (u.id, u.index)).collect::<Vec<_>>(),
+            vec![(10, 0)]
+        );
+        assert_eq!(
+            parse_user_list("Error: couldn't get users"),
+            vec![User::default()]
+        );
+    }
+
+    #[test]
+    fn test_wait_for_a_device_offline_at_first() {
+        let delays = || std::iter::repeat(Duration::ZERO).take(4);
+        let mut states = vec![
+            Err("error: device offline".to_string()),
+            Ok("offline\n".to_string()),
+            Ok("device\n".to_string()),
+        ]
+        .into_iter();
+        assert_eq!(
+            wait_until_ready(delays(), || states.next().unwrap()),
+            Ok(())
+        );
+        assert_eq!(states.next(), None);
+
+        // Never ready: 1 attempt + 4 retries
+        let mut attempts = 0;
+        let err = wait_until_ready(delays(), || {
+            attempts += 1;
+            Err("error: device offline".to_string())
+        })
+        .unwrap_err();
+        assert!(err.contains("device offline"));
+        assert_eq!(attempts, 5);
+    }
+
+    #[test]
+    fn test_backoff_stays_within_the_budget() {
+        let delays: Vec<u64> = backoff(CONNECT_WAIT)
+            .map(|d| d.as_millis() as u64)
+            .collect();
+        assert_eq!(delays, vec![250, 500, 1000, 2000, 4000]);
+
+        let total: Duration = backoff(REBOOT_WAIT).sum();
+        assert!(total <= REBOOT_WAIT);
+        assert!(total > REBOOT_WAIT - Duration::from_secs(4));
+    }
+}
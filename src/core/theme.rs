@@ -6,6 +6,7 @@ pub enum Theme {
     Lupin,
     Dark,
     Light,
+    HighContrast,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -38,7 +39,7 @@ pub struct ColorPalette {
 }
 
 impl Theme {
-    pub const ALL: [Self; 3] = [Self::Lupin, Self::Dark, Self::Light];
+    pub const ALL: [Self; 4] = [Self::Lupin, Self::Dark, Self::Light, Self::HighContrast];
     pub fn palette(&self) -> ColorPalette {
         match self {
             Self::Dark => ColorPalette {
@@ -95,6 +96,25 @@ impl Theme {
                     error: color!(0xE63E6D),
                 },
             },
+            // Pure black and white, with saturated colors for the rest
+            Self::HighContrast => ColorPalette {
+                base: BaseColors {
+                    background: color!(0x000000),
+                    foreground: color!(0x101010),
+                },
+                normal: NormalColors {
+                    primary: color!(0x4D3F00),
+                    secondary: color!(0x005A4E),
+                    surface: color!(0xC8C8C8),
+                    error: color!(0xB00020),
+                },
+                bright: BrightColors {
+                    primary: color!(0xFFD600),
+                    secondary: color!(0x00E5CC),
+                    surface: color!(0xFFFFFF),
+                    error: color!(0xFF5C5C),
+                },
+            },
         }
     }
 }
@@ -108,6 +128,7 @@ impl std::fmt::Display for Theme {
                 Theme::Dark => "Dark",
                 Theme::Light => "Light",
                 Theme::Lupin => "Lupin",
+                Theme::HighContrast => "High contrast",
             }
         )
     }
@@ -3,6 +3,7 @@ use crate::CACHE_DIR;
 use retry::{delay::Fixed, retry, OperationResult};
 use serde::{Deserialize, Serialize};
 use serde_json;
+use sha2::{Digest, Sha256};
 use std::collections::HashMap;
 use std::fs;
 use std::path::{Path, PathBuf};
@@ -19,7 +20,7 @@ pub struct Package {
     pub removal: Removal,
 }
 
-#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum UadList {
     All,
     Aosp,
@@ -50,7 +51,9 @@ impl std::fmt::Display for UadListState {
         let s = match self {
             UadListState::Downloading => "Checking updates...".to_string(),
             UadListState::Done => format!("Done (last was {})", format_diff_time_from_now(date)),
-            UadListState::Failed => "Failed to check update!".to_string(),
+            UadListState::Failed => {
+                "Failed to check update! (using a cached or embedded list)".to_string()
+            }
         };
         write!(f, "{}", s)
     }
@@ -100,6 +103,7 @@ pub enum PackageState {
     Enabled,
     Uninstalled,
     Disabled,
+    Unknown, // `pm` contradicts itself: no action until a refresh tells
 }
 
 impl Default for PackageState {
@@ -109,11 +113,12 @@ impl Default for PackageState {
 }
 
 impl PackageState {
-    pub const ALL: [PackageState; 4] = [
+    pub const ALL: [PackageState; 5] = [
         PackageState::All,
         PackageState::Enabled,
         PackageState::Uninstalled,
         PackageState::Disabled,
+        PackageState::Unknown,
     ];
 }
 
@@ -127,6 +132,7 @@ impl std::fmt::Display for PackageState {
                 PackageState::Enabled => "Enabled",
                 PackageState::Uninstalled => "Uninstalled",
                 PackageState::Disabled => "Disabled",
+                PackageState::Unknown => "Unknown",
             }
         )
     }
@@ -148,12 +154,13 @@ impl Opposite for PackageState {
             }
             PackageState::Uninstalled | PackageState::Disabled => PackageState::Enabled,
             PackageState::All => PackageState::All,
+            PackageState::Unknown => PackageState::Unknown,
         }
     }
 }
 
 // Bad names. To be changed!
-#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Removal {
     All,
     Recommended,
@@ -198,22 +205,71 @@ impl std::fmt::Display for Removal {
 }
 
 type PackageHashMap = HashMap<String, Package>;
-pub fn load_debloat_lists(remote: bool) -> (Result<PackageHashMap, PackageHashMap>, bool) {
+
+const LIST_URL: &str = "https://raw.githubusercontent.com/0x192/universal-android-debloater/\
+    main/resources/assets/uad_lists.json";
+
+/// `local_file` replaces the download (e.g. on airgapped machines): an invalid file
+/// falls back to the embedded list, never to the cached download.
+/// `expected_hash`: SHA-256 the downloaded list must match. Without it, the list is checked
+/// against the checksum published next to it, if any.
+pub fn load_debloat_lists(
+    remote: bool,
+    local_file: Option<&Path>,
+    expected_hash: Option<&str>,
+) -> (Result<PackageHashMap, PackageHashMap>, bool) {
+    if let Some(path) = local_file {
+        let list = fs::read_to_string(path)
+            .map_err(|e| e.to_string())
+            .and_then(|data| parse_uad_lists(&data));
+        return match list {
+            Ok(list) => (Ok(into_hashmap(list)), false),
+            Err(e) => {
+                error!(
+                    "Local debloat list {} is invalid, using the embedded one: {}",
+                    path.display(),
+                    e
+                );
+                (Err(into_hashmap(get_embedded_lists())), false)
+            }
+        };
+    }
+
     let cached_uad_lists: PathBuf = CACHE_DIR.join("uad_lists.json");
     let mut error = false;
     let list: Vec<Package> = if remote {
+        // Fetched once, not for every attempt at downloading the list
+        let expected = expected_hash
+            .map(str::to_string)
+            .or_else(get_remote_checksum);
         match retry(Fixed::from_millis(1000).take(60), || {
-            match ureq::get(
-                "https://raw.githubusercontent.com/0x192/universal-android-debloater/\
-            main/resources/assets/uad_lists.json",
-            )
-            .call()
-            {
+            match ureq::get(LIST_URL).call() {
                 Ok(data) => {
-                    let text = data.into_string().unwrap();
-                    fs::write(cached_uad_lists.clone(), &text).expect("Unable to write file");
-                    let list = serde_json::from_str(&text).expect("Unable to parse");
-                    OperationResult::Ok(list)
+                    let parsed = data
+                        .into_string()
+                        .map_err(|e| e.to_string())
+                        .and_then(|text| {
+                            match &expected {
+                                Some(hash) => verify_checksum(&text, hash)?,
+                                None => debug!("No checksum to verify the debloat list against"),
+                            }
+                            Ok(text)
+                        })
+                        .and_then(|text| parse_uad_lists(&text).map(|list| (text, list)));
+                    match parsed {
+                        Ok((text, list)) => {
+                            // Only a valid list can replace the cached one
+                            fs::write(cached_uad_lists.clone(), &text)
+                                .expect("Unable to write file");
+                            OperationResult::Ok(list)
+                        }
+                        Err(e) => {
+                            // The same broken file would be downloaded again: no retry
+                            error!("Downloaded debloat list is rejected: {}", e);
+                            error = true;
+                            OperationResult::Err(Vec::<Package>::new())
+                        }
+                    }
                 }
                 Err(e) => {
                     warn!("Could not load remote debloat list: {}", e);
@@ -230,12 +286,7 @@ pub fn load_debloat_lists(remote: bool) -> (Result<PackageHashMap, PackageHashMa
         get_local_lists()
     };
 
-    // TODO: Do it without intermediary Vec?
-    let mut package_lists = HashMap::new();
-    for p in list {
-        let name = p.id.clone();
-        package_lists.insert(name, p);
-    }
+    let package_lists = into_hashmap(list);
     if error {
         (Err(package_lists), remote)
     } else {
@@ -243,16 +294,140 @@ pub fn load_debloat_lists(remote: bool) -> (Result<PackageHashMap, PackageHashMa
     }
 }
 
-fn get_local_lists() -> Vec<Package> {
+/// Checksum published next to the list (`sha256sum` output), if any
+fn get_remote_checksum() -> Option<String> {
+    ureq::get(&format!("{}.sha256", LIST_URL))
+        .call()
+        .ok()?
+        .into_string()
+        .ok()
+}
+
+/// `expected` can be a bare hex digest or a `sha256sum` line
+fn verify_checksum(data: &str, expected: &str) -> Result<(), String> {
+    let expected = expected.split_whitespace().next().unwrap_or_default();
+    let actual = format!("{:x}", Sha256::digest(data.as_bytes()));
+    if actual.eq_ignore_ascii_case(expected) {
+        Ok(())
+    } else {
+        Err(format!(
+            "checksum mismatch (expected {}, got {}), the list may have been tampered with",
+            expected, actual
+        ))
+    }
+}
+
+// TODO: Do it without intermediary Vec?
+/// Keyed by package id. The last entry of a duplicated id wins.
+fn into_hashmap(list: Vec<Package>) -> PackageHashMap {
+    let mut packages = PackageHashMap::with_capacity(list.len());
+    let mut duplicates = vec![];
+    for mut p in list {
+        // Stray whitespace is the usual source of "different" ids
+        p.id = p.id.trim().to_string();
+        if packages.contains_key(&p.id) {
+            duplicates.push(p.id.clone());
+        }
+        packages.insert(p.id.clone(), p);
+    }
+    if !duplicates.is_empty() {
+        warn!(
+            "Debloat list: {} duplicate entries collapsed ({})",
+            duplicates.len(),
+            duplicates.join(", ")
+        );
+    }
+    packages
+}
+
+/// Lists downloaded during a previous session, if any
+pub fn read_cached_lists() -> Option<PackageHashMap> {
+    let data = fs::read_to_string(CACHE_DIR.join("uad_lists.json")).ok()?;
+    let list = parse_uad_lists(&data).ok()?;
+    Some(into_hashmap(list))
+}
+
+/// Differences between two versions of the UAD lists
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ListChanges {
+    pub added: usize,
+    pub removed: usize,
+    pub retyped: usize,               // moved to another list or removal category
+    pub new_recommended: Vec<String>, // ids of the added packages recommended for removal
+}
+
+impl ListChanges {
+    pub fn is_empty(&self) -> bool {
+        self.added == 0 && self.removed == 0 && self.retyped == 0
+    }
+}
+
+impl std::fmt::Display for ListChanges {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} packages added, {} removed, {} recategorized",
+            self.added, self.removed, self.retyped
+        )
+    }
+}
+
+pub fn diff_lists(old: &PackageHashMap, new: &PackageHashMap) -> ListChanges {
+    let mut changes = ListChanges {
+        removed: old.keys().filter(|id| !new.contains_key(*id)).count(),
+        ..ListChanges::default()
+    };
+    for (id, package) in new {
+        match old.get(id) {
+            Some(p) if p.list != package.list || p.removal != package.removal => {
+                changes.retyped += 1
+            }
+            Some(_) => {}
+            None => {
+                changes.added += 1;
+                if package.removal == Removal::Recommended {
+                    changes.new_recommended.push(id.clone());
+                }
+            }
+        }
+    }
+    changes
+}
+
+/// Parses a UAD list file and checks it looks like a real one.
+/// A truncated download can still be valid JSON (e.g. `[]`).
+pub fn parse_uad_lists(data: &str) -> Result<Vec<Package>, String> {
+    let list: Vec<Package> = serde_json::from_str(data).map_err(|e| e.to_string())?;
+    if list.is_empty() {
+        return Err("the list is empty".to_string());
+    }
+    if let Some(p) = list.iter().find(|p| p.id.trim().is_empty()) {
+        return Err(format!("package without id ({})", p.description));
+    }
+    Ok(list)
+}
+
+fn get_embedded_lists() -> Vec<Package> {
     const DATA: &str = include_str!("../../resources/assets/uad_lists.json");
+    parse_uad_lists(DATA).expect("Unable to parse")
+}
+
+fn get_local_lists() -> Vec<Package> {
     let cached_uad_lists = CACHE_DIR.join("uad_lists.json");
 
     if Path::new(&cached_uad_lists).exists() {
-        let data = fs::read_to_string(cached_uad_lists).unwrap();
-        serde_json::from_str(&data).expect("Unable to parse")
-    } else {
-        serde_json::from_str(DATA).expect("Unable to parse")
+        match fs::read_to_string(&cached_uad_lists)
+            .map_err(|e| e.to_string())
+            .and_then(|data| parse_uad_lists(&data))
+        {
+            Ok(list) => return list,
+            Err(e) => error!(
+                "Cached debloat list is unusable, using the embedded one: {}",
+                e
+            ),
+        }
     }
+    get_embedded_lists()
 }
 
 #[cfg(test)]
@@ -263,4 +438,80 @@ mod tests {
         const DATA: &str = include_str!("../../resources/assets/uad_lists.json");
         let _: Vec<Package> = serde_json::from_str(DATA).expect("Unable to parse");
     }
+
+    #[test]
+    fn test_embedded_list_is_valid() {
+        const DATA: &str = include_str!("../../resources/assets/uad_lists.json");
+        assert!(parse_uad_lists(DATA).is_ok());
+    }
+
+    #[test]
+    fn test_malformed_json_is_rejected() {
+        const DATA: &str = include_str!("../../resources/assets/uad_lists.json");
+        // Truncated download
+        let truncated: String = DATA.chars().take(DATA.chars().count() / 2).collect();
+        assert!(parse_uad_lists(&truncated).is_err());
+        // Error page served with a 200 status
+        assert!(parse_uad_lists("<html><body>Service unavailable</body></html>").is_err());
+        assert!(parse_uad_lists("").is_err());
+        // Valid JSON but not a list of packages
+        assert!(parse_uad_lists("{}").is_err());
+        assert!(parse_uad_lists(r#"[{"id": "com.android.chrome"}]"#).is_err());
+        assert!(parse_uad_lists("[]").is_err());
+    }
+
+    #[test]
+    fn test_package_without_id_is_rejected() {
+        let data = r#"[{
+            "id": "",
+            "list": "Google",
+            "description": "",
+            "dependencies": [],
+            "neededBy": [],
+            "labels": [],
+            "removal": "Recommended"
+        }]"#;
+        assert!(parse_uad_lists(data).is_err());
+    }
+
+    #[test]
+    fn test_checksum_verification() {
+        // sha256("abc")
+        let hash = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+        assert!(verify_checksum("abc", hash).is_ok());
+        assert!(verify_checksum("abc", &hash.to_uppercase()).is_ok());
+        assert!(verify_checksum("abc", &format!("{}  uad_lists.json\n", hash)).is_ok());
+    }
+
+    #[test]
+    fn test_checksum_mismatch_is_rejected() {
+        let hash = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+        assert!(verify_checksum("abd", hash).is_err());
+        assert!(verify_checksum("abc", "").is_err());
+        assert!(verify_checksum("abc", &hash[1..]).is_err());
+    }
+
+    #[test]
+    fn test_duplicate_ids_are_collapsed() {
+        let data = r#"[{
+            "id": "com.android.chrome",
+            "list": "Google",
+            "description": "first",
+            "dependencies": [],
+            "neededBy": [],
+            "labels": [],
+            "removal": "Recommended"
+        }, {
+            "id": "com.android.chrome ",
+            "list": "Google",
+            "description": "second",
+            "dependencies": [],
+            "neededBy": [],
+            "labels": [],
+            "removal": "Advanced"
+        }]"#;
+        let packages = into_hashmap(parse_uad_lists(data).unwrap());
+        assert_eq!(packages.len(), 1);
+        assert_eq!(packages["com.android.chrome"].description, "second");
+    }
 }
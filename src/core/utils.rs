@@ -1,30 +1,77 @@
-use crate::core::sync::{hashset_system_packages, list_all_system_packages, User};
+use crate::core::categories::load_categories;
+use crate::core::sync::{
+    hashset_packages, hashset_third_party_packages, list_all_packages, parse_package_list, User,
+};
 use crate::core::theme::Theme;
 use crate::core::uad_lists::{Package, PackageState, Removal, UadList};
-use crate::gui::views::list::Selection;
 use crate::gui::widgets::package_row::PackageRow;
 use chrono::offset::Utc;
 use chrono::DateTime;
-use std::collections::HashMap;
-use std::path::PathBuf;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use std::process::Command;
 use std::{fmt, fs};
 
 pub fn fetch_packages(
     uad_lists: &HashMap<String, Package>,
     user_id: Option<&User>,
+    serial: &str,
+) -> Vec<PackageRow> {
+    let all_packages = list_all_packages(user_id, serial); // installed and uninstalled packages
+    let enabled_packages = hashset_packages(PackageState::Enabled, user_id, serial);
+    let disabled_packages = hashset_packages(PackageState::Disabled, user_id, serial);
+    let third_party_packages = hashset_third_party_packages(user_id, serial);
+
+    let mut rows = build_package_rows(
+        uad_lists,
+        &all_packages,
+        &enabled_packages,
+        &disabled_packages,
+    );
+    let categories = load_categories();
+    for row in rows.iter_mut() {
+        // `install-existing` needs the APK of the system image: nothing to restore them from
+        if third_party_packages.contains(&row.name) {
+            row.reversible = false;
+        }
+        row.custom_category = categories.packages.get(&row.name).cloned();
+    }
+    // `disabled_by` needs a `dumpsys` per disabled package: filled in once the list is shown
+    rows
+}
+
+/// `pm list packages -e/-d` only list packages installed for the user. Anything
+/// only reported by `pm list packages -u` is therefore uninstalled (for this user).
+/// A package listed as both enabled and disabled is left for a refresh to sort out.
+pub fn package_state(
+    name: &str,
+    enabled_packages: &HashSet<String>,
+    disabled_packages: &HashSet<String>,
+) -> PackageState {
+    match (
+        enabled_packages.contains(name),
+        disabled_packages.contains(name),
+    ) {
+        (true, true) => PackageState::Unknown,
+        (true, false) => PackageState::Enabled,
+        (false, true) => PackageState::Disabled,
+        (false, false) => PackageState::Uninstalled,
+    }
+}
+
+pub fn build_package_rows(
+    uad_lists: &HashMap<String, Package>,
+    all_system_packages: &str,
+    enabled_system_packages: &HashSet<String>,
+    disabled_system_packages: &HashSet<String>,
 ) -> Vec<PackageRow> {
-    let all_system_packages = list_all_system_packages(user_id); // installed and uninstalled packages
-    let enabled_system_packages = hashset_system_packages(PackageState::Enabled, user_id);
-    let disabled_system_packages = hashset_system_packages(PackageState::Disabled, user_id);
     let mut description;
     let mut uad_list;
     let mut state;
     let mut removal;
     let mut user_package: Vec<PackageRow> = Vec::new();
 
-    for p_name in all_system_packages.lines() {
-        state = PackageState::Uninstalled;
+    for p_name in parse_package_list(all_system_packages) {
         description = "[No description] : CONTRIBUTION WELCOMED";
         uad_list = UadList::Unlisted;
         removal = Removal::Unlisted;
@@ -38,11 +85,7 @@ pub fn fetch_packages(
             removal = uad_lists.get(p_name).unwrap().removal;
         }
 
-        if enabled_system_packages.contains(p_name) {
-            state = PackageState::Enabled;
-        } else if disabled_system_packages.contains(p_name) {
-            state = PackageState::Disabled;
-        }
+        state = package_state(p_name, enabled_system_packages, disabled_system_packages);
 
         let package_row =
             PackageRow::new(p_name, state, description, uad_list, removal, false, false);
@@ -52,31 +95,36 @@ pub fn fetch_packages(
     user_package
 }
 
-pub fn update_selection_count(selection: &mut Selection, p_state: PackageState, add: bool) {
-    match p_state {
-        PackageState::Enabled => {
-            if add {
-                selection.enabled += 1
-            } else if selection.enabled > 0 {
-                selection.enabled -= 1
-            };
-        }
-        PackageState::Disabled => {
-            if add {
-                selection.disabled += 1
-            } else if selection.disabled > 0 {
-                selection.disabled -= 1
-            };
-        }
-        PackageState::Uninstalled => {
-            if add {
-                selection.uninstalled += 1
-            } else if selection.uninstalled > 0 {
-                selection.uninstalled -= 1
-            };
-        }
-        PackageState::All => {}
-    };
+/// Gives every user a row for every package of the device, in the same order, so that
+/// a package index is valid for all users.
+/// A package missing for a user is considered uninstalled for this user.
+pub fn align_user_packages(users_packages: Vec<Vec<PackageRow>>) -> Vec<Vec<PackageRow>> {
+    let mut all_packages: HashMap<&str, &PackageRow> = HashMap::new();
+    for p in users_packages.iter().flatten() {
+        all_packages.entry(p.name.as_str()).or_insert(p);
+    }
+    let mut names: Vec<&str> = all_packages.keys().copied().collect();
+    names.sort_by(|a, b| a.to_lowercase().cmp(&b.to_lowercase()));
+
+    users_packages
+        .iter()
+        .map(|packages| {
+            let by_name: HashMap<&str, &PackageRow> =
+                packages.iter().map(|p| (p.name.as_str(), p)).collect();
+            names
+                .iter()
+                .map(|name| match by_name.get(name) {
+                    Some(p) => (*p).clone(),
+                    None => PackageRow {
+                        state: PackageState::Uninstalled,
+                        selected: false,
+                        current: false,
+                        ..all_packages[name].clone()
+                    },
+                })
+                .collect()
+        })
+        .collect()
 }
 
 pub fn string_to_theme(theme: String) -> Theme {
@@ -84,6 +132,7 @@ pub fn string_to_theme(theme: String) -> Theme {
         "Dark" => Theme::Dark,
         "Light" => Theme::Light,
         "Lupin" => Theme::Lupin,
+        "High contrast" => Theme::HighContrast,
         _ => Theme::Lupin,
     }
 }
@@ -94,6 +143,17 @@ pub fn setup_uad_dir(dir: Option<PathBuf>) -> PathBuf {
     dir
 }
 
+/// Makes sure files can be written in `dir`, without creating it
+pub fn check_writable_dir(dir: &Path) -> Result<(), String> {
+    if !dir.is_dir() {
+        return Err(format!("{} is not a directory", dir.display()));
+    }
+    let probe = dir.join(".uad_write_test");
+    fs::write(&probe, b"").map_err(|e| format!("{} is not writable: {}", dir.display(), e))?;
+    let _ = fs::remove_file(probe);
+    Ok(())
+}
+
 pub fn open_url(dir: PathBuf) {
     #[cfg(target_os = "windows")]
     let output = Command::new("explorer").args([dir]).output();
@@ -139,6 +199,104 @@ pub fn format_diff_time_from_now(date: DateTime<Utc>) -> String {
     }
 }
 
+/// Size in binary units (1 KiB = 1024 bytes), labelled as such: the decimal "KB"/"MB"
+/// would be off by 2.4% to 7.4%
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+/// Count with its thousands grouped (`12,345`)
+pub fn format_count(count: usize) -> String {
+    let digits = count.to_string();
+    let mut formatted = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i) % 3 == 0 {
+            formatted.push(',');
+        }
+        formatted.push(digit);
+    }
+    formatted
+}
+
+/// Piece of a package description line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DescriptionSpan {
+    Text(String),
+    Link { label: String, url: String },
+}
+
+/// Lines of a description, with their links. Only the bits of markdown found in the
+/// lists are understood (`[label](url)`, bare URLs, `-`/`*` bullets, `**`/`` ` ``),
+/// anything else stays as written.
+pub fn parse_description(description: &str) -> Vec<Vec<DescriptionSpan>> {
+    description
+        .lines()
+        .map(|line| {
+            let line = line.trim_end();
+            let line = match line.trim_start().strip_prefix("- ") {
+                Some(item) => format!("• {}", item),
+                None => match line.trim_start().strip_prefix("* ") {
+                    Some(item) => format!("• {}", item),
+                    None => line.to_string(),
+                },
+            };
+            parse_description_line(&line.replace("**", "").replace('`', ""))
+        })
+        .collect()
+}
+
+fn parse_description_line(line: &str) -> Vec<DescriptionSpan> {
+    let mut spans = vec![];
+    let mut text = String::new();
+    let mut rest = line;
+    while !rest.is_empty() {
+        // [label](url)
+        if let Some(link) = rest.strip_prefix('[').and_then(|after| {
+            let (label, after) = after.split_once("](")?;
+            let (url, after) = after.split_once(')')?;
+            (!label.contains(']') && url.starts_with("http")).then(|| (label, url, after))
+        }) {
+            let (label, url, after) = link;
+            spans.push(DescriptionSpan::Text(std::mem::take(&mut text)));
+            spans.push(DescriptionSpan::Link {
+                label: label.to_string(),
+                url: url.to_string(),
+            });
+            rest = after;
+            continue;
+        }
+        if rest.starts_with("http://") || rest.starts_with("https://") {
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            // The end of a sentence isn't part of the URL
+            let url = rest[..end].trim_end_matches(|c| matches!(c, '.' | ',' | ';' | ':' | ')'));
+            spans.push(DescriptionSpan::Text(std::mem::take(&mut text)));
+            spans.push(DescriptionSpan::Link {
+                label: url.to_string(),
+                url: url.to_string(),
+            });
+            rest = &rest[url.len()..];
+            continue;
+        }
+        let c = rest.chars().next().unwrap_or_default();
+        text.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+    spans.push(DescriptionSpan::Text(text));
+    spans.retain(|span| !matches!(span, DescriptionSpan::Text(t) if t.is_empty()));
+    spans
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct DisplayablePath {
     pub path: PathBuf,
@@ -162,3 +320,208 @@ impl fmt::Display for DisplayablePath {
         write!(f, "{}", stem)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // `pm list packages -u --user 0`
+    const PM_LIST_ALL: &str = "package:com.android.chrome\r\n\
+        package:com.google.android.youtube\r\n\
+        package:com.samsung.android.bixby.agent\r\n\
+        package:com.facebook.appmanager\r\n\
+        \r\n";
+    // `pm list packages -e --user 0`
+    const PM_LIST_ENABLED: &str =
+        "package:com.android.chrome\npackage:com.google.android.youtube\n";
+    // `pm list packages -d --user 0`
+    const PM_LIST_DISABLED: &str = "package:com.samsung.android.bixby.agent\n";
+
+    fn hashset(output: &str) -> HashSet<String> {
+        parse_package_list(output).map(String::from).collect()
+    }
+
+    #[test]
+    fn test_package_states_from_pm_output() {
+        let rows = build_package_rows(
+            &HashMap::new(),
+            PM_LIST_ALL,
+            &hashset(PM_LIST_ENABLED),
+            &hashset(PM_LIST_DISABLED),
+        );
+        let states: Vec<(&str, PackageState)> =
+            rows.iter().map(|p| (p.name.as_str(), p.state)).collect();
+
+        assert_eq!(
+            states,
+            vec![
+                ("com.android.chrome", PackageState::Enabled),
+                ("com.facebook.appmanager", PackageState::Uninstalled),
+                ("com.google.android.youtube", PackageState::Enabled),
+                ("com.samsung.android.bixby.agent", PackageState::Disabled),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_check_writable_dir() {
+        let dir = std::env::temp_dir();
+        assert_eq!(check_writable_dir(&dir), Ok(()));
+        assert!(!dir.join(".uad_write_test").exists());
+        assert!(check_writable_dir(&dir.join("uad_missing_dir")).is_err());
+    }
+
+    #[test]
+    fn test_contradictory_states_are_unknown() {
+        // The package is listed by both `pm list packages -e` and `-d`
+        let rows = build_package_rows(
+            &HashMap::new(),
+            PM_LIST_ALL,
+            &hashset(PM_LIST_ENABLED),
+            &hashset("package:com.android.chrome\npackage:com.samsung.android.bixby.agent\n"),
+        );
+        let states: Vec<(&str, PackageState)> =
+            rows.iter().map(|p| (p.name.as_str(), p.state)).collect();
+
+        assert_eq!(
+            states,
+            vec![
+                ("com.android.chrome", PackageState::Unknown),
+                ("com.facebook.appmanager", PackageState::Uninstalled),
+                ("com.google.android.youtube", PackageState::Enabled),
+                ("com.samsung.android.bixby.agent", PackageState::Disabled),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unlisted_packages_are_uninstalled() {
+        let rows = build_package_rows(
+            &HashMap::new(),
+            PM_LIST_ALL,
+            &HashSet::new(),
+            &HashSet::new(),
+        );
+        assert!(rows.iter().all(|p| p.state == PackageState::Uninstalled));
+        assert_eq!(rows.len(), 4);
+    }
+
+    fn user_rows(all: &str, enabled: &str) -> Vec<PackageRow> {
+        build_package_rows(&HashMap::new(), all, &hashset(enabled), &HashSet::new())
+    }
+
+    fn names_and_states(rows: &[PackageRow]) -> Vec<(&str, PackageState)> {
+        rows.iter().map(|p| (p.name.as_str(), p.state)).collect()
+    }
+
+    #[test]
+    fn test_asymmetric_users_are_aligned() {
+        let owner = user_rows(
+            "package:com.android.chrome\npackage:com.facebook.appmanager\n",
+            "package:com.android.chrome\npackage:com.facebook.appmanager\n",
+        );
+        // The work profile has an extra package and lacks com.facebook.appmanager
+        let work_profile = user_rows(
+            "package:com.android.chrome\npackage:com.google.android.youtube\n",
+            "package:com.google.android.youtube\n",
+        );
+
+        let aligned = align_user_packages(vec![owner, work_profile]);
+
+        assert_eq!(aligned.len(), 2);
+        assert_eq!(
+            names_and_states(&aligned[0]),
+            vec![
+                ("com.android.chrome", PackageState::Enabled),
+                ("com.facebook.appmanager", PackageState::Enabled),
+                ("com.google.android.youtube", PackageState::Uninstalled),
+            ]
+        );
+        assert_eq!(
+            names_and_states(&aligned[1]),
+            vec![
+                ("com.android.chrome", PackageState::Uninstalled),
+                ("com.facebook.appmanager", PackageState::Uninstalled),
+                ("com.google.android.youtube", PackageState::Enabled),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_aligned_indexes_are_valid_for_every_user() {
+        let aligned = align_user_packages(vec![
+            user_rows("package:a.b.c\n", ""),
+            user_rows("", ""),
+            user_rows("package:d.e.f\npackage:a.b.c\n", "package:d.e.f\n"),
+        ]);
+
+        for i_package in 0..aligned[0].len() {
+            let names: HashSet<&str> = aligned
+                .iter()
+                .map(|packages| packages[i_package].name.as_str())
+                .collect();
+            assert_eq!(names.len(), 1);
+        }
+        assert!(aligned.iter().all(|packages| packages.len() == 2));
+    }
+
+    #[test]
+    fn test_single_user_is_unchanged() {
+        let rows = user_rows(PM_LIST_ALL, PM_LIST_ENABLED);
+        let aligned = align_user_packages(vec![rows.clone()]);
+        assert_eq!(names_and_states(&aligned[0]), names_and_states(&rows));
+    }
+
+    #[test]
+    fn test_description_links_and_bullets() {
+        let text = |t: &str| DescriptionSpan::Text(t.to_string());
+        let link = |label: &str, url: &str| DescriptionSpan::Link {
+            label: label.to_string(),
+            url: url.to_string(),
+        };
+        let lines = parse_description(
+            "See [the wiki](https://example.com/wiki) or https://example.com.\n\
+            - **Safe** to remove\n\
+            [not a link](ftp) [half",
+        );
+        assert_eq!(
+            lines,
+            vec![
+                vec![
+                    text("See "),
+                    link("the wiki", "https://example.com/wiki"),
+                    text(" or "),
+                    link("https://example.com", "https://example.com"),
+                    text("."),
+                ],
+                vec![text("• Safe to remove")],
+                vec![text("[not a link](ftp) [half")],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_format_bytes() {
+        assert_eq!(format_bytes(0), "0 B");
+        assert_eq!(format_bytes(1023), "1023 B");
+        assert_eq!(format_bytes(1024), "1.0 KiB");
+        assert_eq!(format_bytes(1_048_576), "1.0 MiB");
+        assert_eq!(format_bytes(1_500_000_000), "1.4 GiB");
+    }
+
+    #[test]
+    fn test_format_count() {
+        assert_eq!(format_count(0), "0");
+        assert_eq!(format_count(999), "999");
+        assert_eq!(format_count(1000), "1,000");
+        assert_eq!(format_count(1_234_567), "1,234,567");
+    }
+
+    #[test]
+    fn test_saved_themes_are_read_back() {
+        for theme in Theme::ALL {
+            assert_eq!(string_to_theme(theme.to_string()), theme);
+        }
+        assert_eq!(string_to_theme(String::new()), Theme::Lupin);
+    }
+}
@@ -2,20 +2,34 @@ pub mod style;
 pub mod views;
 pub mod widgets;
 
-use crate::core::sync::{get_devices_list, perform_adb_commands, CommandType, Phone};
+use crate::core::config::Config;
+use crate::core::profiles::{is_valid_profile_name, list_profiles, save_profile, Profile};
+use crate::core::save::{save_recovery, write_support_bundle, SupportBundle};
+use crate::core::sync::{
+    disconnect_device, get_build_fingerprint, get_devices_list, get_unauthorized_devices,
+    perform_adb_commands, restart_adb, wait_for_device, CommandType, Phone, REBOOT_WAIT,
+};
 use crate::core::theme::Theme;
 use crate::core::uad_lists::UadListState;
 use crate::core::update::{get_latest_release, Release, SelfUpdateState, SelfUpdateStatus};
 use crate::core::utils::string_to_theme;
 
 use views::about::{About as AboutView, Message as AboutMessage};
-use views::list::{List as AppsView, LoadingState as ListLoadingState, Message as AppsMessage};
+use views::list::{
+    Action, List as AppsView, LoadingState as ListLoadingState, Message as AppsMessage,
+};
+use views::palette::{Entry as PaletteEntry, Message as PaletteMessage, Palette};
 use views::settings::{Message as SettingsMessage, Settings as SettingsView};
+use views::wireless::{Message as WirelessMessage, Wireless as WirelessView};
 use widgets::navigation_menu::nav_menu;
 
-use iced::widget::column;
-use iced::{window::Settings as Window, Application, Command, Element, Length, Renderer, Settings};
-use std::{env, path::PathBuf};
+use iced::widget::{button, checkbox, column, container, row, text};
+use iced::{
+    event, keyboard, subscription, window, window::Settings as Window, Alignment, Application,
+    Command, Element, Event, Length, Renderer, Settings, Subscription,
+};
+use std::path::PathBuf;
+use std::time::Duration;
 
 #[cfg(feature = "self-update")]
 use crate::core::update::{bin_name, download_update_to_temp_file, remove_file};
@@ -26,6 +40,7 @@ enum View {
     List,
     About,
     Settings,
+    Wireless,
 }
 
 #[derive(Default, Clone)]
@@ -40,8 +55,13 @@ pub struct UadGui {
     apps_view: AppsView,
     about_view: AboutView,
     settings_view: SettingsView,
+    wireless_view: WirelessView,
     devices_list: Vec<Phone>,
     selected_device: Option<Phone>, // index of devices_list
+    pending_device: Option<Phone>,  // switch waiting for a confirmation (see `DeviceSelected`)
+    switch_error: Option<String>,   // the selection could not be saved before the switch
+    palette: Palette,
+    preselected_serial: Option<String>, // from --serial, used by the first device scan only
     update_state: UpdateState,
     nb_running_async_adb_commands: u32,
 }
@@ -51,30 +71,50 @@ pub enum Message {
     // Navigation Panel
     AboutPressed,
     SettingsPressed,
+    WirelessPressed,
     AppsPress,
     DeviceSelected(Phone),
+    ConfirmDeviceSwitch(SelectionKeeper),
+    CancelDeviceSwitch,
+    TogglePalette,
+    PaletteAction(PaletteMessage),
     AboutAction(AboutMessage),
     AppsAction(AppsMessage),
     SettingsAction(SettingsMessage),
     RefreshButtonPressed,
     RebootButtonPressed,
-    LoadDevices(Vec<Phone>),
+    DisconnectPressed,
+    WirelessAction(WirelessMessage),
+    LoadDevices(Vec<Phone>, Vec<String>), // devices, serials of the unauthorized ones
     _NewReleaseDownloaded(Result<(PathBuf, PathBuf), ()>),
     GetLatestRelease(Result<Option<Release>, ()>),
     Nothing,
 }
 
+/// Where the selection goes before switching to another device
+#[derive(Debug, Clone, Copy)]
+pub enum SelectionKeeper {
+    Nowhere,
+    Recovery, // offered again the next time the device is shown
+    Profile,
+}
+
 impl Application for UadGui {
     type Theme = Theme;
     type Executor = iced::executor::Default;
     type Message = Message;
-    type Flags = ();
+    type Flags = Option<String>;
 
-    fn new(_flags: ()) -> (Self, Command<Message>) {
+    fn new(serial: Option<String>) -> (Self, Command<Message>) {
         (
-            Self::default(),
+            Self {
+                preselected_serial: serial,
+                ..Self::default()
+            },
             Command::batch([
-                Command::perform(get_devices_list(), Message::LoadDevices),
+                Command::perform(Self::find_devices(), |(devices, unauthorized)| {
+                    Message::LoadDevices(devices, unauthorized)
+                }),
                 Command::perform(
                     async move { get_latest_release() },
                     Message::GetLatestRelease,
@@ -88,11 +128,15 @@ impl Application for UadGui {
     }
 
     fn title(&self) -> String {
-        String::from("Universal Android Debloater")
+        // Visible even when the window is in the background
+        match self.apps_view.commands_status.summary() {
+            Some(status) => format!("Universal Android Debloater — {}", status),
+            None => String::from("Universal Android Debloater"),
+        }
     }
     fn update(&mut self, msg: Message) -> Command<Message> {
         match msg {
-            Message::LoadDevices(devices_list) => {
+            Message::LoadDevices(devices_list, unauthorized) => {
                 self.selected_device = match &self.selected_device {
                     Some(s_device) => {
                         // Try to reload last selected phone
@@ -101,8 +145,27 @@ impl Application for UadGui {
                             .find(|phone| phone.adb_id == s_device.adb_id)
                             .map(|x| x.to_owned())
                     }
-                    None => devices_list.first().map(|x| x.to_owned()),
+                    None => match self.preselected_serial.take() {
+                        Some(serial) => {
+                            let device = devices_list.iter().find(|phone| phone.adb_id == serial);
+                            if device.is_none() {
+                                warn!("Device {} not found: falling back to the first one", serial);
+                            }
+                            device
+                                .or_else(|| devices_list.first())
+                                .map(|x| x.to_owned())
+                        }
+                        None => devices_list.first().map(|x| x.to_owned()),
+                    },
                 };
+                if devices_list.is_empty() {
+                    self.devices_list = devices_list;
+                    self.apps_view.loading_state = ListLoadingState::NoDevice {
+                        unauthorized: !unauthorized.is_empty(),
+                    };
+                    return Command::none();
+                }
+                self.apps_view.set_devices(&devices_list);
                 self.devices_list = devices_list;
                 self.update(Message::SettingsAction(SettingsMessage::LoadDeviceSettings));
                 self.update(Message::AppsAction(AppsMessage::LoadUadList(true)))
@@ -121,19 +184,99 @@ impl Application for UadGui {
             }
             Message::SettingsPressed => {
                 self.view = View::Settings;
+                self.settings_view.profiles = list_profiles();
                 Command::none()
             }
+            Message::WirelessPressed => {
+                self.view = View::Wireless;
+                self.update(Message::WirelessAction(WirelessMessage::Scan))
+            }
+            Message::WirelessAction(msg) => {
+                let connected = matches!(msg, WirelessMessage::Connected(Ok(_)));
+                let command = self.wireless_view.update(msg).map(Message::WirelessAction);
+                if connected {
+                    return Command::batch([command, self.update(Message::RefreshButtonPressed)]);
+                }
+                command
+            }
             Message::RefreshButtonPressed => {
                 self.apps_view.loading_state = ListLoadingState::FindingPhones("".to_string());
-                Command::perform(get_devices_list(), Message::LoadDevices)
+                Command::perform(Self::find_devices(), |(devices, unauthorized)| {
+                    Message::LoadDevices(devices, unauthorized)
+                })
             }
             Message::RebootButtonPressed => {
+                if self.apps_view.ask_reboot() {
+                    self.view = View::List;
+                    return Command::none();
+                }
+                self.update(Message::AppsAction(AppsMessage::RebootConfirmed))
+            }
+            Message::AppsAction(AppsMessage::RebootConfirmed) => {
                 self.apps_view.loading_state = ListLoadingState::FindingPhones("".to_string());
-                self.selected_device = None;
+                // Kept to select the device again once it is back
+                let serial = self
+                    .selected_device
+                    .as_ref()
+                    .map(|phone| phone.adb_id.clone())
+                    .unwrap_or_default();
                 self.devices_list = vec![];
                 Command::perform(
-                    perform_adb_commands("reboot".to_string(), CommandType::Shell),
-                    |_| Message::Nothing,
+                    async move {
+                        if let Err(e) = perform_adb_commands(
+                            "reboot".to_string(),
+                            CommandType::Shell,
+                            serial.clone(),
+                        )
+                        .await
+                        {
+                            error!("Could not reboot {}: {}", serial, e);
+                        }
+                        // The device disappears for a moment before booting again
+                        tokio::time::sleep(Duration::from_secs(2)).await;
+                        // Minutes of polling: off the executor
+                        let device = serial.clone();
+                        let back = tokio::task::spawn_blocking(move || {
+                            wait_for_device(&device, REBOOT_WAIT)
+                        })
+                        .await
+                        .map_err(|e| e.to_string())
+                        .and_then(|back| back);
+                        if let Err(e) = back {
+                            error!("{} didn't come back after the reboot: {}", serial, e);
+                        }
+                        Self::find_devices().await
+                    },
+                    |(devices, unauthorized)| Message::LoadDevices(devices, unauthorized),
+                )
+            }
+            Message::DisconnectPressed => {
+                let serial = match self.selected_device.take() {
+                    Some(phone) => phone.adb_id,
+                    None => return Command::none(),
+                };
+                self.apps_view.loading_state = ListLoadingState::FindingPhones("".to_string());
+                Command::perform(
+                    async move {
+                        if let Err(e) = disconnect_device(&serial) {
+                            error!("Could not disconnect {}: {}", serial, e);
+                        }
+                    },
+                    |_| Message::RefreshButtonPressed,
+                )
+            }
+            Message::AppsAction(AppsMessage::RetryDeviceScan) => {
+                self.update(Message::RefreshButtonPressed)
+            }
+            Message::AppsAction(AppsMessage::RestartAdb) => {
+                self.apps_view.loading_state = ListLoadingState::FindingPhones("".to_string());
+                Command::perform(
+                    async {
+                        if let Err(e) = restart_adb() {
+                            error!("Could not restart adb: {}", e);
+                        }
+                    },
+                    |_| Message::RefreshButtonPressed,
                 )
             }
             Message::AppsAction(msg) => self
@@ -161,14 +304,29 @@ impl Application for UadGui {
                     }
                 }
 
-                self.settings_view
+                let refresh_filters = matches!(
+                    msg,
+                    SettingsMessage::HideUninstalled(_) | SettingsMessage::SafeMode(_)
+                );
+                let command = self
+                    .settings_view
                     .update(
                         &self.selected_device.clone().unwrap_or_default(),
                         &self.apps_view.phone_packages,
                         &mut self.nb_running_async_adb_commands,
                         msg,
                     )
-                    .map(Message::SettingsAction)
+                    .map(Message::SettingsAction);
+
+                if refresh_filters {
+                    self.apps_view.update(
+                        &mut self.settings_view,
+                        &mut self.selected_device.clone().unwrap_or_default(),
+                        &mut self.update_state.uad_list,
+                        AppsMessage::RefreshFilters,
+                    );
+                }
+                command
             }
             Message::AboutAction(msg) => {
                 self.about_view.update(msg.clone());
@@ -204,25 +362,88 @@ impl Application for UadGui {
                         #[cfg(not(feature = "self-update"))]
                         Command::none()
                     }
+                    AboutMessage::GenerateSupportBundle => {
+                        let mut bundle = SupportBundle::new(
+                            self.selected_device.as_ref(),
+                            &self.apps_view.phone_packages,
+                            &self.update_state.uad_list,
+                            self.about_view.redact_serial,
+                        );
+                        let serial = self
+                            .selected_device
+                            .as_ref()
+                            .map(|phone| phone.adb_id.clone());
+                        Command::perform(
+                            async move {
+                                if let (Some(device), Some(serial)) = (&mut bundle.device, serial) {
+                                    device.build = get_build_fingerprint(&serial);
+                                }
+                                write_support_bundle(&bundle)
+                            },
+                            |res| Message::AboutAction(AboutMessage::SupportBundleWritten(res)),
+                        )
+                    }
                     _ => Command::none(),
                 }
             }
             Message::DeviceSelected(s_device) => {
-                self.selected_device = Some(s_device.clone());
-                self.view = View::List;
-                env::set_var("ANDROID_SERIAL", s_device.adb_id);
-                info!("{:-^65}", "-");
-                info!(
-                    "ANDROID_SDK: {} | DEVICE: {}",
-                    s_device.android_sdk, s_device.model
-                );
-                info!("{:-^65}", "-");
-                self.apps_view.loading_state = ListLoadingState::FindingPhones("".to_string());
-                self.update(Message::SettingsAction(SettingsMessage::LoadDeviceSettings));
-                self.update(Message::AppsAction(AppsMessage::LoadPhonePackages((
-                    self.apps_view.uad_lists.clone(),
-                    UadListState::Done,
-                ))))
+                let switching = self
+                    .selected_device
+                    .as_ref()
+                    .map_or(false, |d| d.adb_id != s_device.adb_id);
+                if switching
+                    && self.settings_view.general.confirm_device_switch
+                    && !self.apps_view.selected_names().is_empty()
+                {
+                    self.pending_device = Some(s_device);
+                    self.switch_error = None;
+                    return Command::none();
+                }
+                self.switch_device(s_device)
+            }
+            Message::ConfirmDeviceSwitch(keeper) => {
+                let s_device = match self.pending_device.take() {
+                    Some(device) => device,
+                    None => return Command::none(),
+                };
+                match self.keep_selection(keeper) {
+                    Ok(()) => self.switch_device(s_device),
+                    Err(e) => {
+                        // Nothing is lost yet: the user can pick another option
+                        error!(
+                            "Could not save the selection before switching devices: {}",
+                            e
+                        );
+                        self.pending_device = Some(s_device);
+                        self.switch_error = Some(e);
+                        Command::none()
+                    }
+                }
+            }
+            Message::CancelDeviceSwitch => {
+                self.pending_device = None;
+                Command::none()
+            }
+            Message::TogglePalette => {
+                if self.palette.is_open() {
+                    self.palette
+                        .update(PaletteMessage::Close, &self.palette_entries());
+                    Command::none()
+                } else {
+                    self.palette.open().map(Message::PaletteAction)
+                }
+            }
+            Message::PaletteAction(msg) => {
+                let entries = self.palette_entries();
+                match self.palette.update(msg, &entries) {
+                    // Package list actions are only visible there
+                    Some(msg @ Message::AppsAction(_)) => {
+                        self.view = View::List;
+                        self.update(msg)
+                    }
+                    Some(msg) => self.update(msg),
+                    None => Command::none(),
+                }
             }
             Message::_NewReleaseDownloaded(_res) => {
                 debug!("UAD update has been download!");
@@ -283,6 +504,51 @@ impl Application for UadGui {
         }
     }
 
+    fn subscription(&self) -> Subscription<Message> {
+        // Whatever the view and the focus
+        let global_events = subscription::events_with(|event, _| match event {
+            Event::Keyboard(keyboard::Event::KeyPressed {
+                key_code: keyboard::KeyCode::K,
+                modifiers,
+            }) if modifiers.command() => Some(Message::TogglePalette),
+            Event::Window(window::Event::Resized { width, .. }) => {
+                Some(Message::AppsAction(AppsMessage::WindowResized(width)))
+            }
+            _ => None,
+        });
+        let keys = match self.view {
+            // The palette input has the focus: only the keys it doesn't use are left
+            _ if self.palette.is_open() => subscription::events_with(|event, _| match event {
+                Event::Keyboard(keyboard::Event::KeyPressed { key_code, .. }) => match key_code {
+                    keyboard::KeyCode::Up => Some(PaletteMessage::Up),
+                    keyboard::KeyCode::Down => Some(PaletteMessage::Down),
+                    keyboard::KeyCode::Escape => Some(PaletteMessage::Close),
+                    _ => None,
+                }
+                .map(Message::PaletteAction),
+                _ => None,
+            }),
+            // Keys not already used by a widget (e.g. the search input) navigate the package list
+            View::List => subscription::events_with(|event, status| match (event, status) {
+                (
+                    Event::Keyboard(keyboard::Event::KeyPressed { key_code, .. }),
+                    event::Status::Ignored,
+                ) => Some(Message::AppsAction(AppsMessage::KeyPressed(key_code))),
+                (Event::Keyboard(keyboard::Event::ModifiersChanged(modifiers)), _) => Some(
+                    Message::AppsAction(AppsMessage::ModifiersChanged(modifiers)),
+                ),
+                _ => None,
+            }),
+            _ => Subscription::none(),
+        };
+        // Bulk actions keep running whatever the view
+        Subscription::batch([
+            global_events,
+            keys,
+            self.apps_view.subscription().map(Message::AppsAction),
+        ])
+    }
+
     fn view(&self) -> Element<Self::Message, Renderer<Self::Theme>> {
         let navigation_container = nav_menu(
             &self.devices_list,
@@ -293,6 +559,13 @@ impl Application for UadGui {
 
         let selected_device = self.selected_device.clone().unwrap_or_default();
         let main_container = match self.view {
+            _ if self.palette.is_open() => self
+                .palette
+                .view(&self.palette_entries())
+                .map(Message::PaletteAction),
+            View::List if self.pending_device.is_some() => {
+                self.device_switch_view(self.pending_device.as_ref().unwrap())
+            }
             View::List => self
                 .apps_view
                 .view(&self.settings_view, &selected_device)
@@ -305,6 +578,7 @@ impl Application for UadGui {
                 .settings_view
                 .view(&selected_device)
                 .map(Message::SettingsAction),
+            View::Wireless => self.wireless_view.view().map(Message::WirelessAction),
         };
 
         column![navigation_container, main_container]
@@ -314,15 +588,189 @@ impl Application for UadGui {
 }
 
 impl UadGui {
-    pub fn start() {
-        let settings: Settings<()> = Settings {
+    /// Unauthorized devices are only looked for when there is nothing else to show
+    async fn find_devices() -> (Vec<Phone>, Vec<String>) {
+        let devices = get_devices_list().await;
+        let unauthorized = if devices.is_empty() {
+            get_unauthorized_devices()
+        } else {
+            vec![]
+        };
+        (devices, unauthorized)
+    }
+
+    fn switch_device(&mut self, s_device: Phone) -> Command<Message> {
+        self.selected_device = Some(s_device.clone());
+        self.view = View::List;
+        info!("{:-^65}", "-");
+        info!(
+            "ANDROID_SDK: {} | DEVICE: {}",
+            s_device.android_sdk, s_device.model
+        );
+        info!("{:-^65}", "-");
+        self.update(Message::SettingsAction(SettingsMessage::LoadDeviceSettings));
+        self.update(Message::AppsAction(AppsMessage::DeviceSwitched))
+    }
+
+    /// Everything the command palette can run, in the order it lists them
+    fn palette_entries(&self) -> Vec<PaletteEntry<Message>> {
+        let mut entries = vec![
+            PaletteEntry::new("Go to the package list", Message::AppsPress),
+            PaletteEntry::new("Go to the settings", Message::SettingsPressed),
+            PaletteEntry::new("Go to wireless debugging", Message::WirelessPressed),
+            PaletteEntry::new("About UAD", Message::AboutPressed),
+            PaletteEntry::new("Refresh devices", Message::RefreshButtonPressed),
+        ];
+        entries.extend(
+            self.devices_list
+                .iter()
+                .filter(|phone| {
+                    self.selected_device
+                        .as_ref()
+                        .map_or(true, |d| d.adb_id != phone.adb_id)
+                })
+                .map(|phone| {
+                    PaletteEntry::new(
+                        format!("Switch device: {} ({})", phone.model, phone.adb_id),
+                        Message::DeviceSelected(phone.clone()),
+                    )
+                }),
+        );
+        if self.selected_device.is_none() {
+            return entries;
+        }
+        entries.push(PaletteEntry::new(
+            "Reboot device",
+            Message::RebootButtonPressed,
+        ));
+        entries.extend(
+            [
+                ("Select all recommended", AppsMessage::SelectRecommended),
+                (
+                    "Select all filtered packages",
+                    AppsMessage::ToggleAllSelected(true),
+                ),
+                ("Unselect all", AppsMessage::ToggleAllSelected(false)),
+                ("Invert selection", AppsMessage::InvertSelection),
+                (
+                    "Remove selection",
+                    AppsMessage::ApplyActionOnSelection(Action::Remove),
+                ),
+                (
+                    "Restore selection",
+                    AppsMessage::ApplyActionOnSelection(Action::Restore),
+                ),
+                ("Export selection", AppsMessage::OpenExport),
+                ("Recommended baseline", AppsMessage::OpenBaseline),
+                ("Profiles", AppsMessage::OpenProfiles),
+                ("Save snapshot", AppsMessage::SaveSnapshot),
+                ("Refresh packages", AppsMessage::RefreshPackages),
+                ("Clear all filters", AppsMessage::ClearAllFilters),
+            ]
+            .into_iter()
+            .map(|(label, msg)| PaletteEntry::new(label, Message::AppsAction(msg))),
+        );
+        entries
+    }
+
+    /// Saves the selection of the current device before switching to another one
+    fn keep_selection(&self, keeper: SelectionKeeper) -> Result<(), String> {
+        let current = self.selected_device.clone().unwrap_or_default();
+        let packages = self.apps_view.selected_names();
+        match keeper {
+            SelectionKeeper::Nowhere => Ok(()),
+            SelectionKeeper::Recovery => save_recovery(&current.adb_id, &packages),
+            SelectionKeeper::Profile => {
+                let date = chrono::Local::now().format("%Y-%m-%d %H-%M");
+                let name = format!("{} {}", current.model, date);
+                // Model names are not always valid file names
+                let name = if is_valid_profile_name(&name) {
+                    name
+                } else {
+                    format!("Selection {}", date)
+                };
+                info!(
+                    "Selection of {} saved as profile \"{}\"",
+                    current.adb_id, name
+                );
+                save_profile(&name, &Profile { packages })
+            }
+        }
+    }
+
+    fn device_switch_view(&self, device: &Phone) -> Element<Message, Renderer<Theme>> {
+        let count = self.apps_view.selected_names().len();
+        let btn = |label, message| {
+            button(text(label))
+                .padding(5)
+                .on_press(message)
+                .style(style::Button::Primary)
+        };
+        let dialog = container(
+            column![
+                text(format!("Switch to {} ({})?", device.model, device.adb_id)).size(25),
+                text(format!(
+                    "{} packages are selected on this device. Keep the selection somewhere \
+                    before switching?",
+                    count
+                )),
+                match &self.switch_error {
+                    Some(e) => text(format!("Could not save the selection: {}", e))
+                        .style(style::Text::Danger),
+                    None => text(""),
+                },
+                row![
+                    btn("Cancel", Message::CancelDeviceSwitch),
+                    btn(
+                        "Save as recovery",
+                        Message::ConfirmDeviceSwitch(SelectionKeeper::Recovery)
+                    ),
+                    btn(
+                        "Save as profile",
+                        Message::ConfirmDeviceSwitch(SelectionKeeper::Profile)
+                    ),
+                    btn(
+                        "Switch without saving",
+                        Message::ConfirmDeviceSwitch(SelectionKeeper::Nowhere)
+                    ),
+                ]
+                .spacing(10),
+                checkbox(
+                    "Don't ask again",
+                    !self.settings_view.general.confirm_device_switch,
+                    |dont_ask| Message::SettingsAction(SettingsMessage::ConfirmDeviceSwitch(
+                        !dont_ask
+                    )),
+                )
+                .text_size(15)
+                .style(style::CheckBox::SettingsEnabled),
+            ]
+            .spacing(20)
+            .align_items(Alignment::Center),
+        )
+        .padding(20)
+        .max_width(700)
+        .style(style::Container::Frame);
+
+        container(dialog)
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .center_x()
+            .center_y()
+            .into()
+    }
+
+    pub fn start(serial: Option<String>) {
+        let settings: Settings<Option<String>> = Settings {
             window: Window {
                 size: (1050, 800),
                 resizable: true,
                 decorations: true,
+                always_on_top: Config::load_configuration_file().general.always_on_top,
                 ..iced::window::Settings::default()
             },
             default_text_size: 17,
+            flags: serial,
             ..iced::Settings::default()
         };
         Self::run(settings).unwrap_err();
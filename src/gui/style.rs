@@ -1,10 +1,40 @@
 use crate::core::theme::Theme;
+use crate::core::uad_lists::{PackageState, UadList};
 use iced::overlay::menu;
 use iced::widget::{
-    button, checkbox, container, pick_list, radio, rule, scrollable, text, text_input,
+    button, checkbox, container, pick_list, progress_bar, radio, rule, scrollable, text, text_input,
 };
 use iced::{application, Background, Color};
 
+/// Used for disabled packages: no color of the palettes means "paused"
+const AMBER: Color = Color {
+    r: 0.90,
+    g: 0.63,
+    b: 0.06,
+    a: 1.0,
+};
+
+/// Kind of indicator shown next to a package name
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Badge {
+    Neutral, // facts (split APKs, disabled by the user...)
+    Info,    // something the user added (notes)
+    Change,  // difference with a snapshot
+    Warning, // something to be careful about (disabled by the system)
+    Danger,  // UAD refuses or skips it (protected)
+}
+
+/// Tinted background, colored text and border: legible whatever the base colors
+fn badge_appearance(color: Color) -> container::Appearance {
+    container::Appearance {
+        background: Some(Background::Color(Color { a: 0.15, ..color })),
+        text_color: Some(color),
+        border_radius: 5.0,
+        border_width: 1.0,
+        border_color: Color { a: 0.5, ..color },
+    }
+}
+
 #[derive(Default, Debug, Clone, Copy)]
 pub enum Application {
     #[default]
@@ -28,6 +58,12 @@ pub enum Container {
     Invisible,
     Frame,
     BorderedFrame,
+    ListBadge(UadList),
+    Badge(Badge),
+    PackageRow {
+        state: PackageState,
+        focused: bool,
+    },
 }
 
 impl container::StyleSheet for Theme {
@@ -49,6 +85,53 @@ impl container::StyleSheet for Theme {
                 border_width: 1.0,
                 border_color: self.palette().normal.error,
             },
+            Container::PackageRow { state, focused } => {
+                let p = self.palette();
+                let border_color = match state {
+                    _ if *focused => p.bright.primary,
+                    PackageState::Disabled => AMBER,
+                    PackageState::Uninstalled => p.normal.surface,
+                    PackageState::Unknown => p.bright.error,
+                    PackageState::Enabled | PackageState::All => Color::TRANSPARENT,
+                };
+                container::Appearance {
+                    border_radius: 5.0,
+                    border_width: 2.0,
+                    border_color,
+                    ..container::Appearance::default()
+                }
+            }
+            Container::ListBadge(list) => {
+                let p = self.palette();
+                let color = match list {
+                    UadList::Aosp => p.bright.secondary,
+                    UadList::Carrier => p.bright.error,
+                    UadList::Google => p.bright.primary,
+                    UadList::Oem => p.normal.secondary,
+                    UadList::Misc | UadList::Pending | UadList::Unlisted | UadList::All => {
+                        p.normal.surface
+                    }
+                };
+                badge_appearance(color)
+            }
+            Container::Badge(badge) => {
+                let p = self.palette();
+                let color = match badge {
+                    Badge::Neutral => p.bright.surface,
+                    Badge::Info => p.bright.primary,
+                    Badge::Change => p.bright.secondary,
+                    // Amber is hard to read on the light background
+                    Badge::Warning if *self == Theme::Light => Color {
+                        r: 0.60,
+                        g: 0.38,
+                        b: 0.0,
+                        a: 1.0,
+                    },
+                    Badge::Warning => AMBER,
+                    Badge::Danger => p.bright.error,
+                };
+                badge_appearance(color)
+            }
         }
     }
 }
@@ -64,6 +147,9 @@ pub enum Button {
     RestorePackage,
     NormalPackage,
     SelectedPackage,
+    UserChipIncluded,
+    UserChipExcluded,
+    Link, // looks like text, for links in the middle of some text
 }
 
 impl button::StyleSheet for Theme {
@@ -111,6 +197,14 @@ impl button::StyleSheet for Theme {
                 border_color: p.normal.primary,
                 ..appearance
             },
+            Button::UserChipIncluded => active_appearance(None, p.bright.secondary),
+            Button::UserChipExcluded => active_appearance(None, p.normal.surface),
+            Button::Link => button::Appearance {
+                background: None,
+                text_color: p.bright.primary,
+                border_width: 0.0,
+                ..appearance
+            },
         }
     }
 
@@ -133,6 +227,12 @@ impl button::StyleSheet for Theme {
             Button::RestorePackage => hover_appearance(p.bright.secondary, None),
             Button::NormalPackage => hover_appearance(p.normal.primary, Some(p.bright.surface)),
             Button::SelectedPackage => hover_appearance(p.normal.primary, None),
+            Button::UserChipIncluded => hover_appearance(p.bright.secondary, None),
+            Button::UserChipExcluded => hover_appearance(p.normal.surface, None),
+            Button::Link => button::Appearance {
+                text_color: p.bright.secondary,
+                ..active
+            },
         }
     }
 
@@ -389,6 +489,7 @@ pub enum Text {
     Danger,
     Commentary,
     Color(Color),
+    PackageName(PackageState),
 }
 
 impl From<Color> for Text {
@@ -410,6 +511,14 @@ impl text::StyleSheet for Theme {
                 color: Some(self.palette().normal.surface),
             },
             Text::Color(c) => text::Appearance { color: Some(c) },
+            Text::PackageName(state) => text::Appearance {
+                color: match state {
+                    PackageState::Disabled => Some(AMBER),
+                    PackageState::Uninstalled => Some(self.palette().normal.surface),
+                    PackageState::Unknown => Some(self.palette().bright.error),
+                    PackageState::Enabled | PackageState::All => None,
+                },
+            },
         }
     }
 }
@@ -439,6 +548,26 @@ impl radio::StyleSheet for Theme {
     }
 }
 
+#[derive(Default, Clone, Copy)]
+pub enum ProgressBar {
+    #[default]
+    Default,
+}
+
+impl progress_bar::StyleSheet for Theme {
+    type Style = ProgressBar;
+
+    fn appearance(&self, style: &Self::Style) -> progress_bar::Appearance {
+        match style {
+            ProgressBar::Default => progress_bar::Appearance {
+                background: Background::Color(self.palette().normal.surface),
+                bar: Background::Color(self.palette().normal.primary),
+                border_radius: 3.0,
+            },
+        }
+    }
+}
+
 #[derive(Default, Clone, Copy)]
 pub enum Rule {
     #[default]
@@ -1,8 +1,9 @@
+use crate::core::save::LOG_DIR;
 use crate::core::theme::Theme;
 use crate::core::utils::{last_modified_date, open_url};
 use crate::gui::{style, UpdateState};
 use crate::CACHE_DIR;
-use iced::widget::{button, column, container, row, text, Space};
+use iced::widget::{button, checkbox, column, container, row, text, Space};
 use iced::{Alignment, Element, Length, Renderer};
 use std::path::PathBuf;
 
@@ -10,21 +11,38 @@ use std::path::PathBuf;
 use crate::core::update::SelfUpdateStatus;
 
 #[derive(Default, Debug, Clone)]
-pub struct About {}
+pub struct About {
+    pub redact_serial: bool,               // in the support bundle
+    support_bundle_report: Option<String>, // path of the last bundle, or the error
+}
 
 #[derive(Debug, Clone)]
 pub enum Message {
     UrlPressed(PathBuf),
     UpdateUadLists,
     DoSelfUpdate,
+    RedactSerialToggled(bool),
+    GenerateSupportBundle,
+    SupportBundleWritten(Result<PathBuf, String>),
 }
 
 impl About {
     pub fn update(&mut self, msg: Message) {
-        if let Message::UrlPressed(url) = msg {
-            open_url(url);
+        match msg {
+            Message::UrlPressed(url) => open_url(url),
+            Message::RedactSerialToggled(toggled) => self.redact_serial = toggled,
+            Message::GenerateSupportBundle => {
+                self.support_bundle_report = Some("Generating...".to_string());
+            }
+            Message::SupportBundleWritten(res) => {
+                self.support_bundle_report = Some(match res {
+                    Ok(path) => format!("Support bundle written to {}", path.display()),
+                    Err(e) => format!("Could not write the support bundle: {}", e),
+                });
+            }
+            // other events are handled by UadGui update()
+            _ => {}
         }
-        // other events are handled by UadGui update()
     }
     pub fn view(&self, update_state: &UpdateState) -> Element<Message, Renderer<Theme>> {
         let about_text = text(
@@ -119,7 +137,7 @@ impl About {
             .style(style::Button::Primary);
 
         let log_btn = button("Locate the logfiles")
-            .on_press(Message::UrlPressed(CACHE_DIR.to_path_buf()))
+            .on_press(Message::UrlPressed(LOG_DIR.to_path_buf()))
             .padding(5)
             .style(style::Button::Primary);
 
@@ -132,11 +150,40 @@ impl About {
 
         let row = row![website_btn, wiki_btn, issue_btn, log_btn,].spacing(20);
 
+        let support_bundle_btn = button("Generate support bundle")
+            .on_press(Message::GenerateSupportBundle)
+            .padding(5)
+            .style(style::Button::Primary);
+
+        let support_bundle_row = row![
+            support_bundle_btn,
+            checkbox(
+                "Redact the serial of the device",
+                self.redact_serial,
+                Message::RedactSerialToggled
+            )
+            .text_size(15)
+            .style(style::CheckBox::SettingsEnabled),
+        ]
+        .align_items(Alignment::Center)
+        .spacing(10);
+
+        let support_bundle_column = match &self.support_bundle_report {
+            Some(report) => column![
+                support_bundle_row,
+                text(report).size(13).style(style::Text::Commentary)
+            ],
+            None => column![support_bundle_row],
+        }
+        .align_items(Alignment::Center)
+        .spacing(5);
+
         let content = column![
             Space::new(Length::Fill, Length::Shrink),
             descr_container,
             update_container,
             row,
+            support_bundle_column,
         ]
         .width(Length::Fill)
         .spacing(20)
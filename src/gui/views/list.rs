@@ -1,26 +1,98 @@
-use crate::core::sync::{action_handler, perform_adb_commands, CommandType, Phone, User};
+use crate::core::categories::{load_categories, save_categories, Categories};
+use crate::core::config::{Config, DeviceSettings, FilterControl, GeneralSettings};
+use crate::core::debloat::{self, Job, Progress};
+use crate::core::dependencies::dependency_warnings;
+use crate::core::notes::{load_notes, save_notes};
+use crate::core::profiles::{
+    closest_package, delete_profile, is_valid_profile_name, list_profiles, load_package_id_list,
+    load_profile, save_profile, Profile,
+};
+use crate::core::save::{
+    append_status, artifact_path, backup_apks, clear_recovery, diff_snapshot, export_apks,
+    export_packages, export_sync_script, has_apk_backup, list_available_backups, load_history,
+    load_last_session, load_recovery, load_snapshot, record_history, reenabled_packages,
+    restore_apks, save_last_session, save_recovery, save_snapshot, sync_changes, write_report,
+    PackageReport, RunReport, StatusEvent, DATA_DIR, EXPORT_DIR, REPORT_DIR, SNAPSHOT_DIR,
+};
+use crate::core::sync::{
+    action_handler, adb_shell_command, allow_adb_restart, apply_pkg_state_commands, get_apk_paths,
+    get_battery, get_device_lock, get_disabled_by, get_free_storage, get_package_permissions,
+    get_package_state, get_package_timestamps, get_suspended, is_device_admin_error,
+    perform_adb_commands, request_builder, start_user, suspend_handler, wait_for_device, AdbError,
+    CommandType, CorePackage, DeviceLock, DisabledBy, PackagePermission, PackageTimestamps, Phone,
+    User, CONNECT_WAIT, SUSPEND_MIN_SDK,
+};
 use crate::core::theme::Theme;
 use crate::core::uad_lists::{
-    load_debloat_lists, Opposite, Package, PackageState, Removal, UadList, UadListState,
+    diff_lists, load_debloat_lists, read_cached_lists, ListChanges, Opposite, Package,
+    PackageState, Removal, UadList, UadListState,
+};
+use crate::core::utils::{
+    align_user_packages, fetch_packages, format_bytes, format_count, open_url, parse_description,
+    DescriptionSpan, DisplayablePath,
 };
-use crate::core::utils::{fetch_packages, update_selection_count};
 use crate::gui::style;
-use std::collections::HashMap;
-use std::env;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use crate::gui::views::settings::Settings;
-use crate::gui::widgets::package_row::{Message as RowMessage, PackageRow};
+use crate::gui::widgets::package_row::{Message as RowMessage, PackageRow, RowContext, UserChip};
+use crate::gui::widgets::splitter::splitter;
+use iced::futures::channel::mpsc::UnboundedReceiver;
+use iced::futures::{future, StreamExt};
 use iced::widget::{
-    button, column, container, pick_list, row, scrollable, text, text_input, Space,
+    button, checkbox, column, container, horizontal_rule, pick_list, progress_bar, radio, row,
+    scrollable, text, text_input, tooltip, Space,
 };
-use iced::{Alignment, Command, Element, Length, Renderer};
+use iced::{
+    alignment,
+    keyboard::{KeyCode, Modifiers},
+    subscription, time, Alignment, Command, Element, Length, Renderer, Subscription,
+};
+use static_init::dynamic;
+
+#[dynamic]
+static PACKAGES_SCROLLABLE_ID: scrollable::Id = scrollable::Id::new("packages");
+
+#[dynamic]
+static NOTE_INPUT_ID: text_input::Id = text_input::Id::new("note");
+
+const JUMP_LETTERS: [char; 26] = [
+    'A', 'B', 'C', 'D', 'E', 'F', 'G', 'H', 'I', 'J', 'K', 'L', 'M', 'N', 'O', 'P', 'Q', 'R', 'S',
+    'T', 'U', 'V', 'W', 'X', 'Y', 'Z',
+];
 
 #[derive(Debug, Default, Clone)]
 pub struct Selection {
-    pub uninstalled: u16,
-    pub enabled: u16,
-    pub disabled: u16,
     pub selected_packages: Vec<usize>, // phone_packages indexes (= what you've selected)
+    pub generation: u32,               // bumped by every change made through `set`
+}
+
+impl Selection {
+    /// Adds or removes a package. The `selected` flag of a row can't be trusted for this:
+    /// rows of other users are never flagged, and a package is listed only once.
+    pub fn set(&mut self, i_package: usize, selected: bool) {
+        let listed = self.selected_packages.contains(&i_package);
+        if selected && !listed {
+            self.selected_packages.push(i_package);
+            self.generation = self.generation.wrapping_add(1);
+        } else if !selected && listed {
+            self.selected_packages.retain(|i| *i != i_package);
+            self.generation = self.generation.wrapping_add(1);
+        }
+    }
+}
+
+/// What the bulk actions would do with the selected packages of the current user
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SelectionCounts {
+    pub to_uninstall: usize,
+    pub to_disable: usize,
+    pub to_restore: usize, // uninstalled packages
+    pub to_enable: usize,  // disabled packages
 }
 
 #[derive(Debug, Default, Clone)]
@@ -28,19 +100,532 @@ pub struct PackageInfo {
     pub i_user: Option<usize>,
     pub index: usize,
     pub removal: String,
+    pub force_disable: bool, // removed by a disable whatever the settings (see `toggle_package`)
+    pub suspend: Option<bool>, // a suspension (see `suspend_packages`): the state doesn't change
+    pub uninstalled_instead: bool, // Android refused the disable (see `action_handler`)
+}
+
+/// Packages removed since the toast appeared, which can be restored with a single click
+#[derive(Debug, Default, Clone)]
+pub struct UndoToast {
+    pub packages: Vec<(usize, usize)>, // (package index, user index)
+    pub last_removal: Option<Instant>, // the toast goes away TOAST_DURATION after it
+}
+
+/// Outcome of the commands sent by a bulk action, shown once they all resolved
+#[derive(Debug, Default, Clone)]
+pub struct BatchSummary {
+    pub pending: usize,
+    pub succeeded: Vec<usize>, // phone_packages indexes
+    pub failed: Vec<usize>,
+    pub skipped: usize,
+    pub attempted: Vec<(usize, &'static str)>, // (package index, action), for the report
+    pub errors: HashMap<usize, String>,        // package index -> last error
+    pub show_failures: bool,
+    pub free_storage_before: Option<u64>, // KB
+    pub free_storage_after: Option<u64>,
+    pub force_stop: bool, // restored packages are force-stopped afterwards
+    pub cancelling: bool,
+    pub cancelled: Vec<usize>, // packages not touched because of the cancel
+    pub finished_after_cancel: Vec<usize>, // mid-sequence when cancelled, completed anyway
+    pub expected: HashMap<(usize, usize), PackageState>, // (package, user) -> state after the action
+    pub verifying: usize,       // states being read again (see `verify_batch`)
+    pub unverified: Vec<usize>, // not in the expected state: added to `failed`
+}
+
+impl BatchSummary {
+    fn resolve(&mut self, i_package: Option<usize>, success: bool) {
+        self.pending = self.pending.saturating_sub(1);
+        if let Some(i) = i_package {
+            let packages = if success {
+                &mut self.succeeded
+            } else {
+                &mut self.failed
+            };
+            if !packages.contains(&i) {
+                packages.push(i);
+            }
+        }
+    }
+
+    fn storage_summary(&self) -> Option<String> {
+        let before = self.free_storage_before?;
+        Some(match self.free_storage_after {
+            None => "Measuring freed storage...".to_string(),
+            // Free space slightly moves on its own, below 1 MB it's just noise
+            Some(after) if after < before + 1024 => {
+                "No storage freed (disabled packages keep their space)".to_string()
+            }
+            Some(after) => format!("Freed ~{}", format_bytes((after - before) * 1024)),
+        })
+    }
+}
+
+/// adb commands sent for packages and not resolved yet, whatever triggered them
+#[derive(Debug, Default, Clone, Copy)]
+pub struct CommandsStatus {
+    pub pending: usize,
+    pub failed: usize,
+}
+
+impl CommandsStatus {
+    fn resolve(&mut self, success: bool) {
+        self.pending = self.pending.saturating_sub(1);
+        if !success {
+            self.failed += 1;
+        }
+        // Back to normal once everything is done
+        if self.pending == 0 {
+            self.failed = 0;
+        }
+    }
+
+    /// e.g. "Debloating… 12 pending, 2 failed"
+    pub fn summary(&self) -> Option<String> {
+        match (self.pending, self.failed) {
+            (0, _) => None,
+            (pending, 0) => Some(format!("Debloating… {} pending", pending)),
+            (pending, failed) => Some(format!(
+                "Debloating… {} pending, {} failed",
+                pending, failed
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct ProfilesDialog {
+    pub profiles: Vec<String>,
+    pub selected: Option<String>,
+    pub new_name: String,
+    pub report: Option<String>, // outcome of the last operation
+    pub fuzzy_match: bool,
+    pub suggestions: Vec<ProfileSuggestion>, // waiting to be accepted or rejected
+    pub id_list_path: String,                // plain text list of package ids to import
+}
+
+/// Package of the device close to a package of the profile that isn't on it
+#[derive(Debug, Clone)]
+pub struct ProfileSuggestion {
+    pub missing: String,
+    pub candidate: String,
+    pub i_package: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportScope {
+    All,
+    Filtered, // what is on screen, in the same order
+}
+
+impl std::fmt::Display for ExportScope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                ExportScope::All => "All packages of the user",
+                ExportScope::Filtered => "Only the packages currently shown",
+            }
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ExportDialog {
+    pub scope: ExportScope,
+    pub report: Option<String>, // outcome of the last export
+    pub apk_dir: String,        // where APKs of the selection are exported (default if empty)
+    pub apk_export: Option<ApkExport>,
+}
+
+/// APKs of the selected packages being pulled, one package at a time
+#[derive(Debug, Clone, Default)]
+pub struct ApkExport {
+    pub dir: PathBuf,
+    pub pending: Vec<String>,
+    pub exported: usize,
+    pub failed: Vec<(String, String)>, // package, error
+}
+
+/// How much of the recommended debloat the device went through
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BaselineReport {
+    pub removed: usize,        // recommended packages disabled or uninstalled
+    pub remaining: Vec<usize>, // recommended packages still enabled
+}
+
+impl BaselineReport {
+    pub fn new(packages: &[PackageRow]) -> Self {
+        let mut report = Self::default();
+        for (i, package) in packages.iter().enumerate() {
+            if package.removal != Removal::Recommended {
+                continue;
+            }
+            match package.state {
+                PackageState::Enabled => report.remaining.push(i),
+                PackageState::Unknown => {} // counted once a refresh tells
+                _ => report.removed += 1,
+            }
+        }
+        report
+    }
+
+    /// Percentage of the recommended packages removed (100 if there are none)
+    pub fn score(&self) -> usize {
+        match self.removed + self.remaining.len() {
+            0 => 100,
+            total => self.removed * 100 / total,
+        }
+    }
+
+    pub fn summary(&self) -> String {
+        format!(
+            "{}% of recommended bloat removed ({}/{})",
+            self.score(),
+            self.removed,
+            self.removed + self.remaining.len()
+        )
+    }
+}
+
+/// Another connected device whose package states can be copied
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyncSource {
+    pub adb_id: String,
+    pub label: String, // model and short serial
+}
+
+impl std::fmt::Display for SyncSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.label)
+    }
+}
+
+/// A package of the selected device that differs on the source device
+#[derive(Debug, Clone)]
+pub struct SyncChange {
+    pub i_package: usize,
+    pub name: String,
+    pub from: PackageState,
+    pub to: PackageState, // state on the source device
+    pub commands: Vec<String>,
+}
+
+/// Commands bringing the selected device to the package states of another one
+#[derive(Debug, Clone)]
+pub struct DeviceSync {
+    pub source: SyncSource,
+    pub user: User,
+    pub missing_user: bool, // the source device doesn't have this user
+    pub changes: Vec<SyncChange>,
+    pub skipped: usize, // not allowed by the settings or by the device
+    pub exported: Option<Result<PathBuf, String>>,
+}
+
+impl DeviceSync {
+    /// The commands as a shell script, explicitly targeting the device
+    pub fn script(&self, target: &Phone) -> String {
+        let mut script = format!(
+            "#!/bin/sh\n# {} ({}): package states of {}, user {}\n",
+            target.model, target.adb_id, self.source, self.user.id
+        );
+        for change in &self.changes {
+            script.push_str(&format!(
+                "\n# {}: {} -> {}\n",
+                change.name, change.from, change.to
+            ));
+            for command in &change.commands {
+                script.push_str(&format!("adb -s {} shell {}\n", target.adb_id, command));
+            }
+        }
+        script
+    }
+}
+
+/// Progress of a bulk action, taken by its subscription once it starts
+type SharedReceiver = Arc<Mutex<Option<UnboundedReceiver<Progress>>>>;
+
+enum RunReceiver {
+    Shared(SharedReceiver),
+    Taken(UnboundedReceiver<Progress>),
+}
+
+/// Action on hold until the user confirms it
+#[derive(Debug, Clone)]
+pub enum PendingAction {
+    Package(usize),           // phone_packages index
+    UserPackage(usize, User), // a package, for another user than the selected one
+    Batch {
+        packages: Vec<usize>,
+        skipped: usize,
+        force_stop: bool,
+    },
+    Reboot,
+    SelectAll,        // every filtered package, on screen or not
+    AutoApplyProfile, // first automatic application of the connect profile on a device
 }
 
 #[derive(Debug, Clone)]
+pub struct Confirmation {
+    pub action: PendingAction,
+    pub warnings: Vec<String>,
+    pub preview: Vec<String>, // e.g. "com.x: Enabled → Uninstalled"
+}
+
+/// Filters of the package list, each one can be reset on its own
+#[derive(Debug, Clone, Copy)]
+pub enum Filter {
+    List,
+    State,
+    Removal,
+    Restorability,
+    SessionChanges,
+    Search,
+}
+
+/// Entries of the context menu of a package row
+#[derive(Debug, Clone, Copy)]
+pub enum ContextAction {
+    CopyId,
+    OpenStorePage,
+    AddNote,
+    TogglePin,
+    ToggleAllowList,
+    ViewPermissions, // listed in the description panel
+    CycleActionOverride,
+    Refresh,
+    Act, // same as the action button of the row
+    ActOnUser(User),
+}
+
+/// Group of packages whose removals can be undone together
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Category {
+    All, // everything UAD removed
+    Removal(Removal),
+    List(UadList),
+}
+
+impl Category {
+    fn all() -> Vec<Category> {
+        std::iter::once(Category::All)
+            .chain(
+                Removal::ALL
+                    .iter()
+                    .filter(|r| **r != Removal::All)
+                    .map(|r| Category::Removal(*r)),
+            )
+            .chain(
+                UadList::ALL
+                    .iter()
+                    .filter(|l| **l != UadList::All)
+                    .map(|l| Category::List(*l)),
+            )
+            .collect()
+    }
+
+    fn contains(&self, package: &PackageRow) -> bool {
+        match self {
+            Category::All => true,
+            Category::Removal(removal) => package.removal == *removal,
+            Category::List(list) => package.uad_list == *list,
+        }
+    }
+}
+
+impl std::fmt::Display for Category {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Category::All => write!(f, "All"),
+            Category::Removal(removal) => write!(f, "Removal: {}", removal),
+            Category::List(list) => write!(f, "List: {}", list),
+        }
+    }
+}
+
+/// What the restoration of a category brings back
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RestoreScope {
+    DisabledOnly, // nothing to download, no `install-existing` failure
+    #[default]
+    Full,
+}
+
+impl RestoreScope {
+    pub const ALL: [RestoreScope; 2] = [RestoreScope::DisabledOnly, RestoreScope::Full];
+}
+
+impl std::fmt::Display for RestoreScope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                RestoreScope::DisabledOnly => "Re-enable disabled only",
+                RestoreScope::Full => "Full restore (with uninstalled)",
+            }
+        )
+    }
+}
+
+/// Packages of a category a restoration applies to, depending on its scope
+#[derive(Debug, Default)]
+struct RestoreTargets {
+    disabled: Vec<usize>,
+    uninstalled: Vec<usize>,
+    left_uninstalled: usize, // out of the scope
+    not_restorable: Vec<String>,
+}
+
+impl RestoreTargets {
+    fn summary(&self, category: Category) -> String {
+        let mut summary = format!(
+            "{}: {} to re-enable, {} to reinstall",
+            category,
+            self.disabled.len(),
+            self.uninstalled.len()
+        );
+        if self.left_uninstalled > 0 {
+            summary.push_str(&format!(
+                ", {} uninstalled left as they are",
+                self.left_uninstalled
+            ));
+        }
+        if !self.not_restorable.is_empty() {
+            summary.push_str(&format!(
+                ". Their APK is gone, they can't be restored: {}",
+                self.not_restorable.join(", ")
+            ));
+        }
+        summary
+    }
+}
+
+/// Whether an uninstalled package can come back with `install-existing`
+/// (i.e. its APK stays on the system partition)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Restorability {
+    #[default]
+    All,
+    Restorable,
+    NotRestorable,
+}
+
+impl Restorability {
+    pub const ALL: [Restorability; 3] = [
+        Restorability::All,
+        Restorability::Restorable,
+        Restorability::NotRestorable,
+    ];
+
+    fn matches(&self, package: &PackageRow) -> bool {
+        match self {
+            Restorability::All => true,
+            Restorability::Restorable => package.reversible,
+            Restorability::NotRestorable => !package.reversible,
+        }
+    }
+}
+
+/// Field of a package the search looks into. Only the name is searched unless asked otherwise.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SearchField {
+    Name,
+    Description,
+    Note,
+    Category, // custom category of the user
+}
+
+impl SearchField {
+    pub const OPTIONAL: [SearchField; 3] = [
+        SearchField::Description,
+        SearchField::Note,
+        SearchField::Category,
+    ];
+}
+
+impl std::fmt::Display for SearchField {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                SearchField::Name => "name",
+                SearchField::Description => "description",
+                SearchField::Note => "note",
+                SearchField::Category => "category",
+            }
+        )
+    }
+}
+
+/// First field matching the search, the name first. The name is matched as typed,
+/// the optional fields whatever the case.
+fn search_match(
+    query: &str,
+    package: &PackageRow,
+    note: Option<&String>,
+    fields: &HashSet<SearchField>,
+) -> Option<SearchField> {
+    if package.name.contains(query) {
+        return Some(SearchField::Name);
+    }
+    let query = query.to_lowercase();
+    SearchField::OPTIONAL
+        .into_iter()
+        .filter(|field| fields.contains(field))
+        .find(|field| {
+            let value = match field {
+                SearchField::Name => None,
+                SearchField::Description => Some(&package.description),
+                SearchField::Note => note,
+                SearchField::Category => package.custom_category.as_ref(),
+            };
+            value.map_or(false, |v| v.to_lowercase().contains(&query))
+        })
+}
+
+impl std::fmt::Display for Restorability {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Restorability::All => "Any restorability",
+                Restorability::Restorable => "Restorable",
+                Restorability::NotRestorable => "Not restorable",
+            }
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Action {
     Remove,
     Restore,
+    Suspend,
+    Unsuspend,
+}
+
+impl std::fmt::Display for Action {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Remove => write!(f, "Remove"),
+            Self::Restore => write!(f, "Restore"),
+            Self::Suspend => write!(f, "Suspend"),
+            Self::Unsuspend => write!(f, "Unsuspend"),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
 pub enum LoadingState {
     DownloadingList(String),
     FindingPhones(String),
+    NoDevice { unauthorized: bool },
     LoadingPackages(String),
+    LoadFailed(String), // error of the last attempt
     _UpdatingUad(String),
     Ready(String),
     RestoringDevice(String),
@@ -52,37 +637,251 @@ impl Default for LoadingState {
     }
 }
 
+/// Loading progress of a connected device: packages of every device are pulled in parallel
+#[derive(Debug, Clone)]
+pub enum DeviceLoad {
+    Finding, // waiting for the UAD lists
+    Pulling,
+    Ready(Vec<Vec<PackageRow>>),
+    Failed(String), // the device never got ready
+}
+
+impl std::fmt::Display for DeviceLoad {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            Self::Finding => "finding",
+            Self::Pulling => "pulling",
+            Self::Ready(_) => "ready",
+            Self::Failed(_) => "unavailable",
+        };
+        write!(f, "{}", s)
+    }
+}
+
 #[derive(Default, Debug, Clone)]
 pub struct List {
     pub loading_state: LoadingState,
     pub uad_lists: HashMap<String, Package>,
+    stale_lists: bool, // the latest UAD lists couldn't be downloaded
+    list_changes: Option<ListChanges>, // compared to the lists of the previous download
+    new_recommended: Vec<String>, // to be selected once the packages are loaded
     pub phone_packages: Vec<Vec<PackageRow>>, // packages of all users of the phone
+    device_loads: Vec<(Phone, DeviceLoad)>,
+    shown_device: String, // adb_id of the device whose packages are in `phone_packages`
     filtered_packages: Vec<usize>, // phone_packages indexes of the selected user (= what you see on screen)
+    hidden_uninstalled: usize, // uninstalled packages left out by the `hide_uninstalled` setting
     pub selection: Selection,
     selected_package_state: Option<PackageState>,
-    selected_removal: Option<Removal>,
+    selected_removals: HashSet<Removal>, // empty means all of them
+    selected_custom: HashSet<String>,    // custom categories, same as `selected_removals`
+    categories: Categories,
+    category_input: String, // label of a new custom category
     selected_list: Option<UadList>,
+    list_query: String, // typed in the list filter, its dropdown is open while not empty
+    list_highlight: usize, // match picked by Enter, moved with the arrow keys
+    selected_restorability: Restorability,
+    session_changes: Vec<String>, // packages changed since the device was loaded, in order
+    only_session_changes: bool,
+    show_allow_listed: bool, // packages of the allow-list are hidden otherwise
     selected_user: Option<User>,
+    all_users: bool, // actions apply to every user, each according to its own package state
+    include_protected: bool, // bulk removals don't skip protected packages
     pub input_value: String,
+    search_fields: HashSet<SearchField>, // searched on top of the name
+    search_matches: HashMap<usize, SearchField>, // filtered packages found by another field
     description: String,
+    notes: HashMap<String, String>, // package name -> note of the user
+    note_input: String,             // note of the current package, as being edited
+    apk_report: Option<String>,     // outcome of the last APK backup/restore of the current package
+    apk_backup_available: bool,     // the current package has an APK backup
     current_package_index: usize,
+    hovered_index: Option<usize>, // previewed in the description panel, `current` stays pinned
+    description_expanded: bool,   // the whole description of the current package is shown
+    focused_index: Option<usize>, // phone_packages index of the row navigated with the keyboard
+    last_clicked_index: Option<usize>, // start of a shift-click range selection
+    shift_pressed: bool,
+    window_width: u32,  // 0 until the window is resized
+    filters_open: bool, // filters panel of the minimal UI
+    sort_by_last_update: bool,
+    selected_first: bool, // selected packages are listed first, after the pinned ones
+    pending_timestamps: HashSet<usize>, // packages whose install dates are being fetched
+    pending_permissions: HashSet<usize>, // packages whose permissions are being fetched
+    user_exclusions: HashSet<(usize, usize)>, // (package index, user index) left out of actions in collapsed multi-user mode
+    undo_toast: Option<UndoToast>,
+    search_generation: u32, // only the last search input is applied
+    batch: Option<BatchSummary>,
+    runs: Vec<(u32, SharedReceiver, Arc<AtomicBool>)>, // running bulk actions, with their cancel flag
+    queue: Vec<(String, Action)>, // staged actions, run in this order whatever the filters
+    run_generation: u32,
+    pub commands_status: CommandsStatus,
+    confirmation: Option<Confirmation>,
+    profiles: Option<ProfilesDialog>,
+    export: Option<ExportDialog>,
+    baseline: Option<BaselineReport>, // shown instead of the list
+    sync: Option<DeviceSync>,         // shown instead of the list
+    snapshots: Vec<DisplayablePath>,
+    compared_snapshot: Option<DisplayablePath>,
+    snapshot_changes: HashMap<usize, Option<PackageState>>, // package index -> state in the snapshot
+    snapshot_removed: usize, // packages of the snapshot no longer on the device
+    scroll_offset: f32,      // relative offset of the package list
+    scroll_offsets: HashMap<String, f32>, // filter combination -> offset it was left at
+    restore_category: Option<Category>,
+    restore_report: Option<String>, // what the restoration of the category does
+    restore_scope: RestoreScope,
+    recovered_selection: Option<Vec<String>>, // unsaved selection of the previous session
+    recovery_saved: Vec<String>,              // selection in the recovery file
+    reenabled: Vec<usize>, // packages removed in the last session that are enabled again
+    forced_uninstalls: Vec<String>, // packages Android uninstalled when asked to disable them
+    missing_for_user: Vec<String>, // selected packages the selected user doesn't have
+    connect_profile_devices: HashSet<String>, // serials the connect profile was applied to
+    connect_profile_report: Option<String>,
+    user_start: Option<Result<(), String>>, // outcome of starting a paused user (None: idle)
+    context_menu: Option<usize>,            // package whose context menu is open
+    pending_details: Vec<(usize, usize)>,   // (user, package) whose `disabled_by` is unknown yet
+    details_generation: u32,                // only the details of the last load are kept
 }
 
 #[derive(Debug, Clone)]
 pub enum Message {
     LoadUadList(bool),
-    LoadPhonePackages((HashMap<String, Package>, UadListState)),
-    RestoringDevice(Result<CommandType, ()>),
+    LoadPhonePackages((HashMap<String, Package>, UadListState, Option<ListChanges>)),
+    DismissListChanges,
+    PackagesScrolled(f32),
+    SaveRecovery(Instant),
+    RestoreCategorySelected(Category),
+    RestoreScopeSelected(RestoreScope),
+    RestoreCategory,
+    RestoreRecoveredSelection,
+    DiscardRecoveredSelection,
+    ScrollToTop,
+    RestoringDevice(Result<CommandType, AdbError>),
+    DevicePackagesLoaded(String, Result<Vec<Vec<PackageRow>>, String>),
+    // Triggered by mod.rs when another device is picked
+    DeviceSwitched,
     ApplyFilters(Vec<Vec<PackageRow>>),
+    RefreshPackages,
+    PackagesRefreshed(Result<Vec<Vec<PackageRow>>, String>),
     SearchInputChanged(String),
+    SearchFieldToggled(SearchField, bool),
+    SearchDebounced(u32),
     ToggleAllSelected(bool),
+    InvertSelection,
+    SelectRecommended, // every enabled package recommended for removal, filtered or not
+    QueueSelection(Action),
+    MoveQueued(usize, bool), // up if true
+    RemoveQueued(usize),
+    ClearQueue,
+    RunQueue,
     ListSelected(UadList),
+    ListQueryChanged(String),
+    ListQuerySubmitted,
     UserSelected(User),
+    DismissMissingForUser,
+    AllUsersToggled(bool),
+    IncludeProtectedToggled(bool),
     PackageStateSelected(PackageState),
-    RemovalSelected(Removal),
+    RestorabilitySelected(Restorability),
+    RemovalToggled(Removal, bool),
+    CustomCategoryToggled(String, bool),
+    CategoryInputChanged(String),
+    CreateCategory,
+    AssignCategory(String),
+    ClearCategory,
+    ClearFilter(Filter),
+    ClearAllFilters,
+    SortByLastUpdate(bool),
+    SelectedFirstToggled(bool),
+    SessionChangesToggled(bool),
+    SessionExpertModeToggled(bool),
+    ShowAllowListedToggled(bool),
+    TimestampsFetched(usize, Option<PackageTimestamps>),
+    ApkCountFetched(usize, usize),
+    PermissionsFetched(usize, Option<Vec<PackagePermission>>),
+    BackupApk,
+    ApkBackedUp(Result<usize, String>),
+    RestoreApk,
+    ApkRestored(usize, Result<usize, String>),
+    JumpToLetter(char),
+    DescriptionResized(f32),
+    DescriptionResizeEnded,
     ApplyActionOnSelection(Action),
+    EnableAndStopSelection,
     List(usize, RowMessage),
-    ChangePackageState(Result<CommandType, ()>),
+    ChangePackageState(Result<CommandType, AdbError>),
+    PackageRefreshed(usize, usize, Option<PackageState>),
+    EnableForAllUsers,
+    TogglePackage,
+    SuspendPackage(bool),
+    SuspensionFetched(usize, usize, Option<bool>), // user index, package index
+    ActOnUser(User), // action on the current package, for this user only
+    OpenStorePage,
+    StorePageOpened,
+    DescriptionLinkPressed(String),
+    ToggleDescriptionExpanded,
+    RefreshFilters,
+    NoteChanged(String),
+    SaveNote,
+    CloseBatchSummary,
+    ToggleBatchFailures,
+    RetryFailedPackages,
+    BatchChecked(u32, Vec<usize>, usize, bool, Vec<String>), // run, packages, skipped, force stop, warnings
+    BatchVerified(u32, usize, usize, PackageState, Option<PackageState>), // run, package, user, expected, actual
+    UndoRemoval,
+    DismissToast,
+    ToastTick(Instant),
+    StorageMeasured(Option<u64>),
+    KeyPressed(KeyCode),
+    ModifiersChanged(Modifiers),
+    WindowResized(u32),
+    ToggleFiltersPanel,
+    ConfirmAction,
+    CancelAction,
+    RetryDeviceScan,
+    RestartAdb,
+    RebootConfirmed,
+    SaveSnapshot,
+    ReapplyRemovals,
+    DetailsLoaded(u32, Vec<(usize, usize, Option<DisabledBy>)>),
+    DismissReenabled,
+    DismissForcedUninstalls,
+    DismissConnectProfileReport,
+    StartUser(User),
+    UserStarted(User, Result<(), String>),
+    SnapshotSelected(DisplayablePath),
+    ClearSnapshotComparison,
+    OpenExport,
+    CloseExport,
+    OpenBaseline,
+    CloseBaseline,
+    CopyBaselineSummary,
+    SelectBaselineRemaining,
+    SyncSourceSelected(SyncSource),
+    CloseSync,
+    ExportSyncScript,
+    RunSync,
+    SyncApplied(usize, Vec<(usize, Option<PackageState>)>), // user, state of each synced package
+    ExportScopeSelected(ExportScope),
+    ExportPackages,
+    ApkExportDirChanged(String),
+    ExportApks,
+    ApkExported(String, Result<usize, String>),
+    OpenProfiles,
+    CloseProfiles,
+    ProfileSelected(String),
+    ProfileNameChanged(String),
+    IdListPathChanged(String),
+    ImportIdList,
+    SaveProfile,
+    ApplyProfile,
+    FuzzyMatchToggled(bool),
+    AcceptSuggestion(usize),
+    RejectSuggestion(usize),
+    DeleteProfile,
+    RunReady(u32, Option<u64>, Vec<Job>), // run, free storage before it, its jobs
+    RunProgress(u32, Progress),
+    ContextAction(usize, ContextAction),
+    CloseContextMenu,
+    CancelBatch,
     Nothing,
 }
 
@@ -94,17 +893,36 @@ impl List {
         list_update_state: &mut UadListState,
         message: Message,
     ) -> Command<Message> {
-        let i_user = self.selected_user.unwrap_or(User { id: 0, index: 0 }).index;
+        let selection_generation = self.selection.generation;
+        let command = self.handle_message(settings, selected_device, list_update_state, message);
+        // Newly selected packages move up right away
+        if self.selected_first && self.selection.generation != selection_generation {
+            self.filter_package_lists(&settings.general);
+        }
+        command
+    }
+
+    fn handle_message(
+        &mut self,
+        settings: &mut Settings,
+        selected_device: &mut Phone,
+        list_update_state: &mut UadListState,
+        message: Message,
+    ) -> Command<Message> {
+        let i_user = self.selected_user.unwrap_or(User::default()).index;
         match message {
             Message::RestoringDevice(output) => {
-                if let Ok(res) = output {
-                    if let CommandType::PackageManager(p) = res {
+                match output {
+                    Ok(CommandType::PackageManager(p)) => {
                         self.loading_state = LoadingState::RestoringDevice(
                             self.phone_packages[i_user][p.index].name.clone(),
                         )
                     }
-                } else {
-                    self.loading_state = LoadingState::RestoringDevice("Error [TODO]".to_string());
+                    Ok(CommandType::Shell) => {}
+                    Err(err) => {
+                        self.loading_state =
+                            LoadingState::RestoringDevice(format!("Error: {}", err));
+                    }
                 }
                 Command::none()
             }
@@ -116,445 +934,6001 @@ impl List {
                 );
                 info!("{:-^65}", "-");
                 self.loading_state = LoadingState::DownloadingList("".to_string());
+                let local_list = Some(settings.general.local_list_path.trim())
+                    .filter(|path| !path.is_empty())
+                    .map(PathBuf::from);
+                let expected_hash = Some(settings.general.list_sha256.trim().to_string())
+                    .filter(|hash| !hash.is_empty());
                 Command::perform(
-                    Self::init_apps_view(remote, selected_device.clone()),
+                    Self::init_apps_view(
+                        remote,
+                        local_list,
+                        expected_hash,
+                        selected_device.clone(),
+                    ),
                     Message::LoadPhonePackages,
                 )
             }
             Message::LoadPhonePackages(list_box) => {
-                let (uad_list, list_state) = list_box;
-                self.loading_state = LoadingState::LoadingPackages("".to_string());
+                let (uad_list, list_state, list_changes) = list_box;
+                if let Some(changes) = &list_changes {
+                    if settings.general.auto_select_new_recommended {
+                        self.new_recommended = changes.new_recommended.clone();
+                    }
+                }
+                if list_changes.is_some() {
+                    self.list_changes = list_changes;
+                }
+                self.loading_state = LoadingState::LoadingPackages(selected_device.model.clone());
                 self.uad_lists = uad_list.clone();
+                self.stale_lists = list_state == UadListState::Failed;
                 *list_update_state = list_state;
-                Command::perform(
-                    Self::load_packages(uad_list, selected_device.user_list.clone()),
-                    Message::ApplyFilters,
-                )
+                self.shown_device.clear();
+                if self.device_load(&selected_device.adb_id).is_none() {
+                    self.device_loads
+                        .push((selected_device.clone(), DeviceLoad::Finding));
+                }
+                Command::batch(self.device_loads.iter_mut().map(|(phone, load)| {
+                    *load = DeviceLoad::Pulling;
+                    let adb_id = phone.adb_id.clone();
+                    Command::perform(
+                        Self::load_packages(uad_list.clone(), phone.clone()),
+                        move |packages| Message::DevicePackagesLoaded(adb_id.clone(), packages),
+                    )
+                }))
+            }
+            Message::DevicePackagesLoaded(adb_id, packages) => {
+                let packages = match packages {
+                    Ok(packages) => packages,
+                    Err(err) => {
+                        error!("Could not load the packages of {}: {}", adb_id, err);
+                        if adb_id == selected_device.adb_id {
+                            self.loading_state = LoadingState::LoadFailed(err.clone());
+                        }
+                        if let Some((_, load)) = self
+                            .device_loads
+                            .iter_mut()
+                            .find(|(p, _)| p.adb_id == adb_id)
+                        {
+                            *load = DeviceLoad::Failed(err);
+                        }
+                        return Command::none();
+                    }
+                };
+                if let Some((_, load)) = self
+                    .device_loads
+                    .iter_mut()
+                    .find(|(p, _)| p.adb_id == adb_id)
+                {
+                    *load = DeviceLoad::Ready(packages.clone());
+                }
+                if adb_id == selected_device.adb_id && self.shown_device != adb_id {
+                    self.shown_device = adb_id;
+                    return self.update(
+                        settings,
+                        selected_device,
+                        list_update_state,
+                        Message::ApplyFilters(packages),
+                    );
+                }
+                Command::none()
+            }
+            Message::DeviceSwitched => {
+                // Keep the changes made on the device we leave
+                let shown_device = std::mem::take(&mut self.shown_device);
+                if let Some((_, load)) = self
+                    .device_loads
+                    .iter_mut()
+                    .find(|(p, _)| p.adb_id == shown_device)
+                {
+                    *load = DeviceLoad::Ready(std::mem::take(&mut self.phone_packages));
+                }
+
+                match self.device_load(&selected_device.adb_id) {
+                    Some(DeviceLoad::Ready(packages)) => {
+                        let packages = packages.clone();
+                        self.shown_device = selected_device.adb_id.clone();
+                        self.update(
+                            settings,
+                            selected_device,
+                            list_update_state,
+                            Message::ApplyFilters(packages),
+                        )
+                    }
+                    Some(DeviceLoad::Failed(err)) => {
+                        self.loading_state = LoadingState::LoadFailed(err.clone());
+                        Command::none()
+                    }
+                    Some(_) => {
+                        // Shown as soon as its packages are pulled
+                        self.loading_state =
+                            LoadingState::LoadingPackages(selected_device.model.clone());
+                        Command::none()
+                    }
+                    None => {
+                        self.loading_state =
+                            LoadingState::LoadingPackages(selected_device.model.clone());
+                        self.device_loads
+                            .push((selected_device.clone(), DeviceLoad::Pulling));
+                        let adb_id = selected_device.adb_id.clone();
+                        Command::perform(
+                            Self::load_packages(self.uad_lists.clone(), selected_device.clone()),
+                            move |packages| Message::DevicePackagesLoaded(adb_id.clone(), packages),
+                        )
+                    }
+                }
             }
             Message::ApplyFilters(packages) => {
                 self.phone_packages = packages;
-                self.filtered_packages = (0..self.phone_packages[i_user].len()).collect();
-                self.selected_package_state = Some(PackageState::Enabled);
-                self.selected_removal = Some(Removal::Recommended);
-                self.selected_list = Some(UadList::All);
-                self.selected_user = Some(User { id: 0, index: 0 });
-                Self::filter_package_lists(self);
+                if self.phone_packages.is_empty() {
+                    self.phone_packages.push(vec![]);
+                }
+                // The previous device may have had more users
+                self.filtered_packages =
+                    (0..self.phone_packages[User::default().index].len()).collect();
+                for filter in [
+                    Filter::List,
+                    Filter::State,
+                    Filter::Removal,
+                    Filter::Restorability,
+                    Filter::SessionChanges,
+                ] {
+                    self.reset_filter(filter, &settings.general);
+                }
+                self.session_changes.clear();
+                self.queue.clear();
+                self.selected_user = Some(User::default());
+                self.user_exclusions.clear();
+                self.missing_for_user.clear();
+                self.forced_uninstalls.clear();
+                self.context_menu = None;
+                self.undo_toast = None;
+                self.batch = None;
+                self.run_generation += 1; // drops what is left of the runs on the previous device
+                self.confirmation = None;
+                self.profiles = None;
+                self.export = None;
+                self.baseline = None;
+                self.sync = None;
+                self.user_start = None;
+                self.snapshots =
+                    list_available_backups(&SNAPSHOT_DIR.join(&selected_device.adb_id));
+                self.compared_snapshot = None;
+                self.snapshot_changes.clear();
+                self.focused_index = None;
+                self.last_clicked_index = None;
+                self.pending_timestamps.clear();
+                self.pending_permissions.clear();
+                self.notes = load_notes();
+                self.categories = load_categories();
+                self.note_input.clear();
+                self.filter_package_lists(&settings.general);
                 self.loading_state = LoadingState::Ready("".to_string());
-                Command::none()
+                self.recovered_selection =
+                    load_recovery(&selected_device.adb_id).filter(|names| !names.is_empty());
+                self.recovery_saved = self.recovered_selection.clone().unwrap_or_default();
+                // Indexes are the same for every user
+                let mut reenabled = vec![];
+                for user in &selected_device.user_list {
+                    let packages = match self.phone_packages.get(user.index) {
+                        Some(packages) => packages,
+                        None => continue,
+                    };
+                    if let Some(last) = load_last_session(&selected_device.adb_id, user) {
+                        reenabled.extend(reenabled_packages(&last, packages));
+                    }
+                    save_last_session(&selected_device.adb_id, user, packages);
+                }
+                reenabled.sort_unstable();
+                reenabled.dedup();
+                if !reenabled.is_empty() {
+                    info!(
+                        "{} packages removed in the last session are enabled again",
+                        reenabled.len()
+                    );
+                }
+                self.reenabled = reenabled;
+                Command::batch([
+                    self.select_new_recommended(settings),
+                    self.apply_connect_profile(settings, selected_device),
+                    self.start_details_load(selected_device),
+                ])
             }
-            Message::ToggleAllSelected(selected) => {
-                for i in self.filtered_packages.clone() {
-                    self.phone_packages[i_user][i].selected = selected;
-
-                    if !selected {
-                        if self.selection.selected_packages.contains(&i) {
-                            update_selection_count(
-                                &mut self.selection,
-                                self.phone_packages[i_user][i].state,
-                                false,
-                            );
-                            self.selection
-                                .selected_packages
-                                .drain_filter(|s_i| *s_i == i);
-                        }
-                    } else if !self.selection.selected_packages.contains(&i) {
-                        self.selection.selected_packages.push(i);
-                        update_selection_count(
-                            &mut self.selection,
-                            self.phone_packages[i_user][i].state,
-                            true,
-                        );
+            Message::SaveRecovery(_) => {
+                // Only the packages of the device shown
+                if !matches!(self.loading_state, LoadingState::Ready(_))
+                    || self.shown_device != selected_device.adb_id
+                {
+                    return Command::none();
+                }
+                let mut names: Vec<String> = self
+                    .selection
+                    .selected_packages
+                    .iter()
+                    .map(|i| self.phone_packages[i_user][*i].name.clone())
+                    .collect();
+                names.sort();
+                // The recovered selection is kept until the user decides what to do with it
+                let undecided = names.is_empty() && self.recovered_selection.is_some();
+                if names != self.recovery_saved && !undecided {
+                    let saved = if names.is_empty() {
+                        clear_recovery(&selected_device.adb_id);
+                        Ok(())
+                    } else {
+                        save_recovery(&selected_device.adb_id, &names)
+                    };
+                    match saved {
+                        Ok(_) => self.recovery_saved = names,
+                        Err(e) => error!("[RECOVERY]: Unable to save the selection: {}", e),
+                    }
+                }
+                for user in &selected_device.user_list {
+                    if let Some(packages) = self.phone_packages.get(user.index) {
+                        save_last_session(&selected_device.adb_id, user, packages);
                     }
                 }
                 Command::none()
             }
-            Message::SearchInputChanged(letter) => {
-                self.input_value = letter;
-                Self::filter_package_lists(self);
+            Message::RestoreRecoveredSelection => {
+                if let Some(names) = self.recovered_selection.take() {
+                    for i_package in 0..self.phone_packages[i_user].len() {
+                        if names.contains(&self.phone_packages[i_user][i_package].name) {
+                            self.set_package_selected(i_package, true, settings);
+                        }
+                    }
+                }
                 Command::none()
             }
-            Message::ListSelected(list) => {
-                self.selected_list = Some(list);
-                Self::filter_package_lists(self);
+            // The counts are shown before anything runs
+            Message::RestoreCategorySelected(category) => {
+                self.restore_category = Some(category);
+                let targets = self.restore_targets(category, &selected_device.adb_id);
+                self.restore_report = Some(targets.summary(category));
                 Command::none()
             }
-            Message::PackageStateSelected(package_state) => {
-                self.selected_package_state = Some(package_state);
-                Self::filter_package_lists(self);
+            Message::RestoreScopeSelected(scope) => {
+                self.restore_scope = scope;
+                if let Some(category) = self.restore_category {
+                    let targets = self.restore_targets(category, &selected_device.adb_id);
+                    self.restore_report = Some(targets.summary(category));
+                }
                 Command::none()
             }
-            Message::RemovalSelected(removal) => {
-                self.selected_removal = Some(removal);
-                Self::filter_package_lists(self);
+            Message::RestoreCategory => {
+                let category = match self.restore_category {
+                    Some(category) => category,
+                    None => return Command::none(),
+                };
+                // The device may have changed since the category was picked
+                let targets = self.restore_targets(category, &selected_device.adb_id);
+                self.restore_report = Some(targets.summary(category));
+                let to_restore = [targets.disabled, targets.uninstalled].concat();
+                if to_restore.is_empty() {
+                    return Command::none();
+                }
+                self.apply_batch(to_restore, 0, false, settings, selected_device)
+            }
+            Message::ReapplyRemovals => {
+                let reenabled = std::mem::take(&mut self.reenabled);
+                for i in &reenabled {
+                    self.set_package_selected(*i, true, settings);
+                }
+                let mut packages = reenabled;
+                let skipped = packages
+                    .drain_filter(|i| self.skips(*i, Action::Remove))
+                    .count();
+                self.start_batch(packages, skipped, false, settings, selected_device)
+            }
+            Message::DismissConnectProfileReport => {
+                self.connect_profile_report = None;
                 Command::none()
             }
-            Message::List(i_package, row_message) => {
-                self.phone_packages[i_user][i_package]
-                    .update(row_message.clone())
-                    .map(move |row_message| Message::List(i_package, row_message));
-
-                let package = &mut self.phone_packages[i_user][i_package];
-
-                match row_message {
-                    RowMessage::ToggleSelection(toggle) => {
-                        if package.removal == Removal::Unsafe && !settings.general.expert_mode {
-                            package.selected = false;
-                        } else {
-                            package.selected = toggle;
-
-                            if package.selected {
-                                self.selection.selected_packages.push(i_package);
-                            } else {
-                                self.selection
-                                    .selected_packages
-                                    .drain_filter(|s_i| *s_i == i_package);
+            Message::StartUser(user) => {
+                let serial = selected_device.adb_id.clone();
+                Command::perform(async move { start_user(&serial, &user) }, move |result| {
+                    Message::UserStarted(user, result)
+                })
+            }
+            Message::UserStarted(user, result) => {
+                match &result {
+                    Ok(()) => {
+                        info!("[USERS] {} started", user);
+                        for u in &mut selected_device.user_list {
+                            if u.id == user.id {
+                                u.paused = false;
                             }
-                            update_selection_count(
-                                &mut self.selection,
-                                package.state,
-                                package.selected,
-                            );
                         }
-                        Command::none()
-                    }
-                    RowMessage::ActionPressed => {
-                        let mut commands = vec![];
-                        let actions = action_handler(
-                            &self.selected_user.unwrap(),
-                            &package.into(),
-                            selected_device,
-                            &settings.device,
-                        );
-
-                        for (i, (i_user, action)) in actions.into_iter().enumerate() {
-                            let p_info = PackageInfo {
-                                i_user,
-                                index: i_package,
-                                removal: package.removal.to_string(),
-                            };
-                            // Only the first command can change the package state
-                            commands.push(Command::perform(
-                                perform_adb_commands(action, CommandType::PackageManager(p_info)),
-                                if i == 0 {
-                                    Message::ChangePackageState
-                                } else {
-                                    |_| Message::Nothing
-                                },
-                            ));
+                        if let Some(selected) = self.selected_user.filter(|u| u.id == user.id) {
+                            self.selected_user = Some(User {
+                                paused: false,
+                                ..selected
+                            });
                         }
-                        Command::batch(commands)
                     }
-                    RowMessage::PackagePressed => {
-                        self.description = package.clone().description;
-                        package.current = true;
-                        if self.current_package_index != i_package {
-                            self.phone_packages[i_user][self.current_package_index].current = false;
-                        }
+                    Err(e) => error!("[USERS] Could not start {}: {}", user, e),
+                }
+                self.user_start = Some(result);
+                Command::none()
+            }
+            Message::DismissReenabled => {
+                self.reenabled.clear();
+                Command::none()
+            }
+            Message::DismissForcedUninstalls => {
+                self.forced_uninstalls.clear();
+                Command::none()
+            }
+            Message::DiscardRecoveredSelection => {
+                self.recovered_selection = None;
+                if self.selection.selected_packages.is_empty() {
+                    clear_recovery(&selected_device.adb_id);
+                    self.recovery_saved.clear();
+                }
+                Command::none()
+            }
+            Message::RefreshPackages => {
+                // The indexes of pending commands wouldn't match the reloaded packages
+                if !matches!(self.loading_state, LoadingState::Ready(_))
+                    || self.commands_status.pending > 0
+                {
+                    return Command::none();
+                }
+                self.loading_state = LoadingState::LoadingPackages(selected_device.model.clone());
+                Command::perform(
+                    Self::load_packages(self.uad_lists.clone(), selected_device.clone()),
+                    Message::PackagesRefreshed,
+                )
+            }
+            Message::PackagesRefreshed(Err(err)) => {
+                // The packages shown are still the last known ones
+                error!("Could not refresh the packages: {}", err);
+                self.loading_state = LoadingState::Ready("".to_string());
+                Command::none()
+            }
+            Message::PackagesRefreshed(Ok(packages)) => {
+                // Unlike `ApplyFilters`, the user, filters and selection are kept.
+                // Packages are matched by name: indexes can change between two loads.
+                let selected_names: HashSet<String> = self
+                    .selection
+                    .selected_packages
+                    .iter()
+                    .map(|i| self.phone_packages[i_user][*i].name.clone())
+                    .collect();
+                let protected_names: HashSet<String> = self.phone_packages[i_user]
+                    .iter()
+                    .filter(|p| p.protected)
+                    .map(|p| p.name.clone())
+                    .collect();
+                let current_name = if self.description.is_empty() {
+                    None
+                } else {
+                    Some(
+                        self.phone_packages[i_user][self.current_package_index]
+                            .name
+                            .clone(),
+                    )
+                };
+
+                self.phone_packages = packages;
+                if self.phone_packages.get(i_user).is_none() {
+                    self.selected_user = Some(User::default());
+                }
+                let i_user = self.selected_user.unwrap_or(User::default()).index;
+
+                for package in self.phone_packages.iter_mut().flatten() {
+                    package.protected = protected_names.contains(&package.name);
+                }
+                self.selection.selected_packages.clear();
+                for i_package in 0..self.phone_packages[i_user].len() {
+                    if selected_names.contains(&self.phone_packages[i_user][i_package].name) {
+                        self.set_package_selected(i_package, true, settings);
+                    }
+                }
+                match current_name.and_then(|name| {
+                    self.phone_packages[i_user]
+                        .iter()
+                        .position(|p| p.name == name)
+                }) {
+                    Some(i_package) => {
                         self.current_package_index = i_package;
-                        Command::none()
+                        self.phone_packages[i_user][i_package].current = true;
+                    }
+                    None => {
+                        self.current_package_index = 0;
+                        self.description.clear();
+                        self.note_input.clear();
                     }
                 }
-            }
-            Message::ApplyActionOnSelection(action) => {
-                let mut selected_packages = self.selection.selected_packages.clone();
 
-                match action {
-                    Action::Remove => {
-                        selected_packages.drain_filter(|i| {
-                            self.phone_packages[i_user][*i].state != PackageState::Enabled
-                        });
+                self.user_exclusions.clear();
+                self.undo_toast = None;
+                self.compared_snapshot = None;
+                self.snapshot_changes.clear();
+                self.focused_index = None;
+                self.last_clicked_index = None;
+                self.pending_timestamps.clear();
+                self.pending_permissions.clear();
+                self.filtered_packages = (0..self.phone_packages[i_user].len()).collect();
+                self.filter_package_lists(&settings.general);
+                self.loading_state = LoadingState::Ready("".to_string());
+                self.start_details_load(selected_device)
+            }
+            Message::DetailsLoaded(generation, details) => {
+                if generation != self.details_generation {
+                    return Command::none();
+                }
+                for (i_user, i_package, disabled_by) in details {
+                    // The state may have changed while `dumpsys` was running
+                    if let Some(package) = self
+                        .phone_packages
+                        .get_mut(i_user)
+                        .and_then(|packages| packages.get_mut(i_package))
+                        .filter(|p| p.state == PackageState::Disabled)
+                    {
+                        package.disabled_by = disabled_by;
                     }
-                    Action::Restore => {
-                        selected_packages.drain_filter(|i| {
-                            self.phone_packages[i_user][*i].state == PackageState::Enabled
-                        });
+                }
+                self.load_next_details(selected_device)
+            }
+            Message::DismissListChanges => {
+                self.list_changes = None;
+                Command::none()
+            }
+            Message::PackagesScrolled(offset) => {
+                self.scroll_offset = offset;
+                Command::none()
+            }
+            Message::ScrollToTop => {
+                self.scroll_offset = 0.0;
+                scrollable::snap_to(PACKAGES_SCROLLABLE_ID.clone(), 0.0)
+            }
+            Message::ToggleAllSelected(selected) => {
+                let count = self.selectable_filtered_count(settings);
+                // Most of them are off screen: a large selection is easily missed
+                if selected && count > 0 && count >= settings.general.confirm_threshold {
+                    self.confirmation = Some(Confirmation {
+                        action: PendingAction::SelectAll,
+                        warnings: vec![format!(
+                            "This selects all {} filtered packages, not only the ones on screen.",
+                            count
+                        )],
+                        preview: vec![],
+                    });
+                    return Command::none();
+                }
+                self.select_all_filtered(selected, settings);
+                Command::none()
+            }
+            Message::InvertSelection => {
+                // Unsafe packages stay unselected without expert mode (see `set_package_selected`)
+                for i in self.filtered_packages.clone() {
+                    let selected = self.phone_packages[i_user][i].selected;
+                    self.set_package_selected(i, !selected, settings);
+                }
+                Command::none()
+            }
+            Message::SelectRecommended => {
+                for i in 0..self.phone_packages[i_user].len() {
+                    let package = &self.phone_packages[i_user][i];
+                    if package.removal == Removal::Recommended
+                        && package.state == PackageState::Enabled
+                    {
+                        self.set_package_selected(i, true, settings);
                     }
                 }
-                let mut commands = vec![];
-                for i in selected_packages {
-                    let actions = action_handler(
-                        &self.selected_user.unwrap(),
-                        &(&self.phone_packages[i_user][i]).into(),
+                Command::none()
+            }
+            Message::DescriptionResized(delta) => {
+                // Dragging the splitter up makes the description panel taller
+                let height = f32::from(settings.general.description_height) - delta;
+                settings.general.description_height =
+                    height.clamp(MIN_DESCRIPTION_HEIGHT, MAX_DESCRIPTION_HEIGHT) as u16;
+                Command::none()
+            }
+            Message::DescriptionResizeEnded => {
+                Config::save_changes(settings, &selected_device.adb_id);
+                Command::none()
+            }
+            Message::JumpToLetter(letter) => {
+                let packages = &self.phone_packages[i_user];
+                match self.filtered_packages.iter().position(|i| {
+                    packages[*i]
+                        .name
+                        .chars()
+                        .next()
+                        .map_or(false, |c| c.eq_ignore_ascii_case(&letter))
+                }) {
+                    Some(pos) => {
+                        // Rows all have the same height so the position in the filtered list
+                        // is a good enough approximation of the scroll offset
+                        let offset = pos as f32 / (self.filtered_packages.len().max(2) - 1) as f32;
+                        scrollable::snap_to(PACKAGES_SCROLLABLE_ID.clone(), offset)
+                    }
+                    None => Command::none(),
+                }
+            }
+            Message::SearchInputChanged(letter) => {
+                self.input_value = letter;
+                self.search_generation += 1;
+                // Clearing the search brings back the full list right away
+                if self.input_value.is_empty() {
+                    self.filter_package_lists(&settings.general);
+                    return Command::none();
+                }
+                let generation = self.search_generation;
+                Command::perform(
+                    async move {
+                        tokio::time::sleep(Duration::from_millis(SEARCH_DEBOUNCE_DELAY)).await;
+                        generation
+                    },
+                    Message::SearchDebounced,
+                )
+            }
+            Message::SearchDebounced(generation) => {
+                if generation == self.search_generation {
+                    self.filter_package_lists(&settings.general);
+                }
+                Command::none()
+            }
+            Message::ListQueryChanged(query) => {
+                self.list_query = query;
+                self.list_highlight = 0;
+                Command::none()
+            }
+            Message::ListQuerySubmitted => match self.matching_lists().get(self.list_highlight) {
+                Some(list) => {
+                    let list = *list;
+                    self.update(
+                        settings,
                         selected_device,
-                        &settings.device,
-                    );
-
+                        list_update_state,
+                        Message::ListSelected(list),
+                    )
+                }
+                None => Command::none(),
+            },
+            Message::ListSelected(list) => self.change_filters(&settings.general, |list_view| {
+                list_view.list_query.clear();
+                list_view.selected_list = Some(list);
+            }),
+            Message::PackageStateSelected(package_state) => {
+                self.change_filters(&settings.general, |list_view| {
+                    list_view.selected_package_state = Some(package_state);
+                })
+            }
+            Message::RestorabilitySelected(restorability) => {
+                self.change_filters(&settings.general, |list_view| {
+                    list_view.selected_restorability = restorability;
+                })
+            }
+            Message::RemovalToggled(removal, toggled) => {
+                if settings.general.safe_mode {
+                    return Command::none();
+                }
+                self.change_filters(&settings.general, |list_view| {
+                    if toggled {
+                        list_view.selected_removals.insert(removal);
+                    } else {
+                        list_view.selected_removals.remove(&removal);
+                    }
+                })
+            }
+            Message::ClearFilter(filter) => self.change_filters(&settings.general, |list_view| {
+                list_view.reset_filter(filter, &settings.general);
+            }),
+            Message::ClearAllFilters => self.change_filters(&settings.general, |list_view| {
+                for filter in [
+                    Filter::List,
+                    Filter::State,
+                    Filter::Removal,
+                    Filter::Restorability,
+                    Filter::SessionChanges,
+                    Filter::Search,
+                ] {
+                    list_view.reset_filter(filter, &settings.general);
+                }
+            }),
+            Message::SessionExpertModeToggled(toggled) => {
+                settings.general.session_expert_mode = toggled;
+                // Unsafe packages selected while it was on
+                for i in self.selection.selected_packages.clone() {
                     let package = &mut self.phone_packages[i_user][i];
-                    for (j, (i_user, action)) in actions.into_iter().enumerate() {
-                        let p_info = PackageInfo {
-                            i_user,
-                            index: i,
-                            removal: package.removal.to_string(),
-                        };
-                        // Only the first command can change the package state
-                        commands.push(Command::perform(
-                            perform_adb_commands(action, CommandType::PackageManager(p_info)),
-                            if j == 0 {
-                                Message::ChangePackageState
-                            } else {
-                                |_| Message::Nothing
-                            },
-                        ));
+                    if !settings.general.can_remove(package.removal) {
+                        package.selected = false;
+                        self.selection.set(i, false);
                     }
                 }
-                Command::batch(commands)
+                Command::none()
             }
-            Message::UserSelected(user) => {
-                for p in &mut self.phone_packages[i_user] {
-                    p.selected = false;
+            Message::ShowAllowListedToggled(toggled) => {
+                // Nothing hidden can be acted upon
+                if !toggled {
+                    for i in self.selection.selected_packages.clone() {
+                        let package = &mut self.phone_packages[i_user][i];
+                        if settings
+                            .general
+                            .allow_listed_packages
+                            .contains(&package.name)
+                        {
+                            package.selected = false;
+                            self.selection.set(i, false);
+                        }
+                    }
                 }
-                self.selected_user = Some(user);
-                for i_package in &self.selection.selected_packages {
-                    self.phone_packages[user.index][*i_package].selected = true;
+                self.change_filters(&settings.general, |list_view| {
+                    list_view.show_allow_listed = toggled;
+                })
+            }
+            Message::SessionChangesToggled(toggled) => {
+                self.change_filters(&settings.general, |list_view| {
+                    list_view.only_session_changes = toggled;
+                })
+            }
+            Message::SortByLastUpdate(toggled) => {
+                self.sort_by_last_update = toggled;
+                self.filter_package_lists(&settings.general);
+                Command::none()
+            }
+            Message::SearchFieldToggled(field, toggled) => {
+                if toggled {
+                    self.search_fields.insert(field);
+                } else {
+                    self.search_fields.remove(&field);
+                }
+                if !self.input_value.is_empty() {
+                    self.filter_package_lists(&settings.general);
                 }
-                self.filtered_packages = (0..self.phone_packages[user.index].len()).collect();
-                Self::filter_package_lists(self);
                 Command::none()
             }
-            Message::ChangePackageState(res) => {
-                if let Ok(CommandType::PackageManager(p)) = res {
-                    let package = &mut self.phone_packages[i_user][p.index];
-                    update_selection_count(&mut self.selection, package.state, false);
-
-                    if !settings.device.multi_user_mode || p.i_user.is_none() {
-                        package.state = package.state.opposite(settings.device.disable_mode);
-                        package.selected = false;
-                    } else {
-                        self.phone_packages[p.i_user.unwrap()][p.index].state = self.phone_packages
-                            [p.i_user.unwrap()][p.index]
-                            .state
-                            .opposite(settings.device.disable_mode);
-                        self.phone_packages[p.i_user.unwrap()][p.index].selected = false;
+            Message::SelectedFirstToggled(toggled) => {
+                self.selected_first = toggled;
+                self.filter_package_lists(&settings.general);
+                Command::none()
+            }
+            Message::TimestampsFetched(i_package, timestamps) => {
+                self.pending_timestamps.remove(&i_package);
+                if timestamps.is_none() {
+                    warn!(
+                        "Could not get the install dates of {}",
+                        self.phone_packages[i_user][i_package].name
+                    );
+                }
+                // Install dates don't depend on the user
+                for packages in &mut self.phone_packages {
+                    if let Some(p) = packages.get_mut(i_package) {
+                        p.timestamps = timestamps.clone();
                     }
-                    self.selection
-                        .selected_packages
-                        .drain_filter(|s_i| *s_i == p.index);
-                    Self::filter_package_lists(self);
+                }
+                if self.sort_by_last_update {
+                    self.filter_package_lists(&settings.general);
                 }
                 Command::none()
             }
-            Message::Nothing => Command::none(),
-        }
-    }
-
-    pub fn view(
-        &self,
-        settings: &Settings,
-        selected_device: &Phone,
-    ) -> Element<Message, Renderer<Theme>> {
-        match &self.loading_state {
-            LoadingState::DownloadingList(_) => {
-                let text = "Downloading latest UAD lists from Github. Please wait...";
-                waiting_view(settings, text, true)
+            Message::PermissionsFetched(i_package, permissions) => {
+                self.pending_permissions.remove(&i_package);
+                if permissions.is_none() {
+                    warn!(
+                        "Could not get the permissions of {}",
+                        self.phone_packages[i_user][i_package].name
+                    );
+                }
+                for packages in &mut self.phone_packages {
+                    if let Some(p) = packages.get_mut(i_package) {
+                        p.permissions = permissions.clone();
+                    }
+                }
+                Command::none()
             }
-            LoadingState::FindingPhones(_) => {
-                let text = "Finding connected devices...";
-                waiting_view(settings, text, false)
+            Message::ApkCountFetched(i_package, count) => {
+                for packages in &mut self.phone_packages {
+                    if let Some(p) = packages.get_mut(i_package) {
+                        p.apk_count = Some(count);
+                    }
+                }
+                Command::none()
             }
-            LoadingState::LoadingPackages(_) => {
-                let text = "Pulling packages from the device. Please wait...";
-                waiting_view(settings, text, false)
+            Message::BackupApk => {
+                self.apk_report = Some("Backing up APK files...".to_string());
+                Command::perform(
+                    backup_apks(
+                        selected_device.adb_id.clone(),
+                        self.phone_packages[i_user][self.current_package_index]
+                            .name
+                            .clone(),
+                    ),
+                    Message::ApkBackedUp,
+                )
             }
-            LoadingState::_UpdatingUad(_) => {
-                let text = "Updating UAD. Please wait...";
-                waiting_view(settings, text, false)
+            Message::ApkBackedUp(result) => {
+                self.apk_backup_available = has_apk_backup(
+                    &selected_device.adb_id,
+                    &self.phone_packages[i_user][self.current_package_index].name,
+                );
+                self.apk_report = Some(match result {
+                    Ok(count) => format!("{} APK file(s) backed up", count),
+                    Err(e) => format!("APK backup failed: {}", e),
+                });
+                Command::none()
             }
-            LoadingState::RestoringDevice(output) => {
-                let text = format!("Restoring device: {}", output);
-                waiting_view(settings, &text, false)
+            Message::RestoreApk => {
+                let i_package = self.current_package_index;
+                self.apk_report = Some("Installing from the APK backup...".to_string());
+                Command::perform(
+                    restore_apks(
+                        selected_device.adb_id.clone(),
+                        self.phone_packages[i_user][i_package].name.clone(),
+                    ),
+                    move |result| Message::ApkRestored(i_package, result),
+                )
             }
-            LoadingState::Ready(_) => {
-                let search_packages = text_input(
-                    "Search packages...",
-                    &self.input_value,
-                    Message::SearchInputChanged,
+            Message::ApkRestored(i_package, result) => {
+                self.apk_report = Some(match result {
+                    Ok(count) => {
+                        self.phone_packages[i_user][i_package].state = PackageState::Enabled;
+                        self.filter_package_lists(&settings.general);
+                        format!("Installed from {} APK file(s)", count)
+                    }
+                    Err(e) => {
+                        error!("[APK RESTORE] {}", e);
+                        format!("APK installation failed: {}", e)
+                    }
+                });
+                Command::none()
+            }
+            Message::List(i_package, row_message) => {
+                self.phone_packages[i_user][i_package]
+                    .update(row_message.clone())
+                    .map(move |row_message| Message::List(i_package, row_message));
+
+                let package = &mut self.phone_packages[i_user][i_package];
+
+                match row_message {
+                    RowMessage::ToggleSelection(toggle) => {
+                        match self.last_clicked_index.filter(|_| self.shift_pressed) {
+                            Some(anchor) => self.select_range(anchor, i_package, toggle, settings),
+                            None => self.set_package_selected(i_package, toggle, settings),
+                        }
+                        self.last_clicked_index = Some(i_package);
+                        Command::none()
+                    }
+                    RowMessage::ActionPressed => {
+                        if self.phone_packages[i_user][i_package].locked() {
+                            return Command::none();
+                        }
+                        let mut warnings = self.removal_warnings(&[i_package], settings);
+                        warnings.extend(paused_users_warning(
+                            &self.target_users(settings, selected_device),
+                        ));
+                        if warnings.is_empty() {
+                            self.apply_package_action(i_package, settings, selected_device)
+                        } else {
+                            self.confirmation = Some(Confirmation {
+                                action: PendingAction::Package(i_package),
+                                warnings,
+                                preview: self.action_preview(
+                                    &[i_package],
+                                    settings,
+                                    selected_device,
+                                ),
+                            });
+                            Command::none()
+                        }
+                    }
+                    RowMessage::ToggleUserChip(u_index) => {
+                        if !self.user_exclusions.remove(&(i_package, u_index)) {
+                            self.user_exclusions.insert((i_package, u_index));
+                        }
+                        Command::none()
+                    }
+                    RowMessage::TogglePin => {
+                        let pinned = &mut settings.general.pinned_packages;
+                        match pinned.iter().position(|name| *name == package.name) {
+                            Some(pos) => {
+                                pinned.remove(pos);
+                            }
+                            None => pinned.push(package.name.clone()),
+                        }
+                        Config::save_changes(settings, &selected_device.adb_id);
+                        self.filter_package_lists(&settings.general);
+                        Command::none()
+                    }
+                    RowMessage::ToggleAllowList => {
+                        let allow_list = &mut settings.general.allow_listed_packages;
+                        match allow_list.iter().position(|name| *name == package.name) {
+                            Some(pos) => {
+                                allow_list.remove(pos);
+                            }
+                            None => {
+                                allow_list.push(package.name.clone());
+                                // Nothing hidden can be acted upon
+                                if !self.show_allow_listed {
+                                    package.selected = false;
+                                    self.selection.set(i_package, false);
+                                }
+                            }
+                        }
+                        Config::save_changes(settings, &selected_device.adb_id);
+                        self.filter_package_lists(&settings.general);
+                        Command::none()
+                    }
+                    // Already applied by the row
+                    RowMessage::CycleActionOverride => Command::none(),
+                    RowMessage::RefreshPressed => {
+                        self.refresh_package(i_package, i_user, selected_device)
+                    }
+                    RowMessage::Hovered(true) => {
+                        self.hovered_index = Some(i_package);
+                        Command::none()
+                    }
+                    RowMessage::ContextMenuRequested => {
+                        self.context_menu = Some(i_package);
+                        Command::none()
+                    }
+                    RowMessage::Hovered(false) => {
+                        // The next row may have been entered first
+                        if self.hovered_index == Some(i_package) {
+                            self.hovered_index = None;
+                        }
+                        Command::none()
+                    }
+                    RowMessage::PackagePressed => {
+                        self.description = package.clone().description;
+                        self.description_expanded = false;
+                        self.note_input =
+                            self.notes.get(&package.name).cloned().unwrap_or_default();
+                        package.current = true;
+                        if self.current_package_index != i_package {
+                            self.phone_packages[i_user][self.current_package_index].current = false;
+                            self.apk_report = None;
+                        }
+                        self.current_package_index = i_package;
+                        self.focused_index = Some(i_package);
+                        self.apk_backup_available = has_apk_backup(
+                            &selected_device.adb_id,
+                            &self.phone_packages[i_user][i_package].name,
+                        );
+
+                        // Querying the device for every package would make loading much slower
+                        let package = &self.phone_packages[i_user][i_package];
+                        let name = package.name.clone();
+                        let serial = selected_device.adb_id.clone();
+                        let timestamps_command = if package.timestamps.is_none()
+                            && self.pending_timestamps.insert(i_package)
+                        {
+                            let (name, serial) = (name.clone(), serial.clone());
+                            Command::perform(
+                                async move { get_package_timestamps(&name, &serial) },
+                                move |timestamps| Message::TimestampsFetched(i_package, timestamps),
+                            )
+                        } else {
+                            Command::none()
+                        };
+                        let permissions_command = if package.permissions.is_none()
+                            && self.pending_permissions.insert(i_package)
+                        {
+                            let (name, serial) = (name.clone(), serial.clone());
+                            Command::perform(
+                                async move { get_package_permissions(&name, &serial) },
+                                move |permissions| {
+                                    Message::PermissionsFetched(i_package, permissions)
+                                },
+                            )
+                        } else {
+                            Command::none()
+                        };
+                        let suspended_command = if package.suspended.is_none()
+                            && package.state == PackageState::Enabled
+                            && selected_device.android_sdk >= SUSPEND_MIN_SDK
+                        {
+                            let (name, serial) = (name.clone(), serial.clone());
+                            let user = self.selected_user;
+                            Command::perform(
+                                async move { get_suspended(&name, user.as_ref(), &serial) },
+                                move |suspended| {
+                                    Message::SuspensionFetched(i_user, i_package, suspended)
+                                },
+                            )
+                        } else {
+                            Command::none()
+                        };
+                        // Uninstalled packages have no APK on the device
+                        let apk_count_command = if package.apk_count.is_none()
+                            && package.state != PackageState::Uninstalled
+                        {
+                            Command::perform(
+                                async move { get_apk_paths(&name, &serial).len() },
+                                move |count| Message::ApkCountFetched(i_package, count),
+                            )
+                        } else {
+                            Command::none()
+                        };
+                        Command::batch([
+                            timestamps_command,
+                            permissions_command,
+                            suspended_command,
+                            apk_count_command,
+                        ])
+                    }
+                }
+            }
+            Message::ApplyActionOnSelection(action @ (Action::Suspend | Action::Unsuspend)) => {
+                let mut selected_packages = self.selection.selected_packages.clone();
+                selected_packages.drain_filter(|i| self.skips(*i, action));
+                self.suspend_packages(
+                    &selected_packages,
+                    action == Action::Suspend,
+                    settings,
+                    selected_device,
                 )
-                .padding(5);
+            }
+            Message::ApplyActionOnSelection(action) => {
+                let mut selected_packages = self.selection.selected_packages.clone();
+                let skipped = selected_packages
+                    .drain_filter(|i| self.skips(*i, action))
+                    .count();
+                self.start_batch(selected_packages, skipped, false, settings, selected_device)
+            }
+            Message::EnableAndStopSelection => {
+                let mut selected_packages = self.selection.selected_packages.clone();
+                let skipped = selected_packages
+                    .drain_filter(|i| self.skips(*i, Action::Restore))
+                    .count();
+                self.start_batch(selected_packages, skipped, true, settings, selected_device)
+            }
+            Message::QueueSelection(action) => {
+                for i in self.selection.selected_packages.clone() {
+                    let name = self.phone_packages[i_user][i].name.clone();
+                    match self.queue.iter_mut().find(|(n, _)| *n == name) {
+                        Some(entry) => entry.1 = action,
+                        None => self.queue.push((name, action)),
+                    }
+                    // Staged: out of the selection
+                    self.set_package_selected(i, false, settings);
+                }
+                Command::none()
+            }
+            Message::MoveQueued(pos, up) => {
+                let other = if up {
+                    pos.checked_sub(1)
+                } else {
+                    Some(pos + 1)
+                };
+                if let Some(other) = other.filter(|o| *o < self.queue.len()) {
+                    self.queue.swap(pos, other);
+                }
+                Command::none()
+            }
+            Message::RemoveQueued(pos) => {
+                if pos < self.queue.len() {
+                    self.queue.remove(pos);
+                }
+                Command::none()
+            }
+            Message::ClearQueue => {
+                self.queue.clear();
+                Command::none()
+            }
+            Message::RunQueue => {
+                let mut packages = vec![];
+                let mut skipped = 0;
+                for (name, action) in std::mem::take(&mut self.queue) {
+                    match self.phone_packages[i_user]
+                        .iter()
+                        .position(|p| p.name == name)
+                    {
+                        Some(i) if !self.skips(i, action) => packages.push(i),
+                        _ => skipped += 1,
+                    }
+                }
+                self.start_batch(packages, skipped, false, settings, selected_device)
+            }
+            Message::ConfirmAction => match self.confirmation.take() {
+                Some(Confirmation {
+                    action: PendingAction::Package(i_package),
+                    ..
+                }) => self.apply_package_action(i_package, settings, selected_device),
+                Some(Confirmation {
+                    action: PendingAction::UserPackage(i_package, user),
+                    ..
+                }) => self.apply_user_package_action(i_package, user, settings, selected_device),
+                Some(Confirmation {
+                    action:
+                        PendingAction::Batch {
+                            packages,
+                            skipped,
+                            force_stop,
+                        },
+                    ..
+                }) => self.apply_batch(packages, skipped, force_stop, settings, selected_device),
+                Some(Confirmation {
+                    action: PendingAction::Reboot,
+                    ..
+                }) => Command::perform(async {}, |_| Message::RebootConfirmed),
+                Some(Confirmation {
+                    action: PendingAction::SelectAll,
+                    ..
+                }) => {
+                    self.select_all_filtered(true, settings);
+                    Command::none()
+                }
+                Some(Confirmation {
+                    action: PendingAction::AutoApplyProfile,
+                    ..
+                }) => {
+                    settings.device.auto_apply_confirmed = true;
+                    Config::save_changes(settings, &selected_device.adb_id);
+                    let mut packages = self.selection.selected_packages.clone();
+                    let skipped = packages
+                        .drain_filter(|i| self.skips(*i, Action::Remove))
+                        .count();
+                    self.apply_batch(packages, skipped, false, settings, selected_device)
+                }
+                None => Command::none(),
+            },
+            Message::CancelAction => {
+                self.confirmation = None;
+                Command::none()
+            }
+            Message::RetryFailedPackages => match self.batch.take() {
+                Some(batch) => {
+                    self.apply_batch(batch.failed, 0, batch.force_stop, settings, selected_device)
+                }
+                None => Command::none(),
+            },
+            Message::BatchChecked(generation, packages, skipped, force_stop, warnings) => {
+                // The list was reloaded in the meantime
+                if generation != self.run_generation {
+                    return Command::none();
+                }
+                self.confirm_batch(
+                    packages,
+                    skipped,
+                    force_stop,
+                    warnings,
+                    settings,
+                    selected_device,
+                )
+            }
+            Message::BatchVerified(generation, i_package, u_index, expected, state) => {
+                // Indices of an earlier run may no longer match the list
+                if generation != self.run_generation {
+                    return Command::none();
+                }
+                if let Some(batch) = &mut self.batch {
+                    batch.verifying = batch.verifying.saturating_sub(1);
+                    let package = self
+                        .phone_packages
+                        .get_mut(u_index)
+                        .and_then(|packages| packages.get_mut(i_package));
+                    match (state, package) {
+                        (Some(state), Some(package)) if state != expected => {
+                            warn!(
+                                "[VERIFY] {} is {} instead of {}",
+                                package.name, state, expected
+                            );
+                            package.verification_failed = true;
+                            batch.succeeded.retain(|i| *i != i_package);
+                            for packages in [&mut batch.failed, &mut batch.unverified] {
+                                if !packages.contains(&i_package) {
+                                    packages.push(i_package);
+                                }
+                            }
+                            batch.errors.insert(
+                                i_package,
+                                format!("Verification failed: {} instead of {}", state, expected),
+                            );
+                        }
+                        _ => {}
+                    }
+                }
+                // The list shows what the device reported
+                self.update(
+                    settings,
+                    selected_device,
+                    list_update_state,
+                    Message::PackageRefreshed(i_package, u_index, state),
+                )
+            }
+            Message::CloseBatchSummary => {
+                self.batch = None;
+                Command::none()
+            }
+            Message::ToggleBatchFailures => {
+                if let Some(batch) = &mut self.batch {
+                    batch.show_failures = !batch.show_failures;
+                }
+                Command::none()
+            }
+            Message::UserSelected(user) => {
+                if user.index >= self.phone_packages.len() {
+                    warn!("No packages loaded for {}", user);
+                    return Command::none();
+                }
+                for p in &mut self.phone_packages[i_user] {
+                    p.selected = false;
+                }
+                self.selected_user = Some(user);
+                self.user_start = None;
+                self.hovered_index = None;
+                // Rows are aligned across users but matching on the id doesn't rely on it.
+                // A package the user doesn't have stays selected, and is listed in a notice.
+                self.missing_for_user.clear();
+                for i_package in self.selection.selected_packages.clone() {
+                    let previous = match self.phone_packages[i_user].get(i_package) {
+                        Some(p) => p.clone(),
+                        None => continue,
+                    };
+                    match self.phone_packages[user.index]
+                        .iter()
+                        .position(|p| p.name == previous.name)
+                    {
+                        Some(i) => {
+                            let package = &mut self.phone_packages[user.index][i];
+                            package.selected = true;
+                            if package.state == PackageState::Uninstalled
+                                && previous.state != PackageState::Uninstalled
+                            {
+                                self.missing_for_user.push(previous.name);
+                            }
+                            if i != i_package {
+                                self.selection.set(i_package, false);
+                                self.selection.set(i, true);
+                            }
+                        }
+                        None => self.missing_for_user.push(previous.name),
+                    }
+                }
+                self.filtered_packages = (0..self.phone_packages[user.index].len()).collect();
+                self.filter_package_lists(&settings.general);
+                Command::none()
+            }
+            Message::DismissMissingForUser => {
+                self.missing_for_user.clear();
+                Command::none()
+            }
+            Message::AllUsersToggled(toggled) => {
+                self.all_users = toggled;
+                Command::none()
+            }
+            Message::IncludeProtectedToggled(toggled) => {
+                self.include_protected = toggled;
+                Command::none()
+            }
+            Message::ChangePackageState(Err(AdbError {
+                command_type: CommandType::PackageManager(p),
+                stderr,
+                ..
+            })) => {
+                if is_device_admin_error(&stderr) {
+                    for packages in &mut self.phone_packages {
+                        if let Some(package) = packages.get_mut(p.index) {
+                            package.protected = true;
+                        }
+                    }
+                }
+                if let Some(batch) = &mut self.batch {
+                    batch.errors.insert(p.index, stderr);
+                }
+                self.resolve_batch_command(Some(p.index), false, &settings.general, selected_device)
+            }
+            Message::ChangePackageState(res) => {
+                let mut batch_command = Command::none();
+                let mut refresh_command = Command::none();
+                if let Ok(CommandType::PackageManager(p)) = res {
+                    // Commands can target another user than the selected one even without
+                    // multi-user mode (undo, enable for all users)
+                    let target_user = p.i_user.unwrap_or(i_user);
+                    // Suspended packages stay enabled: neither the state nor the history change
+                    if let Some(suspend) = p.suspend {
+                        if !settings.general.keep_selection_after_action {
+                            self.selection.set(p.index, false);
+                            self.phone_packages[i_user][p.index].selected = false;
+                        }
+                        let package = &mut self.phone_packages[target_user][p.index];
+                        package.suspended = Some(suspend);
+                        if !self.session_changes.contains(&package.name) {
+                            self.session_changes.push(package.name.clone());
+                        }
+                        return self.resolve_batch_command(
+                            Some(p.index),
+                            true,
+                            &settings.general,
+                            selected_device,
+                        );
+                    }
+                    batch_command = self.resolve_batch_command(
+                        Some(p.index),
+                        true,
+                        &settings.general,
+                        selected_device,
+                    );
+                    // The override is set on the row of the selected user
+                    let disable = !p.uninstalled_instead
+                        && (p.force_disable
+                            || self.phone_packages[i_user][p.index].disables(&settings.device));
+                    let expected = self.phone_packages[target_user][p.index]
+                        .state
+                        .opposite(disable);
+
+                    // In multi-user mode, this is received once per affected user.
+                    // A restored package the user can't remove again doesn't stay selected.
+                    let keep_selected = settings.general.keep_selection_after_action
+                        && (expected != PackageState::Enabled
+                            || settings
+                                .general
+                                .can_remove(self.phone_packages[i_user][p.index].removal));
+                    if !keep_selected {
+                        self.selection.set(p.index, false);
+                        self.phone_packages[i_user][p.index].selected = false;
+                        self.phone_packages[target_user][p.index].selected = false;
+                    }
+
+                    if let Some(batch) = &mut self.batch {
+                        batch.expected.insert((p.index, target_user), expected);
+                    }
+                    let package = &mut self.phone_packages[target_user][p.index];
+                    package.verification_failed = false;
+                    // Otherwise the refresh below sets the state
+                    if !settings.general.verify_package_state {
+                        package.state = expected;
+                        package.disabled_by =
+                            (expected == PackageState::Disabled).then_some(DisabledBy::User);
+                    }
+                    // Lost with the package, kept by a disable
+                    if expected == PackageState::Uninstalled {
+                        package.suspended = None;
+                    }
+                    if !self.session_changes.contains(&package.name) {
+                        self.session_changes.push(package.name.clone());
+                    }
+                    if p.uninstalled_instead && !self.forced_uninstalls.contains(&package.name) {
+                        self.forced_uninstalls.push(package.name.clone());
+                    }
+                    if settings.general.status_sink && !settings.general.status_path.is_empty() {
+                        let event =
+                            StatusEvent::new(&selected_device.adb_id, &package.name, expected);
+                        // Best effort: the sink must never get in the way of the actions
+                        let path = artifact_path(&DATA_DIR, &settings.general.status_path);
+                        if let Err(e) = append_status(&path, &event) {
+                            warn!(
+                                "[STATUS] Could not write {}: {}",
+                                settings.general.status_path, e
+                            );
+                        }
+                    }
+                    let removed = expected != PackageState::Enabled;
+                    if removed {
+                        record_history(&selected_device.adb_id, &package.name, expected);
+                    }
+                    self.filter_package_lists(&settings.general);
+                    // The device doesn't always end up in the state we assumed
+                    refresh_command = self.refresh_package(p.index, target_user, selected_device);
+
+                    if removed {
+                        // Several removals in a short time (e.g. a batch) share the same toast
+                        let toast = self.undo_toast.get_or_insert_with(UndoToast::default);
+                        toast.packages.push((p.index, target_user));
+                        toast.last_removal = Some(Instant::now());
+                    }
+                }
+                Command::batch([batch_command, refresh_command])
+            }
+            Message::PackageRefreshed(i_package, u_index, Some(state)) => {
+                if let Some(package) = self
+                    .phone_packages
+                    .get_mut(u_index)
+                    .and_then(|packages| packages.get_mut(i_package))
+                {
+                    if package.state != state {
+                        // Nothing was assumed when verifying the states
+                        if !settings.general.verify_package_state {
+                            warn!(
+                                "[REFRESH] {} is {} on the device (expected: {})",
+                                package.name, state, package.state
+                            );
+                        }
+                        package.state = state;
+                        package.apk_count = None;
+                        if state == PackageState::Uninstalled {
+                            package.suspended = None;
+                        }
+                        if state != PackageState::Disabled {
+                            package.disabled_by = None;
+                        }
+                        self.filter_package_lists(&settings.general);
+                    }
+                }
+                Command::none()
+            }
+            Message::PackageRefreshed(_, _, None) => Command::none(),
+            // Triggered by mod.rs when a setting affecting the filters changed
+            Message::RefreshFilters => {
+                if let LoadingState::Ready(_) = self.loading_state {
+                    if settings.general.safe_mode {
+                        self.selected_removals = default_removals(&settings.general);
+                        // Selected before safe mode was enabled
+                        for i in self.selection.selected_packages.clone() {
+                            let package = &mut self.phone_packages[i_user][i];
+                            if !settings.general.can_remove(package.removal) {
+                                package.selected = false;
+                                self.selection.set(i, false);
+                            }
+                        }
+                    }
+                    self.filter_package_lists(&settings.general);
+                }
+                Command::none()
+            }
+            Message::CustomCategoryToggled(label, toggled) => {
+                self.change_filters(&settings.general, |list_view| {
+                    if toggled {
+                        list_view.selected_custom.insert(label);
+                    } else {
+                        list_view.selected_custom.remove(&label);
+                    }
+                })
+            }
+            Message::CategoryInputChanged(label) => {
+                self.category_input = label;
+                Command::none()
+            }
+            Message::CreateCategory => {
+                let label = self.category_input.trim().to_string();
+                if label.is_empty() {
+                    return Command::none();
+                }
+                self.category_input.clear();
+                if !self.categories.labels.contains(&label) {
+                    self.categories.labels.push(label.clone());
+                }
+                self.update(
+                    settings,
+                    selected_device,
+                    list_update_state,
+                    Message::AssignCategory(label),
+                )
+            }
+            Message::AssignCategory(label) => {
+                self.set_custom_category(Some(label), &settings.general);
+                Command::none()
+            }
+            Message::ClearCategory => {
+                self.set_custom_category(None, &settings.general);
+                Command::none()
+            }
+            Message::NoteChanged(note) => {
+                self.note_input = note;
+                Command::none()
+            }
+            Message::SaveNote => {
+                let name = self.phone_packages[i_user][self.current_package_index]
+                    .name
+                    .clone();
+                let note = self.note_input.trim();
+                if note.is_empty() {
+                    self.notes.remove(&name);
+                } else {
+                    self.notes.insert(name, note.to_string());
+                }
+                if let Err(e) = save_notes(&self.notes) {
+                    error!("[NOTES] Could not save notes: {}", e);
+                }
+                Command::none()
+            }
+            Message::EnableForAllUsers => {
+                self.enable_for_all_users(self.current_package_index, selected_device)
+            }
+            Message::TogglePackage => {
+                self.toggle_package(self.current_package_index, settings, selected_device)
+            }
+            Message::SuspendPackage(suspend) => self.suspend_packages(
+                &[self.current_package_index],
+                suspend,
+                settings,
+                selected_device,
+            ),
+            Message::SuspensionFetched(u_index, i_package, suspended) => {
+                if let Some(package) = self
+                    .phone_packages
+                    .get_mut(u_index)
+                    .and_then(|packages| packages.get_mut(i_package))
+                {
+                    package.suspended = suspended;
+                }
+                Command::none()
+            }
+            Message::ActOnUser(user) => {
+                let i_package = self.current_package_index;
+                let package = match self.phone_packages.get(user.index) {
+                    Some(packages) => &packages[i_package],
+                    None => return Command::none(),
+                };
+                if package.locked() {
+                    return Command::none();
+                }
+                if package.state == PackageState::Enabled && package.removal == Removal::Unsafe {
+                    self.confirmation = Some(Confirmation {
+                        action: PendingAction::UserPackage(i_package, user),
+                        warnings: vec![format!("{} is marked as unsafe", package.name)],
+                        preview: vec![],
+                    });
+                    return Command::none();
+                }
+                self.apply_user_package_action(i_package, user, settings, selected_device)
+            }
+            Message::OpenStorePage => {
+                // Not every package is on the Play Store: the page will just be a 404
+                let url = PathBuf::from(format!(
+                    "https://play.google.com/store/apps/details?id={}",
+                    self.phone_packages[i_user][self.current_package_index].name
+                ));
+                Command::perform(async move { open_url(url) }, |_| Message::StorePageOpened)
+            }
+            Message::StorePageOpened => Command::none(),
+            Message::DescriptionLinkPressed(url) => {
+                Command::perform(async move { open_url(PathBuf::from(url)) }, |_| {
+                    Message::StorePageOpened
+                })
+            }
+            Message::ToggleDescriptionExpanded => {
+                self.description_expanded = !self.description_expanded;
+                Command::none()
+            }
+            Message::UndoRemoval => {
+                let toast = match self.undo_toast.take() {
+                    Some(toast) => toast,
+                    None => return Command::none(),
+                };
+                let mut commands = vec![];
+                for (i_package, u_index) in toast.packages {
+                    let package = &self.phone_packages[u_index][i_package];
+                    let user = selected_device
+                        .user_list
+                        .iter()
+                        .find(|u| u.index == u_index)
+                        .copied()
+                        .unwrap_or(User::default());
+                    let actions = apply_pkg_state_commands(
+                        &CorePackage::from(package),
+                        &PackageState::Enabled,
+                        &user,
+                        selected_device,
+                    )
+                    .into_iter()
+                    .map(|command| (Some(u_index), command))
+                    .collect();
+                    commands.extend(build_package_commands(
+                        actions,
+                        i_package,
+                        package.removal,
+                        &selected_device.adb_id,
+                    ));
+                }
+                self.send_commands(commands)
+            }
+            Message::DismissToast => {
+                self.undo_toast = None;
+                Command::none()
+            }
+            Message::ToastTick(now) => {
+                let expired = self.undo_toast.as_ref().map_or(false, |t| {
+                    t.last_removal.map_or(true, |last| {
+                        now.duration_since(last) >= Duration::from_secs(TOAST_DURATION)
+                    })
+                });
+                if expired {
+                    self.undo_toast = None;
+                }
+                Command::none()
+            }
+            Message::StorageMeasured(free_storage) => {
+                if let Some(batch) = &mut self.batch {
+                    batch.free_storage_after = free_storage;
+                    // Nothing to compare with: don't show a measure that will never come
+                    if free_storage.is_none() {
+                        batch.free_storage_before = None;
+                    }
+                }
+                Command::none()
+            }
+            Message::KeyPressed(key_code) => match (key_code, self.focused_index) {
+                // The dropdown of the list filter is open
+                (KeyCode::Up | KeyCode::Down, _) if !self.list_query.is_empty() => {
+                    let last = self.matching_lists().len().saturating_sub(1);
+                    self.list_highlight = if key_code == KeyCode::Up {
+                        self.list_highlight.saturating_sub(1)
+                    } else {
+                        (self.list_highlight + 1).min(last)
+                    };
+                    Command::none()
+                }
+                (KeyCode::Escape, _) if !self.list_query.is_empty() => {
+                    self.list_query.clear();
+                    Command::none()
+                }
+                (KeyCode::Escape, _) if self.context_menu.is_some() => {
+                    self.context_menu = None;
+                    Command::none()
+                }
+                (KeyCode::Up | KeyCode::Down, focused) => {
+                    if self.filtered_packages.is_empty() {
+                        return Command::none();
+                    }
+                    let last = self.filtered_packages.len() - 1;
+                    let pos = match focused
+                        .and_then(|f| self.filtered_packages.iter().position(|i| *i == f))
+                    {
+                        Some(pos) if key_code == KeyCode::Up => pos.saturating_sub(1),
+                        Some(pos) => (pos + 1).min(last),
+                        None => 0,
+                    };
+                    let i_package = self.filtered_packages[pos];
+                    // Same approximation as JumpToLetter
+                    let offset = pos as f32 / last.max(1) as f32;
+                    Command::batch([
+                        self.update(
+                            settings,
+                            selected_device,
+                            list_update_state,
+                            Message::List(i_package, RowMessage::PackagePressed),
+                        ),
+                        scrollable::snap_to(PACKAGES_SCROLLABLE_ID.clone(), offset),
+                    ])
+                }
+                (KeyCode::F5, _) => self.update(
+                    settings,
+                    selected_device,
+                    list_update_state,
+                    Message::RefreshPackages,
+                ),
+                (KeyCode::Space, Some(i_package)) => {
+                    let selected = self.phone_packages[i_user][i_package].selected;
+                    self.update(
+                        settings,
+                        selected_device,
+                        list_update_state,
+                        Message::List(i_package, RowMessage::ToggleSelection(!selected)),
+                    )
+                }
+                (KeyCode::Enter | KeyCode::NumpadEnter, Some(i_package)) => {
+                    let package = &self.phone_packages[i_user][i_package];
+                    // Same rule as the action button of the row
+                    if package.state != PackageState::Enabled
+                        || settings.general.can_remove(package.removal)
+                    {
+                        self.update(
+                            settings,
+                            selected_device,
+                            list_update_state,
+                            Message::List(i_package, RowMessage::ActionPressed),
+                        )
+                    } else {
+                        Command::none()
+                    }
+                }
+                _ => Command::none(),
+            },
+            Message::ModifiersChanged(modifiers) => {
+                self.shift_pressed = modifiers.shift();
+                Command::none()
+            }
+            Message::WindowResized(width) => {
+                self.window_width = width;
+                Command::none()
+            }
+            Message::ToggleFiltersPanel => {
+                self.filters_open = !self.filters_open;
+                Command::none()
+            }
+            Message::SaveSnapshot => {
+                let user = self.selected_user.unwrap_or_default();
+                match save_snapshot(&selected_device.adb_id, &user, &self.phone_packages[i_user]) {
+                    Ok(_) => {
+                        self.snapshots =
+                            list_available_backups(&SNAPSHOT_DIR.join(&selected_device.adb_id));
+                    }
+                    Err(e) => error!("[SNAPSHOT] Could not save snapshot: {}", e),
+                }
+                Command::none()
+            }
+            Message::SnapshotSelected(snapshot) => {
+                let s = match load_snapshot(&snapshot.path) {
+                    Ok(s) => s,
+                    Err(e) => {
+                        error!("[SNAPSHOT] Could not load {}: {}", snapshot, e);
+                        return Command::none();
+                    }
+                };
+                // Compared with the packages of the user it was taken for
+                let current = self.selected_user.unwrap_or_default();
+                let user = match selected_device.user_list.iter().find(|u| u.id == s.user_id) {
+                    _ if s.user_id == current.id => current,
+                    Some(user) if user.index < self.phone_packages.len() => *user,
+                    _ => {
+                        warn!(
+                            "[SNAPSHOT] {} was taken for user {}, not loaded on this device",
+                            snapshot, s.user_id
+                        );
+                        return Command::none();
+                    }
+                };
+                let command = if user.index == i_user {
+                    Command::none()
+                } else {
+                    self.update(
+                        settings,
+                        selected_device,
+                        list_update_state,
+                        Message::UserSelected(user),
+                    )
+                };
+                let (changes, removed) = diff_snapshot(&s, &self.phone_packages[user.index]);
+                self.snapshot_changes = changes;
+                self.snapshot_removed = removed;
+                self.compared_snapshot = Some(snapshot);
+                command
+            }
+            Message::ClearSnapshotComparison => {
+                self.compared_snapshot = None;
+                self.snapshot_changes.clear();
+                Command::none()
+            }
+            Message::OpenExport => {
+                self.export = Some(ExportDialog {
+                    scope: ExportScope::Filtered,
+                    report: None,
+                    apk_dir: String::new(),
+                    apk_export: None,
+                });
+                Command::none()
+            }
+            Message::ApkExportDirChanged(dir) => {
+                if let Some(dialog) = &mut self.export {
+                    dialog.apk_dir = dir;
+                }
+                Command::none()
+            }
+            Message::ExportApks => {
+                let names: Vec<String> = self
+                    .selection
+                    .selected_packages
+                    .iter()
+                    .map(|i| self.phone_packages[i_user][*i].name.clone())
+                    .collect();
+                if let Some(dialog) = &mut self.export {
+                    let dir = match dialog.apk_dir.trim() {
+                        "" => EXPORT_DIR.join(&selected_device.adb_id).join("apks"),
+                        dir => PathBuf::from(dir),
+                    };
+                    dialog.apk_export = Some(ApkExport {
+                        dir,
+                        pending: names,
+                        ..ApkExport::default()
+                    });
+                }
+                self.export_next_apk(&selected_device.adb_id)
+            }
+            Message::ApkExported(package, result) => {
+                if let Some(export) = self.export.as_mut().and_then(|d| d.apk_export.as_mut()) {
+                    match result {
+                        Ok(_) => export.exported += 1,
+                        Err(e) => {
+                            error!("[APK EXPORT] {}: {}", package, e);
+                            export.failed.push((package, e));
+                        }
+                    }
+                }
+                self.export_next_apk(&selected_device.adb_id)
+            }
+            Message::CloseExport => {
+                self.export = None;
+                Command::none()
+            }
+            Message::OpenBaseline => {
+                self.baseline = Some(BaselineReport::new(&self.phone_packages[i_user]));
+                Command::none()
+            }
+            Message::CloseBaseline => {
+                self.baseline = None;
+                Command::none()
+            }
+            Message::CopyBaselineSummary => match &self.baseline {
+                Some(report) => iced::clipboard::write(format!(
+                    "{}: {}",
+                    selected_device.model,
+                    report.summary()
+                )),
+                None => Command::none(),
+            },
+            Message::SyncSourceSelected(source) => {
+                self.sync = Some(self.device_sync(source, settings, selected_device));
+                Command::none()
+            }
+            Message::CloseSync => {
+                self.sync = None;
+                Command::none()
+            }
+            Message::ExportSyncScript => {
+                if let Some(sync) = &mut self.sync {
+                    let exported = export_sync_script(
+                        &selected_device.adb_id,
+                        &sync.source.adb_id,
+                        &sync.script(selected_device),
+                    );
+                    if let Err(e) = &exported {
+                        error!("[SYNC] Could not export the script: {}", e);
+                    }
+                    sync.exported = Some(exported);
+                }
+                Command::none()
+            }
+            Message::RunSync => {
+                let sync = match self.sync.take() {
+                    Some(sync) => sync,
+                    None => return Command::none(),
+                };
+                let user = sync.user;
+                let serial = selected_device.adb_id.clone();
+                // One package after the other, in a single future: the state of each one
+                // is read back from the device once its commands ran
+                Command::perform(
+                    async move {
+                        let mut states = vec![];
+                        for change in sync.changes {
+                            for command in &change.commands {
+                                if let Err(e) = adb_shell_command(&serial, true, command) {
+                                    error!("[SYNC] {}: {}", command, e);
+                                    break;
+                                }
+                            }
+                            states.push((
+                                change.i_package,
+                                get_package_state(&change.name, Some(&user), &serial),
+                            ));
+                        }
+                        states
+                    },
+                    move |states| Message::SyncApplied(user.index, states),
+                )
+            }
+            Message::SyncApplied(u_index, states) => {
+                let commands = states
+                    .into_iter()
+                    .map(|(i_package, state)| {
+                        self.update(
+                            settings,
+                            selected_device,
+                            list_update_state,
+                            Message::PackageRefreshed(i_package, u_index, state),
+                        )
+                    })
+                    .collect::<Vec<_>>();
+                Command::batch(commands)
+            }
+            Message::SelectBaselineRemaining => {
+                self.baseline = None;
+                self.update(
+                    settings,
+                    selected_device,
+                    list_update_state,
+                    Message::SelectRecommended,
+                )
+            }
+            Message::ExportScopeSelected(scope) => {
+                if let Some(dialog) = &mut self.export {
+                    dialog.scope = scope;
+                }
+                Command::none()
+            }
+            Message::ExportPackages => {
+                if let Some(dialog) = &mut self.export {
+                    let packages = &self.phone_packages[i_user];
+                    let rows: Vec<&PackageRow> = match dialog.scope {
+                        ExportScope::All => packages.iter().collect(),
+                        ExportScope::Filtered => self
+                            .filtered_packages
+                            .iter()
+                            .map(|i| &packages[*i])
+                            .collect(),
+                    };
+                    dialog.report = Some(match export_packages(&selected_device.adb_id, &rows) {
+                        Ok(path) => {
+                            format!("Exported {} packages to {}", rows.len(), path.display())
+                        }
+                        Err(e) => {
+                            error!("[EXPORT] Could not export packages: {}", e);
+                            format!("Could not export packages: {}", e)
+                        }
+                    });
+                }
+                Command::none()
+            }
+            Message::OpenProfiles => {
+                self.profiles = Some(ProfilesDialog {
+                    profiles: list_profiles(),
+                    ..ProfilesDialog::default()
+                });
+                Command::none()
+            }
+            Message::CloseProfiles => {
+                self.profiles = None;
+                Command::none()
+            }
+            Message::ProfileSelected(name) => {
+                if let Some(dialog) = &mut self.profiles {
+                    dialog.selected = Some(name);
+                }
+                Command::none()
+            }
+            Message::ProfileNameChanged(name) => {
+                if let Some(dialog) = &mut self.profiles {
+                    dialog.new_name = name;
+                }
+                Command::none()
+            }
+            Message::IdListPathChanged(path) => {
+                if let Some(dialog) = &mut self.profiles {
+                    dialog.id_list_path = path;
+                }
+                Command::none()
+            }
+            Message::ImportIdList => {
+                let (path, fuzzy_match) = match &self.profiles {
+                    Some(dialog) => (
+                        PathBuf::from(dialog.id_list_path.trim()),
+                        dialog.fuzzy_match,
+                    ),
+                    None => return Command::none(),
+                };
+                let (report, suggestions) = match load_package_id_list(&path) {
+                    Ok(list) => self.apply_profile(&list, fuzzy_match, settings),
+                    Err(e) => (
+                        format!("Could not import {}: {}", path.display(), e),
+                        vec![],
+                    ),
+                };
+                if let Some(dialog) = &mut self.profiles {
+                    dialog.report = Some(report);
+                    dialog.suggestions = suggestions;
+                }
+                Command::none()
+            }
+            Message::SaveProfile => {
+                let profile = Profile {
+                    packages: self
+                        .selection
+                        .selected_packages
+                        .iter()
+                        .map(|i| self.phone_packages[i_user][*i].name.clone())
+                        .collect(),
+                };
+                if let Some(dialog) = &mut self.profiles {
+                    let name = dialog.new_name.trim().to_string();
+                    dialog.report = Some(if !is_valid_profile_name(&name) {
+                        "Invalid profile name".to_string()
+                    } else {
+                        match save_profile(&name, &profile) {
+                            Ok(_) => {
+                                dialog.profiles = list_profiles();
+                                dialog.selected = Some(name.clone());
+                                dialog.new_name.clear();
+                                format!("Saved {} packages in {}", profile.packages.len(), name)
+                            }
+                            Err(e) => format!("Could not save {}: {}", name, e),
+                        }
+                    });
+                }
+                Command::none()
+            }
+            Message::ApplyProfile => {
+                let name = match self.profiles.as_ref().and_then(|d| d.selected.clone()) {
+                    Some(name) => name,
+                    None => return Command::none(),
+                };
+                let fuzzy_match = self.profiles.as_ref().map_or(false, |d| d.fuzzy_match);
+                let (report, suggestions) = match load_profile(&name) {
+                    Ok(profile) => self.apply_profile(&profile, fuzzy_match, settings),
+                    Err(e) => (format!("Could not load {}: {}", name, e), vec![]),
+                };
+                if let Some(dialog) = &mut self.profiles {
+                    dialog.report = Some(report);
+                    dialog.suggestions = suggestions;
+                }
+                Command::none()
+            }
+            Message::FuzzyMatchToggled(toggled) => {
+                if let Some(dialog) = &mut self.profiles {
+                    dialog.fuzzy_match = toggled;
+                }
+                Command::none()
+            }
+            Message::AcceptSuggestion(i) => {
+                let suggestion = match &mut self.profiles {
+                    Some(dialog) if i < dialog.suggestions.len() => dialog.suggestions.remove(i),
+                    _ => return Command::none(),
+                };
+                self.set_package_selected(suggestion.i_package, true, settings);
+                Command::none()
+            }
+            Message::RejectSuggestion(i) => {
+                if let Some(dialog) = &mut self.profiles {
+                    if i < dialog.suggestions.len() {
+                        dialog.suggestions.remove(i);
+                    }
+                }
+                Command::none()
+            }
+            Message::DeleteProfile => {
+                if let Some(dialog) = &mut self.profiles {
+                    if let Some(name) = dialog.selected.take() {
+                        dialog.report = Some(match delete_profile(&name) {
+                            Ok(_) => format!("Deleted {}", name),
+                            Err(e) => format!("Could not delete {}: {}", name, e),
+                        });
+                        dialog.profiles = list_profiles();
+                    }
+                }
+                Command::none()
+            }
+            // Trigger an action in mod.rs (Message::AppsAction(msg))
+            Message::RetryDeviceScan | Message::RestartAdb | Message::RebootConfirmed => {
+                Command::none()
+            }
+            Message::RunProgress(generation, progress) => {
+                let message = match progress {
+                    Progress::Started(_) => return Command::none(),
+                    Progress::Finished { .. } => {
+                        self.runs.retain(|(g, _, _)| *g != generation);
+                        return Command::none();
+                    }
+                    Progress::Finishing(job) => {
+                        if let Some(batch) = &mut self.batch {
+                            if !batch.finished_after_cancel.contains(&job.info.index) {
+                                batch.finished_after_cancel.push(job.info.index);
+                            }
+                        }
+                        return Command::none();
+                    }
+                    Progress::Cancelled(job) => {
+                        if let Some(batch) = &mut self.batch {
+                            if !batch.cancelled.contains(&job.info.index) {
+                                batch.cancelled.push(job.info.index);
+                            }
+                        }
+                        // Resolved without changing anything
+                        return self.resolve_batch_command(
+                            None,
+                            true,
+                            &settings.general,
+                            selected_device,
+                        );
+                    }
+                    Progress::Completed(job) if job.change_state => {
+                        Message::ChangePackageState(Ok(CommandType::PackageManager(job.info)))
+                    }
+                    Progress::Failed(job, e) if job.change_state => {
+                        Message::ChangePackageState(Err(e))
+                    }
+                    _ => Message::Nothing,
+                };
+                self.update(settings, selected_device, list_update_state, message)
+            }
+            Message::RunReady(generation, free_storage, jobs) => {
+                // Dropped with the list while the storage was measured
+                if generation != self.run_generation {
+                    return Command::none();
+                }
+                if let Some(batch) = &mut self.batch {
+                    batch.free_storage_before = free_storage;
+                }
+                // Followed through `subscription`
+                self.commands_status.pending += jobs.len();
+                let cancel = Arc::new(AtomicBool::new(false));
+                let receiver = debloat::spawn(selected_device.adb_id.clone(), jobs, cancel.clone());
+                self.runs
+                    .push((generation, Arc::new(Mutex::new(Some(receiver))), cancel));
+                Command::none()
+            }
+            Message::CloseContextMenu => {
+                self.context_menu = None;
+                Command::none()
+            }
+            Message::ContextAction(i_package, action) => {
+                self.context_menu = None;
+                // Actions on the current package need it to be the one of the menu
+                let make_current = Message::List(i_package, RowMessage::PackagePressed);
+                let messages = match action {
+                    ContextAction::CopyId => {
+                        return iced::clipboard::write(
+                            self.phone_packages[i_user][i_package].name.clone(),
+                        );
+                    }
+                    ContextAction::AddNote => {
+                        return Command::batch([
+                            self.update(settings, selected_device, list_update_state, make_current),
+                            text_input::focus(NOTE_INPUT_ID.clone()),
+                        ]);
+                    }
+                    ContextAction::OpenStorePage => vec![make_current, Message::OpenStorePage],
+                    ContextAction::TogglePin => {
+                        vec![Message::List(i_package, RowMessage::TogglePin)]
+                    }
+                    ContextAction::ToggleAllowList => {
+                        vec![Message::List(i_package, RowMessage::ToggleAllowList)]
+                    }
+                    ContextAction::ViewPermissions => vec![make_current],
+                    ContextAction::CycleActionOverride => {
+                        vec![Message::List(i_package, RowMessage::CycleActionOverride)]
+                    }
+                    ContextAction::Refresh => {
+                        vec![Message::List(i_package, RowMessage::RefreshPressed)]
+                    }
+                    ContextAction::Act => vec![Message::List(i_package, RowMessage::ActionPressed)],
+                    ContextAction::ActOnUser(user) => vec![make_current, Message::ActOnUser(user)],
+                };
+                let commands: Vec<_> = messages
+                    .into_iter()
+                    .map(|message| {
+                        self.update(settings, selected_device, list_update_state, message)
+                    })
+                    .collect();
+                Command::batch(commands)
+            }
+            Message::CancelBatch => {
+                // Seen by the runs between two packages (see `debloat::run`)
+                for (_, _, cancel) in &self.runs {
+                    cancel.store(true, Ordering::Relaxed);
+                }
+                if let Some(batch) = &mut self.batch {
+                    batch.cancelling = true;
+                }
+                Command::none()
+            }
+            Message::Nothing => {
+                self.resolve_batch_command(None, true, &settings.general, selected_device)
+            }
+        }
+    }
+
+    pub fn view(
+        &self,
+        settings: &Settings,
+        selected_device: &Phone,
+    ) -> Element<Message, Renderer<Theme>> {
+        match &self.loading_state {
+            LoadingState::DownloadingList(_) => {
+                let text = "Downloading latest UAD lists from Github. Please wait...";
+                waiting_view(settings, text, true)
+            }
+            LoadingState::FindingPhones(_) => {
+                let text = "Finding connected devices...";
+                waiting_view(settings, text, false)
+            }
+            LoadingState::NoDevice { unauthorized } => no_device_view(*unauthorized),
+            LoadingState::LoadFailed(err) => load_failed_view(err),
+            LoadingState::LoadingPackages(model) => {
+                let text = format!("Pulling packages from {}. Please wait...", model);
+                waiting_view(settings, &text, false)
+            }
+            LoadingState::_UpdatingUad(_) => {
+                let text = "Updating UAD. Please wait...";
+                waiting_view(settings, text, false)
+            }
+            LoadingState::RestoringDevice(output) => {
+                let text = format!("Restoring device: {}", output);
+                waiting_view(settings, &text, false)
+            }
+            LoadingState::Ready(_) if self.batch.as_ref().map_or(false, |b| b.pending == 0) => {
+                self.batch_summary_view(self.batch.as_ref().unwrap())
+            }
+            LoadingState::Ready(_) if self.profiles.is_some() => {
+                Self::profiles_view(self.profiles.as_ref().unwrap())
+            }
+            LoadingState::Ready(_) if self.export.is_some() => {
+                self.export_view(self.export.as_ref().unwrap())
+            }
+            LoadingState::Ready(_) if self.baseline.is_some() => {
+                self.baseline_view(self.baseline.as_ref().unwrap())
+            }
+            LoadingState::Ready(_) if self.sync.is_some() => {
+                Self::sync_view(self.sync.as_ref().unwrap(), selected_device)
+            }
+            LoadingState::Ready(_) if self.confirmation.is_some() => {
+                Self::confirmation_view(self.confirmation.as_ref().unwrap())
+            }
+            LoadingState::Ready(_) => {
+                let search_packages = text_input(
+                    "Search packages...",
+                    &self.input_value,
+                    Message::SearchInputChanged,
+                )
+                .padding(5);
+
+                // let package_amount = text(format!("{} packages found", packages.len()));
+
+                // Nothing to pick on single user devices
+                let user_controls = if selected_device.user_list.len() > 1 {
+                    row![
+                        pick_list(
+                            selected_device.user_list.clone(),
+                            self.selected_user,
+                            Message::UserSelected,
+                        )
+                        .width(Length::Units(85)),
+                        checkbox("All users", self.all_users, Message::AllUsersToggled)
+                            .text_size(15)
+                            .style(style::CheckBox::SettingsEnabled),
+                        text(
+                            self.selection_per_user(settings, selected_device)
+                                .iter()
+                                .map(|(user, count)| format!("user {}: {}", user.id, count))
+                                .collect::<Vec<_>>()
+                                .join(" · ")
+                        )
+                        .size(13)
+                        .style(style::Text::Commentary)
+                    ]
+                    .spacing(10)
+                    .align_items(Alignment::Center)
+                } else {
+                    row![]
+                };
+
+                let list_picklist = self.list_combo_box();
+                let package_state_picklist = pick_list(
+                    &PackageState::ALL[..],
+                    self.selected_package_state,
+                    Message::PackageStateSelected,
+                );
+
+                let restorability_picklist = pick_list(
+                    &Restorability::ALL[..],
+                    Some(self.selected_restorability),
+                    Message::RestorabilitySelected,
+                );
+
+                let jump_picklist = pick_list(&JUMP_LETTERS[..], None, Message::JumpToLetter)
+                    .placeholder("A-Z")
+                    .width(Length::Units(65));
+
+                let refresh_btn = button("Refresh")
+                    .padding(5)
+                    .on_press(Message::RefreshPackages)
+                    .style(style::Button::Primary);
+
+                // Controls after the search input are pushed to the right
+                let controls = &settings.general.filter_controls;
+                let mut control_panel = if controls.contains(&FilterControl::Search) {
+                    row![]
+                } else {
+                    row![Space::new(Length::Fill, Length::Shrink)]
+                };
+                let mut search_packages = Some(search_packages);
+                let mut user_controls = Some(user_controls);
+                let mut list_picklist = Some(list_picklist);
+                let mut package_state_picklist = Some(package_state_picklist);
+                let mut restorability_picklist = Some(restorability_picklist);
+                let mut jump_picklist = Some(jump_picklist);
+                let mut refresh_btn = Some(refresh_btn);
+                // Only the search input stays in sight in the minimal UI
+                let minimal = self.minimal_ui();
+                let mut filters_panel = column![].spacing(10);
+                for control in controls {
+                    // Every control is listed once, `take` just moves it out
+                    let element: Option<Element<Message, Renderer<Theme>>> = match control {
+                        FilterControl::Search => search_packages.take().map(|search| {
+                            let search_fields = SearchField::OPTIONAL.into_iter().fold(
+                                row![text("Also in:").size(15).style(style::Text::Commentary)]
+                                    .spacing(8)
+                                    .align_items(Alignment::Center),
+                                |row, field| {
+                                    row.push(
+                                        checkbox(
+                                            field.to_string(),
+                                            self.search_fields.contains(&field),
+                                            move |toggled| {
+                                                Message::SearchFieldToggled(field, toggled)
+                                            },
+                                        )
+                                        .size(16)
+                                        .text_size(15)
+                                        .style(style::CheckBox::SettingsEnabled),
+                                    )
+                                },
+                            );
+                            row![
+                                search,
+                                search_fields,
+                                Space::new(Length::Fill, Length::Shrink)
+                            ]
+                            .align_items(Alignment::Center)
+                            .width(Length::Fill)
+                            .spacing(10)
+                            .into()
+                        }),
+                        FilterControl::User => user_controls.take().map(Element::from),
+                        FilterControl::PackageState => {
+                            package_state_picklist.take().map(Element::from)
+                        }
+                        FilterControl::List => list_picklist.take().map(Element::from),
+                        FilterControl::Restorability => {
+                            restorability_picklist.take().map(Element::from)
+                        }
+                        FilterControl::JumpToLetter => jump_picklist.take().map(Element::from),
+                        FilterControl::Refresh => refresh_btn.take().map(Element::from),
+                    };
+                    match element {
+                        Some(element) if minimal && *control != FilterControl::Search => {
+                            filters_panel = filters_panel.push(element);
+                        }
+                        Some(element) => control_panel = control_panel.push(element),
+                        None => {}
+                    }
+                }
+                // Pointless if expert mode is saved, and safe mode forbids it
+                if !settings.general.expert_mode && !settings.general.safe_mode {
+                    let expert_checkbox = checkbox(
+                        "Expert mode (this session)",
+                        settings.general.session_expert_mode,
+                        Message::SessionExpertModeToggled,
+                    )
+                    .text_size(15)
+                    .style(style::CheckBox::SettingsEnabled);
+                    if minimal {
+                        filters_panel = filters_panel.push(expert_checkbox);
+                    } else {
+                        control_panel = control_panel.push(expert_checkbox);
+                    }
+                }
+                if minimal {
+                    control_panel = control_panel.push(
+                        button(if self.filters_open {
+                            "Filters ▴"
+                        } else {
+                            "Filters ▾"
+                        })
+                        .padding(5)
+                        .on_press(Message::ToggleFiltersPanel)
+                        .style(style::Button::Primary),
+                    );
+                }
+                let control_panel = control_panel
+                    .width(Length::Fill)
+                    .align_items(Alignment::Center)
+                    .spacing(10)
+                    .padding([0, 16, 0, 0]);
+                let control_panel = if minimal && self.filters_open {
+                    column![
+                        control_panel,
+                        container(filters_panel)
+                            .padding(10)
+                            .style(style::Container::Frame),
+                    ]
+                    .spacing(10)
+                } else {
+                    column![control_panel]
+                };
+
+                let debloat_progress = self.debloat_progress().into_iter().fold(
+                    row![text("Debloated:").style(style::Text::Commentary)]
+                        .spacing(15)
+                        .align_items(Alignment::Center),
+                    |meters, (removal, removed, total)| {
+                        meters.push(
+                            column![
+                                text(format!("{} {}/{}", removal, removed, total)).size(13),
+                                progress_bar(0.0..=total as f32, removed as f32)
+                                    .height(Length::Units(6))
+                                    .width(Length::Units(110))
+                                    .style(style::ProgressBar::Default),
+                            ]
+                            .spacing(2),
+                        )
+                    },
+                );
+
+                let removal_filters = Removal::ALL
+                    .iter()
+                    .filter(|r| **r != Removal::All)
+                    .fold(
+                        row![text("Removal:").style(style::Text::Commentary)]
+                            .spacing(15)
+                            .align_items(Alignment::Center),
+                        |filters, r| {
+                            let removal = *r;
+                            filters.push(
+                                checkbox(
+                                    removal.to_string(),
+                                    self.selected_removals.contains(&removal),
+                                    move |toggled| Message::RemovalToggled(removal, toggled),
+                                )
+                                .text_size(15)
+                                .style(
+                                    if settings.general.safe_mode {
+                                        style::CheckBox::SettingsDisabled
+                                    } else {
+                                        style::CheckBox::SettingsEnabled
+                                    },
+                                ),
+                            )
+                        },
+                    )
+                    .push(self.categories.labels.iter().fold(
+                        row![].spacing(15),
+                        |filters, label| {
+                            let label = label.clone();
+                            filters.push(
+                                checkbox(
+                                    label.clone(),
+                                    self.selected_custom.contains(&label),
+                                    move |toggled| {
+                                        Message::CustomCategoryToggled(label.clone(), toggled)
+                                    },
+                                )
+                                .text_size(15)
+                                .style(style::CheckBox::SettingsEnabled),
+                            )
+                        },
+                    ))
+                    .push(Space::new(Length::Fill, Length::Shrink))
+                    .push(
+                        text(if self.hidden_uninstalled > 0 {
+                            format!(
+                                "{} of {} packages ({} uninstalled hidden)",
+                                self.filtered_packages.len(),
+                                self.phone_packages[self.selected_user.unwrap().index].len(),
+                                self.hidden_uninstalled
+                            )
+                        } else {
+                            format!(
+                                "{} of {} packages",
+                                self.filtered_packages.len(),
+                                self.phone_packages[self.selected_user.unwrap().index].len()
+                            )
+                        })
+                        .size(15)
+                        .style(style::Text::Commentary),
+                    )
+                    .push(
+                        checkbox(
+                            format!(
+                                "Show hidden ({})",
+                                settings.general.allow_listed_packages.len()
+                            ),
+                            self.show_allow_listed,
+                            Message::ShowAllowListedToggled,
+                        )
+                        .text_size(15)
+                        .style(style::CheckBox::SettingsEnabled),
+                    )
+                    .push(
+                        checkbox(
+                            format!("Changed this session ({})", self.session_changes.len()),
+                            self.only_session_changes,
+                            Message::SessionChangesToggled,
+                        )
+                        .text_size(15)
+                        .style(style::CheckBox::SettingsEnabled),
+                    )
+                    .push(
+                        checkbox(
+                            "Sort by last update",
+                            self.sort_by_last_update,
+                            Message::SortByLastUpdate,
+                        )
+                        .text_size(15)
+                        .style(style::CheckBox::SettingsEnabled),
+                    )
+                    .push(
+                        checkbox(
+                            "Selected first",
+                            self.selected_first,
+                            Message::SelectedFirstToggled,
+                        )
+                        .text_size(15)
+                        .style(style::CheckBox::SettingsEnabled),
+                    )
+                    .width(Length::Fill);
+
+                let collapse_users = settings.device.multi_user_mode
+                    && settings.device.collapse_multi_user
+                    && self.phone_packages.len() > 1;
+
+                // Pinned packages come first (see filter_package_lists)
+                let nb_pinned = self
+                    .filtered_packages
+                    .iter()
+                    .take_while(|i| {
+                        settings.general.pinned_packages.contains(
+                            &self.phone_packages[self.selected_user.unwrap().index][**i].name,
+                        )
+                    })
+                    .count();
+
+                let packages = self.filtered_packages.iter().enumerate().fold(
+                    column![].spacing(settings.general.list_density.list_spacing()),
+                    |col, (pos, i)| {
+                        let col = if pos == nb_pinned && nb_pinned > 0 {
+                            col.push(horizontal_rule(4))
+                        } else {
+                            col
+                        };
+                        let snapshot_change =
+                            self.snapshot_changes
+                                .get(i)
+                                .map(|old_state| match old_state {
+                                    Some(state) => format!("was {}", state),
+                                    None => "new".to_string(),
+                                });
+                        let package = &self.phone_packages[self.selected_user.unwrap().index][*i];
+                        let context = RowContext {
+                            user_chips: if collapse_users {
+                                self.user_chips(selected_device, *i)
+                            } else {
+                                vec![]
+                            },
+                            focused: self.focused_index == Some(*i),
+                            snapshot_change,
+                            commands: self.command_preview(*i, settings, selected_device),
+                            has_note: self.notes.contains_key(&package.name),
+                            search_match: self.search_matches.get(i).map(|f| f.to_string()),
+                        };
+                        let col = col.push(
+                            package
+                                .view(settings, selected_device, context)
+                                .map(move |msg| Message::List(*i, msg)),
+                        );
+                        if self.context_menu == Some(*i) {
+                            col.push(self.context_menu_view(*i, settings, selected_device))
+                        } else {
+                            col
+                        }
+                    },
+                );
+
+                let filter_chips = self.filter_chips(&settings.general);
+
+                let packages_scrollable: Element<Message, Renderer<Theme>> =
+                    if self.filtered_packages.is_empty() {
+                        self.empty_list_view()
+                    } else {
+                        scrollable(packages)
+                            .id(PACKAGES_SCROLLABLE_ID.clone())
+                            .on_scroll(Message::PackagesScrolled)
+                            .scrollbar_margin(2)
+                            .height(Length::Fill)
+                            .style(style::Scrollable::Packages)
+                            .into()
+                    };
+
+                // let mut packages_v: Vec<&str> = self.packages.lines().collect();
+
+                let reversibility = if self.description.is_empty() {
+                    text("")
+                } else if self.phone_packages[self.selected_user.unwrap().index]
+                    [self.current_package_index]
+                    .reversible
+                {
+                    text("Uninstallation can be reverted by restoring the package")
+                        .style(style::Text::Commentary)
+                } else {
+                    text("Uninstallation is permanent: the app has to be installed again to come back")
+                        .style(style::Text::Danger)
+                };
+
+                let timestamps = if self.description.is_empty() {
+                    text("")
+                } else {
+                    let timestamps = match &self.phone_packages[self.selected_user.unwrap().index]
+                        [self.current_package_index]
+                        .timestamps
+                    {
+                        Some(t) => text(format!(
+                            "Installed: {} | Last updated: {}",
+                            t.first_install, t.last_update
+                        )),
+                        None if self
+                            .pending_timestamps
+                            .contains(&self.current_package_index) =>
+                        {
+                            text("Fetching install dates...")
+                        }
+                        None => text("Install dates unavailable"),
+                    };
+                    timestamps.style(style::Text::Commentary)
+                };
+
+                let i_current = self.current_package_index;
+                let permissions = if self.description.is_empty() {
+                    column![]
+                } else {
+                    let package =
+                        &self.phone_packages[self.selected_user.unwrap().index][i_current];
+                    match &package.permissions {
+                        Some(permissions) => {
+                            let sensitive: Vec<&PackagePermission> =
+                                permissions.iter().filter(|p| p.is_sensitive()).collect();
+                            if sensitive.is_empty() {
+                                column![
+                                    text("No sensitive permissions").style(style::Text::Commentary)
+                                ]
+                            } else {
+                                sensitive.iter().fold(
+                                    column![text("Sensitive permissions:")],
+                                    |col, p| {
+                                        col.push(
+                                            text(format!(
+                                                "  {}{}",
+                                                p.name.trim_start_matches("android.permission."),
+                                                if p.granted { " (granted)" } else { "" }
+                                            ))
+                                            .size(15)
+                                            .style(
+                                                if p.granted {
+                                                    style::Text::Danger
+                                                } else {
+                                                    style::Text::Commentary
+                                                },
+                                            ),
+                                        )
+                                    },
+                                )
+                            }
+                        }
+                        None if self.pending_permissions.contains(&i_current) => {
+                            column![text("Fetching permissions...").style(style::Text::Commentary)]
+                        }
+                        None => {
+                            column![text("Permissions unavailable").style(style::Text::Commentary)]
+                        }
+                    }
+                };
+
+                // Quick on/off switch, "off" being a disable whatever the settings
+                let toggle_package =
+                    if !self.description.is_empty() && selected_device.android_sdk >= 23 {
+                        let package =
+                            &self.phone_packages[self.selected_user.unwrap().index][i_current];
+                        let (label, allowed) = match package.state {
+                            PackageState::Enabled => (
+                                "Toggle: disable",
+                                settings.general.can_remove(package.removal),
+                            ),
+                            _ => ("Toggle: enable", !package.locked()),
+                        };
+                        let btn = button(text(label).size(13))
+                            .padding([2, 6])
+                            .style(style::Button::Primary);
+                        let suspend_btn = match package.suspended {
+                            _ if package.state != PackageState::Enabled
+                                || selected_device.android_sdk < SUSPEND_MIN_SDK =>
+                            {
+                                None
+                            }
+                            Some(true) => Some(
+                                button(text("Unsuspend").size(13))
+                                    .padding([2, 6])
+                                    .on_press(Message::SuspendPackage(false))
+                                    .style(style::Button::Primary),
+                            ),
+                            _ => {
+                                let btn = button(text("Suspend").size(13))
+                                    .padding([2, 6])
+                                    .style(style::Button::Primary);
+                                Some(if settings.general.can_remove(package.removal) {
+                                    btn.on_press(Message::SuspendPackage(true))
+                                } else {
+                                    btn
+                                })
+                            }
+                        };
+                        let toggle_row = row![if allowed {
+                            btn.on_press(Message::TogglePackage)
+                        } else {
+                            btn
+                        }]
+                        .spacing(10);
+                        match suspend_btn {
+                            Some(suspend_btn) => toggle_row.push(suspend_btn),
+                            None => toggle_row,
+                        }
+                    } else {
+                        row![]
+                    };
+
+                let enable_for_all_users = if !self.description.is_empty()
+                    && selected_device.android_sdk >= 21
+                    && self.phone_packages.len() > 1
+                    && self
+                        .phone_packages
+                        .iter()
+                        .any(|packages| packages[i_current].state != PackageState::Enabled)
+                {
+                    row![button(text("Enable for all users").size(13))
+                        .padding([2, 6])
+                        .on_press(Message::EnableForAllUsers)
+                        .style(style::Button::Primary)]
+                } else {
+                    row![]
+                };
+
+                // One-off changes for the other users, without switching to them
+                let other_users_row = if !self.description.is_empty()
+                    && settings.device.multi_user_mode
+                    && selected_device.user_list.len() > 1
+                {
+                    let i_user = self.selected_user.unwrap_or(User::default()).index;
+                    selected_device
+                        .user_list
+                        .iter()
+                        .filter(|user| user.index != i_user)
+                        .filter_map(|user| {
+                            let package = self.phone_packages.get(user.index)?.get(i_current)?;
+                            Some((user, package))
+                        })
+                        .fold(
+                            row![text("Only for").size(13)]
+                                .spacing(5)
+                                .align_items(Alignment::Center),
+                            |row, (user, package)| {
+                                let label = text(format!(
+                                    "{} ({})",
+                                    package.action_label(&settings.device),
+                                    user
+                                ))
+                                .size(13);
+                                let btn =
+                                    button(label).padding([2, 6]).style(style::Button::Primary);
+                                row.push(if package.locked() {
+                                    btn
+                                } else {
+                                    btn.on_press(Message::ActOnUser(*user))
+                                })
+                            },
+                        )
+                } else {
+                    row![]
+                };
+
+                let note_row = if self.description.is_empty() {
+                    row![]
+                } else {
+                    row![
+                        text_input(
+                            "Add a note about this package...",
+                            &self.note_input,
+                            Message::NoteChanged,
+                        )
+                        .id(NOTE_INPUT_ID.clone())
+                        .on_submit(Message::SaveNote)
+                        .padding(5),
+                        button(text("Save note").size(13))
+                            .padding([4, 6])
+                            .on_press(Message::SaveNote)
+                            .style(style::Button::Primary),
+                    ]
+                    .spacing(10)
+                    .padding([0, 10, 0, 0])
+                    .align_items(Alignment::Center)
+                };
+
+                let category_row = if self.description.is_empty() {
+                    row![]
+                } else {
+                    let package =
+                        &self.phone_packages[self.selected_user.unwrap().index][i_current];
+                    row![
+                        text("Category").size(14),
+                        pick_list(
+                            self.categories.labels.clone(),
+                            package.custom_category.clone(),
+                            Message::AssignCategory,
+                        )
+                        .placeholder(package.removal.to_string())
+                        .text_size(14),
+                        if package.custom_category.is_some() {
+                            button(text("Reset").size(13))
+                                .padding([2, 6])
+                                .on_press(Message::ClearCategory)
+                                .style(style::Button::Primary)
+                        } else {
+                            button(text("Reset").size(13))
+                                .padding([2, 6])
+                                .style(style::Button::Primary)
+                        },
+                        text_input(
+                            "New category...",
+                            &self.category_input,
+                            Message::CategoryInputChanged,
+                        )
+                        .on_submit(Message::CreateCategory)
+                        .padding(5),
+                        button(text("Create").size(13))
+                            .padding([4, 6])
+                            .on_press(Message::CreateCategory)
+                            .style(style::Button::Primary),
+                    ]
+                    .spacing(10)
+                    .padding([0, 10, 0, 0])
+                    .align_items(Alignment::Center)
+                };
+
+                let apk_row = if self.description.is_empty() {
+                    row![]
+                } else {
+                    let package =
+                        &self.phone_packages[self.selected_user.unwrap().index][i_current];
+                    let backup_apk_btn = button(text("Back up APK").size(13))
+                        .padding([2, 6])
+                        .style(style::Button::Primary);
+                    let backup_apk_btn = if package.state == PackageState::Uninstalled {
+                        backup_apk_btn
+                    } else {
+                        backup_apk_btn.on_press(Message::BackupApk)
+                    };
+                    let store_btn = button(text("Play Store page").size(13))
+                        .padding([2, 6])
+                        .on_press(Message::OpenStorePage)
+                        .style(style::Button::Primary);
+                    let apk_row = if self.apk_backup_available {
+                        row![
+                            store_btn,
+                            backup_apk_btn,
+                            button(text("Install from APK backup").size(13))
+                                .padding([2, 6])
+                                .on_press(Message::RestoreApk)
+                                .style(style::Button::Primary),
+                        ]
+                    } else {
+                        row![store_btn, backup_apk_btn]
+                    };
+                    let apk_row = match &self.apk_report {
+                        Some(report) => apk_row.push(text(report).style(style::Text::Commentary)),
+                        None => apk_row,
+                    };
+                    apk_row.spacing(10).align_items(Alignment::Center)
+                };
+
+                let i_user = self.selected_user.unwrap_or(User::default()).index;
+                let preview = self
+                    .hovered_index
+                    .filter(|i| settings.general.hover_preview && *i != self.current_package_index)
+                    .and_then(|i| self.phone_packages.get(i_user)?.get(i));
+                let description_scroll = match preview {
+                    Some(package) => scrollable(
+                        column![
+                            text(format!("{} (preview)", package.name))
+                                .style(style::Text::Commentary),
+                            description_view(&package.description, false)
+                        ]
+                        .spacing(5),
+                    ),
+                    None => scrollable(
+                        column![
+                            reversibility,
+                            timestamps,
+                            toggle_package,
+                            enable_for_all_users,
+                            other_users_row,
+                            apk_row,
+                            note_row,
+                            category_row,
+                            permissions,
+                            description_view(&self.description, self.description_expanded)
+                        ]
+                        .spacing(5),
+                    ),
+                }
+                .scrollbar_margin(7)
+                .style(style::Scrollable::Description);
+
+                let description_panel = container(description_scroll)
+                    .height(Length::Units(settings.general.description_height))
+                    .width(Length::Fill)
+                    .style(style::Container::Frame);
+
+                let counts = self.selection_counts(settings);
+                let disable_mode = settings.device.disable_mode;
+                let (restore_label, remove_label) =
+                    if settings.general.count_disabled_as_uninstalled {
+                        (
+                            format!(
+                                "Restore selection ({})",
+                                format_count(counts.to_restore + counts.to_enable)
+                            ),
+                            format!(
+                                "Remove selection ({})",
+                                format_count(counts.to_uninstall + counts.to_disable)
+                            ),
+                        )
+                    } else {
+                        let restore_label = match (counts.to_restore, counts.to_enable) {
+                            (0, 0) if disable_mode => "Enable selection (0)".to_string(),
+                            (n, 0) => format!("Restore selection ({})", format_count(n)),
+                            (0, n) => format!("Enable selection ({})", format_count(n)),
+                            (r, e) => {
+                                format!("Restore {} / Enable {}", format_count(r), format_count(e))
+                            }
+                        };
+                        let remove_label = match (counts.to_uninstall, counts.to_disable) {
+                            (0, 0) if disable_mode => "Disable selection (0)".to_string(),
+                            (n, 0) => format!("Uninstall selection ({})", format_count(n)),
+                            (0, n) => format!("Disable selection ({})", format_count(n)),
+                            (u, d) => format!(
+                                "Uninstall {} / Disable {}",
+                                format_count(u),
+                                format_count(d)
+                            ),
+                        };
+                        (restore_label, remove_label)
+                    };
+                let (restore_label, remove_label) =
+                    if self.all_users && selected_device.user_list.len() > 1 {
+                        (
+                            format!("{} — all users", restore_label),
+                            format!("{} — all users", remove_label),
+                        )
+                    } else {
+                        (restore_label, remove_label)
+                    };
+
+                let apply_restore_selection = button(text(restore_label))
+                    .on_press(Message::ApplyActionOnSelection(Action::Restore))
+                    .padding(5)
+                    .style(style::Button::Primary);
+
+                let enable_and_stop_selection = tooltip(
+                    button(text("Enable & force-stop"))
+                        .on_press(Message::EnableAndStopSelection)
+                        .padding(5)
+                        .style(style::Button::Primary),
+                    "Two steps per package: restores or enables the selected packages, \
+                    then force-stops them. They stay enabled but don't run until opened \
+                    or woken up by the system.",
+                    tooltip::Position::Top,
+                )
+                .size(13)
+                .padding(6)
+                .style(style::Container::Frame);
+
+                let apply_remove_selection = button(text(remove_label))
+                    .on_press(Message::ApplyActionOnSelection(Action::Remove))
+                    .padding(5)
+                    .style(style::Button::Primary);
+
+                let select_all_btn = button(text(format!(
+                    "Select all {} filtered",
+                    format_count(self.selectable_filtered_count(settings))
+                )))
+                .padding(5)
+                .on_press(Message::ToggleAllSelected(true))
+                .style(style::Button::Primary);
+
+                let unselect_all_btn = button("Unselect all")
+                    .padding(5)
+                    .on_press(Message::ToggleAllSelected(false))
+                    .style(style::Button::Primary);
+
+                let invert_selection_btn = button("Invert selection")
+                    .padding(5)
+                    .on_press(Message::InvertSelection)
+                    .style(style::Button::Primary);
+
+                let profiles_btn = button("Profiles")
+                    .padding(5)
+                    .on_press(Message::OpenProfiles)
+                    .style(style::Button::Primary);
+
+                let export_btn = button("Export")
+                    .padding(5)
+                    .on_press(Message::OpenExport)
+                    .style(style::Button::Primary);
+
+                let baseline_btn = button("Baseline")
+                    .padding(5)
+                    .on_press(Message::OpenBaseline)
+                    .style(style::Button::Primary);
+
+                let sync_sources: Vec<SyncSource> = self
+                    .device_loads
+                    .iter()
+                    .filter(|(phone, load)| {
+                        phone.adb_id != selected_device.adb_id
+                            && matches!(load, DeviceLoad::Ready(_))
+                    })
+                    .map(|(phone, _)| SyncSource {
+                        adb_id: phone.adb_id.clone(),
+                        label: format!("{} ({})", phone.model, phone.short_serial()),
+                    })
+                    .collect();
+                let sync_picklist = if sync_sources.is_empty() {
+                    row![]
+                } else {
+                    row![pick_list(sync_sources, None, Message::SyncSourceSelected)
+                        .placeholder("Sync from device")]
+                };
+
+                let save_snapshot_btn = button("Save snapshot")
+                    .padding(5)
+                    .on_press(Message::SaveSnapshot)
+                    .style(style::Button::Primary);
+
+                let snapshot_picklist = pick_list(
+                    self.snapshots.clone(),
+                    self.compared_snapshot.clone(),
+                    Message::SnapshotSelected,
+                )
+                .placeholder("Compare with snapshot");
+
+                let snapshot_comparison = match &self.compared_snapshot {
+                    Some(_) => row![
+                        text(format!(
+                            "{} changed, {} gone",
+                            self.snapshot_changes.len(),
+                            self.snapshot_removed
+                        ))
+                        .style(style::Text::Commentary),
+                        button("Clear")
+                            .padding(5)
+                            .on_press(Message::ClearSnapshotComparison)
+                            .style(style::Button::Primary),
+                    ]
+                    .spacing(10)
+                    .align_items(Alignment::Center),
+                    None => row![],
+                };
+
+                let composition = self.selection_composition();
+                let selection_composition = text(
+                    composition
+                        .iter()
+                        .map(|(removal, count)| format!("{} {}", removal, count))
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                )
+                .size(14)
+                .style(if composition.iter().any(|(r, _)| *r == Removal::Unsafe) {
+                    style::Text::Danger
+                } else {
+                    style::Text::Commentary
+                });
+
+                let i_user = self.selected_user.unwrap_or(User::default()).index;
+                let include_protected = if self
+                    .selection
+                    .selected_packages
+                    .iter()
+                    .any(|i| self.phone_packages[i_user][*i].protected)
+                {
+                    row![checkbox(
+                        "Include protected packages",
+                        self.include_protected,
+                        Message::IncludeProtectedToggled,
+                    )
+                    .text_size(15)
+                    .style(style::CheckBox::SettingsEnabled)]
+                } else {
+                    row![]
+                };
+
+                let selection_btns = row![select_all_btn, unselect_all_btn, invert_selection_btn]
+                    .spacing(10)
+                    .align_items(Alignment::Center);
+                let selection_tools = row![
+                    profiles_btn,
+                    export_btn,
+                    baseline_btn,
+                    sync_picklist,
+                    save_snapshot_btn,
+                    snapshot_picklist,
+                    snapshot_comparison,
+                ]
+                .spacing(10)
+                .align_items(Alignment::Center);
+                let selection_info = row![include_protected, selection_composition]
+                    .spacing(10)
+                    .align_items(Alignment::Center);
+                let queue_btns = row![
+                    button(text("Queue restore"))
+                        .on_press(Message::QueueSelection(Action::Restore))
+                        .padding(5)
+                        .style(style::Button::Primary),
+                    button(text("Queue removal"))
+                        .on_press(Message::QueueSelection(Action::Remove))
+                        .padding(5)
+                        .style(style::Button::Primary),
+                ]
+                .spacing(10);
+                // Android 9+
+                let suspend_btns = if selected_device.android_sdk >= SUSPEND_MIN_SDK {
+                    row![
+                        button(text("Unsuspend selection"))
+                            .on_press(Message::ApplyActionOnSelection(Action::Unsuspend))
+                            .padding(5)
+                            .style(style::Button::Primary),
+                        button(text("Suspend selection"))
+                            .on_press(Message::ApplyActionOnSelection(Action::Suspend))
+                            .padding(5)
+                            .style(style::Button::Primary),
+                    ]
+                    .spacing(10)
+                } else {
+                    row![]
+                };
+                let apply_btns = row![
+                    suspend_btns,
+                    enable_and_stop_selection,
+                    apply_restore_selection,
+                    apply_remove_selection,
+                ]
+                .spacing(10)
+                .align_items(Alignment::Center);
+
+                let action_row: Element<Message, Renderer<Theme>> = if self.minimal_ui() {
+                    column![
+                        selection_btns,
+                        selection_tools,
+                        selection_info,
+                        queue_btns,
+                        apply_btns
+                    ]
+                    .width(Length::Fill)
+                    .spacing(10)
+                    .align_items(Alignment::End)
+                    .into()
+                } else {
+                    row![
+                        selection_btns,
+                        selection_tools,
+                        Space::new(Length::Fill, Length::Shrink),
+                        selection_info,
+                        queue_btns,
+                        apply_btns,
+                    ]
+                    .width(Length::Fill)
+                    .spacing(10)
+                    .align_items(Alignment::Center)
+                    .into()
+                };
+
+                let description_splitter =
+                    splitter(Message::DescriptionResized, Message::DescriptionResizeEnded);
+
+                let stale_lists_warning = if self.stale_lists {
+                    row![text(
+                        "Could not download the latest UAD lists: package descriptions and \
+                        recommendations may be outdated"
+                    )
+                    .style(style::Text::Danger)]
+                } else {
+                    row![]
+                };
+
+                let list_changes_banner = match &self.list_changes {
+                    Some(changes) => row![
+                        text(format!("The UAD lists were updated: {}", changes))
+                            .style(style::Text::Commentary),
+                        Space::new(Length::Fill, Length::Shrink),
+                        button(text("Dismiss").size(13))
+                            .padding([2, 6])
+                            .on_press(Message::DismissListChanges)
+                            .style(style::Button::Primary),
+                    ]
+                    .align_items(Alignment::Center),
+                    None => row![],
+                };
+
+                let recovery_banner = match &self.recovered_selection {
+                    Some(names) => row![
+                        text(format!(
+                            "{} packages were selected but not applied in your last session",
+                            names.len()
+                        ))
+                        .style(style::Text::Commentary),
+                        Space::new(Length::Fill, Length::Shrink),
+                        button(text("Restore selection").size(13))
+                            .padding([2, 6])
+                            .on_press(Message::RestoreRecoveredSelection)
+                            .style(style::Button::Primary),
+                        button(text("Discard").size(13))
+                            .padding([2, 6])
+                            .on_press(Message::DiscardRecoveredSelection)
+                            .style(style::Button::Primary),
+                    ]
+                    .spacing(10)
+                    .align_items(Alignment::Center),
+                    None => row![],
+                };
+
+                let reenabled_banner = if self.reenabled.is_empty() {
+                    row![]
+                } else {
+                    row![
+                        text(format!(
+                            "{} packages you removed are enabled again since your last session \
+                            (system update?)",
+                            self.reenabled.len()
+                        ))
+                        .style(style::Text::Danger),
+                        Space::new(Length::Fill, Length::Shrink),
+                        button(text("Remove them again").size(13))
+                            .padding([2, 6])
+                            .on_press(Message::ReapplyRemovals)
+                            .style(style::Button::Primary),
+                        button(text("Dismiss").size(13))
+                            .padding([2, 6])
+                            .on_press(Message::DismissReenabled)
+                            .style(style::Button::Primary),
+                    ]
+                    .spacing(10)
+                    .align_items(Alignment::Center)
+                };
+
+                let forced_uninstalls_banner = if self.forced_uninstalls.is_empty() {
+                    row![]
+                } else {
+                    row![
+                        text(format!(
+                            "Android doesn't allow disabling {}: uninstalled instead",
+                            self.forced_uninstalls.join(", ")
+                        ))
+                        .style(style::Text::Danger),
+                        Space::new(Length::Fill, Length::Shrink),
+                        button(text("Dismiss").size(13))
+                            .padding([2, 6])
+                            .on_press(Message::DismissForcedUninstalls)
+                            .style(style::Button::Primary),
+                    ]
+                    .spacing(10)
+                    .align_items(Alignment::Center)
+                };
+
+                let running_batch = self
+                    .batch
+                    .as_ref()
+                    .filter(|batch| batch.pending > 0 && !self.runs.is_empty());
+                let cancel_banner = match running_batch {
+                    None => row![],
+                    Some(batch) if batch.cancelling => {
+                        row![
+                            text("Cancelling: the package being changed is completed first")
+                                .style(style::Text::Commentary)
+                        ]
+                    }
+                    Some(batch) => row![
+                        text(format!(
+                            "Bulk action running: {} commands pending",
+                            batch.pending
+                        ))
+                        .style(style::Text::Commentary),
+                        Space::new(Length::Fill, Length::Shrink),
+                        button(text("Cancel").size(13))
+                            .padding([2, 6])
+                            .on_press(Message::CancelBatch)
+                            .style(style::Button::Primary),
+                    ]
+                    .spacing(10)
+                    .align_items(Alignment::Center),
+                };
+
+                let connect_profile_banner = match &self.connect_profile_report {
+                    Some(report) => row![
+                        text(report).style(style::Text::Commentary),
+                        Space::new(Length::Fill, Length::Shrink),
+                        button(text("Dismiss").size(13))
+                            .padding([2, 6])
+                            .on_press(Message::DismissConnectProfileReport)
+                            .style(style::Button::Primary),
+                    ]
+                    .spacing(10)
+                    .align_items(Alignment::Center),
+                    None => row![],
+                };
+
+                let paused_user = selected_device
+                    .user_list
+                    .iter()
+                    .find(|u| Some(u.id) == self.selected_user.map(|s| s.id) && u.paused);
+                let paused_user_banner = match paused_user {
+                    Some(user) => row![
+                        text(format!(
+                            "{} is paused (e.g. a work profile turned off): actions on it \
+                            may fail until it is turned on",
+                            user
+                        ))
+                        .style(style::Text::Commentary),
+                        Space::new(Length::Fill, Length::Shrink),
+                        match &self.user_start {
+                            Some(Err(e)) => text(format!("Could not start it: {}", e))
+                                .size(13)
+                                .style(style::Text::Danger),
+                            _ => text(""),
+                        },
+                        button(text("Start user").size(13))
+                            .padding([2, 6])
+                            .on_press(Message::StartUser(*user))
+                            .style(style::Button::Primary),
+                    ]
+                    .spacing(10)
+                    .align_items(Alignment::Center),
+                    None => row![],
+                };
+
+                let missing_banner = if self.missing_for_user.is_empty() {
+                    row![]
+                } else {
+                    row![
+                        text(format!(
+                            "{} selected packages aren't installed for this user: {}",
+                            self.missing_for_user.len(),
+                            self.missing_for_user.join(", ")
+                        ))
+                        .style(style::Text::Commentary),
+                        Space::new(Length::Fill, Length::Shrink),
+                        button(text("Dismiss").size(13))
+                            .padding([2, 6])
+                            .on_press(Message::DismissMissingForUser)
+                            .style(style::Button::Primary),
+                    ]
+                    .spacing(10)
+                    .align_items(Alignment::Center)
+                };
+
+                // Unsafe packages can always be viewed, only acting on them is gated
+                let read_only_unsafe = self
+                    .filtered_packages
+                    .iter()
+                    .filter(|i| self.phone_packages[i_user][**i].read_only_unsafe(settings))
+                    .count();
+                let audit_banner = if read_only_unsafe == 0 {
+                    row![]
+                } else {
+                    row![text(format!(
+                        "Audit: {} unsafe packages are shown read-only. Turn on expert mode \
+                        to select or remove them.",
+                        read_only_unsafe
+                    ))
+                    .style(style::Text::Commentary)]
+                };
+
+                let scroll_to_top = if self.scroll_offset > 0.05 {
+                    row![
+                        Space::new(Length::Fill, Length::Shrink),
+                        button(text("Scroll to top").size(13))
+                            .padding([2, 6])
+                            .on_press(Message::ScrollToTop)
+                            .style(style::Button::Primary),
+                    ]
+                } else {
+                    row![]
+                };
+
+                let content = column![
+                    stale_lists_warning,
+                    list_changes_banner,
+                    recovery_banner,
+                    reenabled_banner,
+                    forced_uninstalls_banner,
+                    connect_profile_banner,
+                    paused_user_banner,
+                    cancel_banner,
+                    missing_banner,
+                    control_panel,
+                    removal_filters,
+                    debloat_progress,
+                    filter_chips,
+                    audit_banner,
+                    scroll_to_top,
+                    packages_scrollable,
+                    description_splitter,
+                    description_panel
+                ]
+                .width(Length::Fill)
+                .spacing(10)
+                .align_items(Alignment::Center);
+
+                let content = match &self.undo_toast {
+                    Some(toast) => content.push(self.undo_toast_view(toast)),
+                    None => content,
+                };
+                let restore_category_row = row![
+                    text("Undo the debloat of a category"),
+                    pick_list(
+                        Category::all(),
+                        self.restore_category,
+                        Message::RestoreCategorySelected,
+                    )
+                    .placeholder("Category"),
+                    pick_list(
+                        &RestoreScope::ALL[..],
+                        Some(self.restore_scope),
+                        Message::RestoreScopeSelected,
+                    ),
+                    if self.restore_category.is_some() {
+                        button(text("Restore").size(13))
+                            .padding([2, 6])
+                            .on_press(Message::RestoreCategory)
+                            .style(style::Button::Primary)
+                    } else {
+                        button(text("Restore").size(13))
+                            .padding([2, 6])
+                            .style(style::Button::Primary)
+                    },
+                    match &self.restore_report {
+                        Some(report) => text(report).size(14).style(style::Text::Commentary),
+                        None => text(""),
+                    },
+                ]
+                .spacing(10)
+                .align_items(Alignment::Center);
+
+                let content = content
+                    .push(self.queue_view())
+                    .push(restore_category_row)
+                    .push(action_row);
+
+                container(content).height(Length::Fill).padding(10).into()
+            }
+        }
+    }
+
+    /// Shown instead of the package list when there is nothing to list
+    fn empty_list_view(&self) -> Element<Message, Renderer<Theme>> {
+        let i_user = self.selected_user.unwrap_or(User::default()).index;
+        let no_packages = self
+            .phone_packages
+            .get(i_user)
+            .map_or(true, |packages| packages.is_empty());
+
+        let col = if no_packages {
+            let mut hint = String::from(
+                "The device didn't list any package: it may have been disconnected or \
+                locked while loading.",
+            );
+            if self.stale_lists || self.uad_lists.is_empty() {
+                hint.push_str("\nThe UAD lists couldn't be downloaded either.");
+            }
+            column![
+                text("No packages on the device").size(20),
+                text(hint)
+                    .style(style::Text::Commentary)
+                    .horizontal_alignment(alignment::Horizontal::Center),
+                button("Retry")
+                    .padding(5)
+                    .on_press(Message::LoadUadList(true))
+                    .style(style::Button::Primary),
+            ]
+        } else {
+            column![
+                text("No packages match the current filters").size(20),
+                button("Clear all filters")
+                    .padding(5)
+                    .on_press(Message::ClearAllFilters)
+                    .style(style::Button::Primary),
+            ]
+        };
+
+        container(
+            col.spacing(10)
+                .max_width(600)
+                .align_items(Alignment::Center),
+        )
+        .width(Length::Fill)
+        .height(Length::Fill)
+        .center_y()
+        .center_x()
+        .into()
+    }
+
+    /// Replaces the selection with the packages of the profile and describes the outcome
+    /// With `fuzzy_match`, packages close to the missing ones are suggested instead of being selected
+    fn apply_profile(
+        &mut self,
+        profile: &Profile,
+        fuzzy_match: bool,
+        settings: &Settings,
+    ) -> (String, Vec<ProfileSuggestion>) {
+        let i_user = self.selected_user.unwrap_or(User::default()).index;
+        for i in self.selection.selected_packages.clone() {
+            self.set_package_selected(i, false, settings);
+        }
+
+        let mut missing = vec![];
+        let mut refused = 0;
+        for name in &profile.packages {
+            match self.phone_packages[i_user]
+                .iter()
+                .position(|p| p.name == *name)
+            {
+                Some(i) => {
+                    self.set_package_selected(i, true, settings);
+                    if !self.phone_packages[i_user][i].selected {
+                        refused += 1;
+                    }
+                }
+                None => missing.push(name.as_str()),
+            }
+        }
+
+        let mut report = format!(
+            "{} packages selected",
+            profile.packages.len() - missing.len() - refused
+        );
+        if refused > 0 {
+            report.push_str(&format!(
+                "\n{} unsafe packages left out (expert mode is disabled)",
+                refused
+            ));
+        }
+        let mut suggestions = vec![];
+        if fuzzy_match {
+            let candidates = self.phone_packages[i_user]
+                .iter()
+                .map(|p| p.name.as_str())
+                .filter(|name| !profile.packages.iter().any(|p| p == name));
+            for name in &missing {
+                if let Some(candidate) = closest_package(name, candidates.clone()) {
+                    suggestions.push(ProfileSuggestion {
+                        missing: name.to_string(),
+                        candidate: candidate.to_string(),
+                        i_package: self.phone_packages[i_user]
+                            .iter()
+                            .position(|p| p.name == candidate)
+                            .unwrap_or_default(),
+                    });
+                }
+            }
+        }
+        if !missing.is_empty() {
+            report.push_str(&format!("\nNot on this device: {}", missing.join(", ")));
+        }
+        if !suggestions.is_empty() {
+            report.push_str(&format!(
+                "\n{} close matches to review below",
+                suggestions.len()
+            ));
+        }
+        (report, suggestions)
+    }
+
+    fn profiles_view(dialog: &ProfilesDialog) -> Element<Message, Renderer<Theme>> {
+        let profile_picklist = pick_list(
+            dialog.profiles.clone(),
+            dialog.selected.clone(),
+            Message::ProfileSelected,
+        )
+        .placeholder("Select a profile")
+        .width(Length::Units(250));
+
+        let apply_btn = button("Apply").padding(5).style(style::Button::Primary);
+        let delete_btn = button("Delete").padding(5).style(style::Button::Primary);
+        let (apply_btn, delete_btn) = match dialog.selected {
+            Some(_) => (
+                apply_btn.on_press(Message::ApplyProfile),
+                delete_btn.on_press(Message::DeleteProfile),
+            ),
+            None => (apply_btn, delete_btn),
+        };
+
+        let name_input = text_input(
+            "New profile name",
+            &dialog.new_name,
+            Message::ProfileNameChanged,
+        )
+        .on_submit(Message::SaveProfile)
+        .padding(5)
+        .width(Length::Units(250));
+
+        let save_btn = button("Save selection")
+            .padding(5)
+            .on_press(Message::SaveProfile)
+            .style(style::Button::Primary);
+
+        let id_list_input = text_input(
+            "Path of a text file with one package id per line",
+            &dialog.id_list_path,
+            Message::IdListPathChanged,
+        )
+        .on_submit(Message::ImportIdList)
+        .padding(5)
+        .width(Length::Units(250));
+
+        let import_btn = button("Import id list")
+            .padding(5)
+            .on_press(Message::ImportIdList)
+            .style(style::Button::Primary);
+
+        let fuzzy_match_checkbox = checkbox(
+            "Suggest close matches for packages missing on this device",
+            dialog.fuzzy_match,
+            Message::FuzzyMatchToggled,
+        )
+        .text_size(15)
+        .style(style::CheckBox::SettingsEnabled);
+
+        let suggestions = dialog.suggestions.iter().enumerate().fold(
+            column![].spacing(5),
+            |col, (i, suggestion)| {
+                col.push(
+                    row![
+                        text(format!("{} → {}", suggestion.missing, suggestion.candidate))
+                            .size(15)
+                            .width(Length::Fill),
+                        button(text("Accept").size(13))
+                            .padding([2, 6])
+                            .on_press(Message::AcceptSuggestion(i))
+                            .style(style::Button::Primary),
+                        button(text("Reject").size(13))
+                            .padding([2, 6])
+                            .on_press(Message::RejectSuggestion(i))
+                            .style(style::Button::Primary),
+                    ]
+                    .spacing(10)
+                    .align_items(Alignment::Center),
+                )
+            },
+        );
+
+        let close_btn = button("Close")
+            .padding(5)
+            .on_press(Message::CloseProfiles)
+            .style(style::Button::Primary);
+
+        let report = match &dialog.report {
+            Some(report) => text(report).style(style::Text::Commentary),
+            None => text(""),
+        };
+
+        let dialog = container(
+            column![
+                text("Selection profiles").size(25),
+                row![profile_picklist, apply_btn, delete_btn]
+                    .spacing(10)
+                    .align_items(Alignment::Center),
+                row![name_input, save_btn]
+                    .spacing(10)
+                    .align_items(Alignment::Center),
+                row![id_list_input, import_btn]
+                    .spacing(10)
+                    .align_items(Alignment::Center),
+                fuzzy_match_checkbox,
+                report,
+                scrollable(suggestions).height(Length::Shrink),
+                close_btn,
+            ]
+            .spacing(20)
+            .align_items(Alignment::Center),
+        )
+        .padding(20)
+        .max_width(700)
+        .style(style::Container::Frame);
+
+        container(dialog)
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .center_x()
+            .center_y()
+            .into()
+    }
+
+    /// Pulls the APKs of the first package left, if any
+    fn export_next_apk(&mut self, serial: &str) -> Command<Message> {
+        let export = match self.export.as_mut().and_then(|d| d.apk_export.as_mut()) {
+            Some(export) if !export.pending.is_empty() => export,
+            _ => return Command::none(),
+        };
+        let package = export.pending.remove(0);
+        Command::perform(
+            export_apks(serial.to_string(), package.clone(), export.dir.clone()),
+            move |result| Message::ApkExported(package, result),
+        )
+    }
+
+    fn export_view(&self, dialog: &ExportDialog) -> Element<Message, Renderer<Theme>> {
+        let scopes = [ExportScope::Filtered, ExportScope::All].iter().fold(
+            column![].spacing(10),
+            |col, scope| {
+                let count = match scope {
+                    ExportScope::All => {
+                        self.phone_packages[self.selected_user.unwrap().index].len()
+                    }
+                    ExportScope::Filtered => self.filtered_packages.len(),
+                };
+                col.push(
+                    radio(
+                        format!("{} ({})", scope, count),
+                        *scope,
+                        Some(dialog.scope),
+                        Message::ExportScopeSelected,
+                    )
+                    .size(20),
+                )
+            },
+        );
+
+        let export_btn = button("Export to CSV")
+            .padding(5)
+            .on_press(Message::ExportPackages)
+            .style(style::Button::Primary);
+
+        let close_btn = button("Close")
+            .padding(5)
+            .on_press(Message::CloseExport)
+            .style(style::Button::Primary);
+
+        let report = match &dialog.report {
+            Some(report) => text(report).style(style::Text::Commentary),
+            None => text(""),
+        };
+
+        let selected = self.selection.selected_packages.len();
+        let running = dialog
+            .apk_export
+            .as_ref()
+            .map_or(false, |e| !e.pending.is_empty());
+        let export_apks_btn = button(text(format!("Export APKs of the selection ({})", selected)))
+            .padding(5)
+            .style(style::Button::Primary);
+        let export_apks_btn = if selected > 0 && !running {
+            export_apks_btn.on_press(Message::ExportApks)
+        } else {
+            export_apks_btn
+        };
+
+        let apk_row = row![
+            text_input(
+                "Folder (UAD's export folder if empty)",
+                &dialog.apk_dir,
+                Message::ApkExportDirChanged,
+            )
+            .padding(5),
+            export_apks_btn,
+        ]
+        .spacing(10)
+        .align_items(Alignment::Center);
+
+        let apk_report = match &dialog.apk_export {
+            Some(export) => {
+                let done = export.exported + export.failed.len();
+                let total = done + export.pending.len();
+                let summary = if export.pending.is_empty() {
+                    format!(
+                        "{} package(s) exported to {}, {} failed",
+                        export.exported,
+                        export.dir.display(),
+                        export.failed.len()
+                    )
+                } else {
+                    format!("Pulling APKs... {}/{}", done, total)
+                };
+                export.failed.iter().fold(
+                    column![text(summary).style(style::Text::Commentary)].spacing(2),
+                    |col, (package, e)| {
+                        col.push(
+                            text(format!("{}: {}", package, e))
+                                .size(14)
+                                .style(style::Text::Danger),
+                        )
+                    },
+                )
+            }
+            None => column![],
+        };
+
+        let dialog = container(
+            column![
+                text("Export packages").size(25),
+                scopes,
+                report,
+                export_btn,
+                horizontal_rule(5),
+                apk_row,
+                scrollable(apk_report).height(Length::Shrink),
+                close_btn,
+            ]
+            .spacing(20)
+            .align_items(Alignment::Center),
+        )
+        .padding(20)
+        .max_width(700)
+        .style(style::Container::Frame);
+
+        container(dialog)
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .center_x()
+            .center_y()
+            .into()
+    }
+
+    fn baseline_view(&self, report: &BaselineReport) -> Element<Message, Renderer<Theme>> {
+        let packages = &self.phone_packages[self.selected_user.unwrap_or(User::default()).index];
+        let remaining = report
+            .remaining
+            .iter()
+            .fold(column![].spacing(2), |col, i| {
+                col.push(text(&packages[*i].name).size(14))
+            });
+
+        let select_btn = button(text(format!(
+            "Select the {} remaining",
+            report.remaining.len()
+        )))
+        .padding(5)
+        .style(style::Button::Primary);
+        let select_btn = if report.remaining.is_empty() {
+            select_btn
+        } else {
+            select_btn.on_press(Message::SelectBaselineRemaining)
+        };
+
+        let copy_btn = button("Copy summary")
+            .padding(5)
+            .on_press(Message::CopyBaselineSummary)
+            .style(style::Button::Primary);
+
+        let close_btn = button("Close")
+            .padding(5)
+            .on_press(Message::CloseBaseline)
+            .style(style::Button::Primary);
+
+        let dialog = container(
+            column![
+                text("Recommended baseline").size(25),
+                text(report.summary()).size(20),
+                text(
+                    "Packages of the Recommended category still enabled for this user. \
+                    Disabled and uninstalled packages count as removed."
+                )
+                .style(style::Text::Commentary)
+                .size(15),
+                if report.remaining.is_empty() {
+                    Element::from(text("Nothing left to remove").style(style::Text::Commentary))
+                } else {
+                    scrollable(remaining)
+                        .height(Length::Units(300))
+                        .style(style::Scrollable::Description)
+                        .into()
+                },
+                row![select_btn, copy_btn, close_btn].spacing(10),
+            ]
+            .spacing(20)
+            .align_items(Alignment::Center),
+        )
+        .padding(20)
+        .max_width(700)
+        .style(style::Container::Frame);
+
+        container(dialog)
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .center_x()
+            .center_y()
+            .into()
+    }
+
+    fn sync_view(sync: &DeviceSync, target: &Phone) -> Element<Message, Renderer<Theme>> {
+        let changes = sync
+            .changes
+            .iter()
+            .fold(column![].spacing(8), |col, change| {
+                col.push(
+                    column![
+                        text(format!("{}: {} → {}", change.name, change.from, change.to)).size(14),
+                        text(
+                            change
+                                .commands
+                                .iter()
+                                .map(|c| format!("adb -s {} shell {}", target.adb_id, c))
+                                .collect::<Vec<_>>()
+                                .join("\n")
+                        )
+                        .size(13)
+                        .style(style::Text::Commentary),
+                    ]
+                    .spacing(2),
+                )
+            });
+
+        let export_btn = button("Export script")
+            .padding(5)
+            .style(style::Button::Primary);
+        let run_btn = button(text(format!("Run on {}", target.model)))
+            .padding(5)
+            .style(style::Button::Primary);
+        let (export_btn, run_btn) = if sync.changes.is_empty() {
+            (export_btn, run_btn)
+        } else {
+            (
+                export_btn.on_press(Message::ExportSyncScript),
+                run_btn.on_press(Message::RunSync),
+            )
+        };
+
+        let close_btn = button("Close")
+            .padding(5)
+            .on_press(Message::CloseSync)
+            .style(style::Button::Primary);
+
+        let status = match &sync.exported {
+            Some(Ok(path)) => text(format!("Script saved to {}", path.display()))
+                .size(15)
+                .style(style::Text::Commentary),
+            Some(Err(e)) => text(format!("Could not save the script: {}", e))
+                .size(15)
+                .style(style::Text::Danger),
+            None if sync.skipped > 0 => text(format!(
+                "{} packages left out: not removable with the current settings, \
+                or not supported by this device",
+                sync.skipped
+            ))
+            .size(15)
+            .style(style::Text::Commentary),
+            None => text(""),
+        };
+
+        let dialog = container(
+            column![
+                text(format!("Sync from {}", sync.source)).size(25),
+                text(format!(
+                    "Brings user {} of {} ({}) to the package states of {}.",
+                    sync.user.id,
+                    target.model,
+                    target.short_serial(),
+                    sync.source
+                ))
+                .style(style::Text::Commentary)
+                .size(15),
+                if sync.missing_user {
+                    Element::from(
+                        text(format!("{} has no user {}", sync.source, sync.user.id))
+                            .style(style::Text::Danger),
+                    )
+                } else if sync.changes.is_empty() {
+                    Element::from(text("Nothing differs").style(style::Text::Commentary))
+                } else {
+                    scrollable(changes)
+                        .height(Length::Units(300))
+                        .style(style::Scrollable::Description)
+                        .into()
+                },
+                status,
+                row![export_btn, run_btn, close_btn].spacing(10),
+            ]
+            .spacing(20)
+            .align_items(Alignment::Center),
+        )
+        .padding(20)
+        .max_width(700)
+        .style(style::Container::Frame);
+
+        container(dialog)
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .center_x()
+            .center_y()
+            .into()
+    }
+
+    /// Compares the selected user with the same user of the source device
+    fn device_sync(&self, source: SyncSource, settings: &Settings, target: &Phone) -> DeviceSync {
+        let user = self.selected_user.unwrap_or(User::default());
+        let mut sync = DeviceSync {
+            source,
+            user,
+            missing_user: false,
+            changes: vec![],
+            skipped: 0,
+            exported: None,
+        };
+        // User indexes differ from a device to another, ids don't
+        let source_packages = self
+            .device_loads
+            .iter()
+            .find_map(|(phone, load)| match load {
+                DeviceLoad::Ready(packages) if phone.adb_id == sync.source.adb_id => {
+                    let source_user = phone.user_list.iter().find(|u| u.id == user.id);
+                    match source_user {
+                        Some(u) => packages.get(u.index),
+                        None if phone.user_list.is_empty() => packages.first(),
+                        None => None,
+                    }
+                }
+                _ => None,
+            });
+        let source_packages = match source_packages {
+            Some(packages) => packages,
+            None => {
+                sync.missing_user = true;
+                return sync;
+            }
+        };
+
+        let packages = &self.phone_packages[user.index];
+        for (i, to) in sync_changes(source_packages, packages) {
+            let package = &packages[i];
+            let allowed = !package.locked()
+                && (to == PackageState::Enabled || settings.general.can_remove(package.removal));
+            let commands = if allowed {
+                apply_pkg_state_commands(&CorePackage::from(package), &to, &user, target)
+            } else {
+                vec![]
+            };
+            if commands.is_empty() {
+                sync.skipped += 1;
+                continue;
+            }
+            sync.changes.push(SyncChange {
+                i_package: i,
+                name: package.name.clone(),
+                from: package.state,
+                to,
+                commands,
+            });
+        }
+        sync
+    }
+
+    fn reset_filter(&mut self, filter: Filter, general: &GeneralSettings) {
+        match filter {
+            Filter::List => self.selected_list = Some(general.default_list),
+            Filter::State => self.selected_package_state = Some(general.default_package_state),
+            Filter::Removal => {
+                self.selected_removals = default_removals(general);
+                self.selected_custom.clear();
+            }
+            Filter::Restorability => self.selected_restorability = Restorability::default(),
+            Filter::SessionChanges => self.only_session_changes = false,
+            Filter::Search => self.input_value.clear(),
+        }
+    }
+
+    /// A chip for every filter that is not set to its default value
+    fn filter_chips(&self, general: &GeneralSettings) -> Element<Message, Renderer<Theme>> {
+        let mut chips = vec![];
+        if let Some(list) = self.selected_list.filter(|l| *l != general.default_list) {
+            chips.push((Filter::List, format!("List: {}", list)));
+        }
+        if let Some(state) = self
+            .selected_package_state
+            .filter(|s| *s != general.default_package_state)
+        {
+            chips.push((Filter::State, format!("State: {}", state)));
+        }
+        if self.selected_removals != default_removals(general) || !self.selected_custom.is_empty() {
+            let removals = Removal::ALL
+                .iter()
+                .filter(|r| self.selected_removals.contains(r))
+                .map(|r| r.to_string())
+                .chain(
+                    self.categories
+                        .labels
+                        .iter()
+                        .filter(|l| self.selected_custom.contains(*l))
+                        .cloned(),
+                )
+                .collect::<Vec<_>>();
+            chips.push((
+                Filter::Removal,
+                if removals.is_empty() {
+                    "Removal: any".to_string()
+                } else {
+                    format!("Removal: {}", removals.join(", "))
+                },
+            ));
+        }
+        if self.selected_restorability != Restorability::default() {
+            chips.push((
+                Filter::Restorability,
+                self.selected_restorability.to_string(),
+            ));
+        }
+        if self.only_session_changes {
+            chips.push((Filter::SessionChanges, "Changed this session".to_string()));
+        }
+        if !self.input_value.is_empty() {
+            chips.push((Filter::Search, format!("Search: {}", self.input_value)));
+        }
+
+        if chips.is_empty() {
+            return row![].into();
+        }
+
+        chips
+            .into_iter()
+            .fold(row![].spacing(5), |row, (filter, label)| {
+                row.push(
+                    button(text(format!("{}  ×", label)).size(13))
+                        .padding([2, 6])
+                        .on_press(Message::ClearFilter(filter))
+                        .style(style::Button::UserChipIncluded),
+                )
+            })
+            .push(Space::new(Length::Fill, Length::Shrink))
+            .push(
+                button(text("Clear all filters").size(13))
+                    .padding([2, 6])
+                    .on_press(Message::ClearAllFilters)
+                    .style(style::Button::Primary),
+            )
+            .width(Length::Fill)
+            .align_items(Alignment::Center)
+            .into()
+    }
+
+    /// Counted the same way `ApplyActionOnSelection` and `action_handler` pick the packages
+    /// and the commands, so that the buttons tell what will actually be done
+    fn selection_counts(&self, settings: &Settings) -> SelectionCounts {
+        let i_user = self.selected_user.unwrap_or(User::default()).index;
+        let mut counts = SelectionCounts::default();
+        for package in self
+            .selection
+            .selected_packages
+            .iter()
+            .map(|i| &self.phone_packages[i_user][*i])
+        {
+            match package.state {
+                PackageState::Enabled if package.disables(&settings.device) => {
+                    counts.to_disable += 1
+                }
+                PackageState::Enabled => counts.to_uninstall += 1,
+                PackageState::Uninstalled => counts.to_restore += 1,
+                PackageState::Disabled => counts.to_enable += 1,
+                PackageState::All | PackageState::Unknown => {}
+            }
+        }
+        counts
+    }
+
+    /// Selects the installed packages the last list update newly recommends,
+    /// and scrolls to the first one shown.
+    /// Selects the packages of the connect profile the first time a device is shown in the
+    /// session, and applies them if asked to. Applying is confirmed once per device.
+    fn apply_connect_profile(
+        &mut self,
+        settings: &Settings,
+        selected_device: &Phone,
+    ) -> Command<Message> {
+        let name = settings.general.connect_profile.clone();
+        if name.is_empty()
+            || !self
+                .connect_profile_devices
+                .insert(selected_device.adb_id.clone())
+        {
+            return Command::none();
+        }
+        let profile = match load_profile(&name) {
+            Ok(profile) => profile,
+            Err(e) => {
+                self.connect_profile_report =
+                    Some(format!("Could not load profile {}: {}", name, e));
+                return Command::none();
+            }
+        };
+        let (report, _) = self.apply_profile(&profile, false, settings);
+        info!(
+            "[PROFILE] {} applied on connect: {}",
+            name,
+            report.replace('\n', ", ")
+        );
+        self.connect_profile_report =
+            Some(format!("Profile {}: {}", name, report.replace('\n', ". ")));
+        if !settings.general.auto_apply_profile {
+            return Command::none();
+        }
+
+        let mut packages = self.selection.selected_packages.clone();
+        let skipped = packages
+            .drain_filter(|i| self.skips(*i, Action::Remove))
+            .count();
+        if settings.device.auto_apply_confirmed {
+            return self.start_batch(packages, skipped, false, settings, selected_device);
+        }
+        let mut warnings = vec![format!(
+            "Profile {} is applied automatically to the devices that are connected. \
+            This is the first time on {} ({}): check the selection before going on. \
+            You won't be asked again for this device.",
+            name, selected_device.model, selected_device.adb_id
+        )];
+        warnings.extend(self.removal_warnings(&packages, settings));
+        self.confirmation = Some(Confirmation {
+            action: PendingAction::AutoApplyProfile,
+            warnings,
+            preview: self.action_preview(&packages, settings, selected_device),
+        });
+        Command::none()
+    }
+
+    fn select_new_recommended(&mut self, settings: &Settings) -> Command<Message> {
+        let new_recommended: HashSet<String> = self.new_recommended.drain(..).collect();
+        let i_user = self.selected_user.unwrap_or(User::default()).index;
+        let new_packages: Vec<usize> = self.phone_packages[i_user]
+            .iter()
+            .enumerate()
+            .filter(|(_, p)| p.state == PackageState::Enabled && new_recommended.contains(&p.name))
+            .map(|(i, _)| i)
+            .collect();
+        if new_packages.is_empty() {
+            return Command::none();
+        }
+        info!(
+            "[LIST UPDATE] Selecting {} newly recommended packages",
+            new_packages.len()
+        );
+        for i in &new_packages {
+            self.set_package_selected(*i, true, settings);
+        }
+        match self
+            .filtered_packages
+            .iter()
+            .position(|i| new_packages.contains(i))
+        {
+            Some(pos) => {
+                // Same approximation as JumpToLetter
+                let offset = pos as f32 / (self.filtered_packages.len().max(2) - 1) as f32;
+                scrollable::snap_to(PACKAGES_SCROLLABLE_ID.clone(), offset)
+            }
+            None => Command::none(),
+        }
+    }
+
+    /// Number of selected packages of each removal category, in the order of the categories
+    /// Lists whose name contains the query, "All lists" included
+    fn matching_lists(&self) -> Vec<UadList> {
+        let query = self.list_query.to_lowercase();
+        UadList::ALL
+            .iter()
+            .filter(|list| list.to_string().to_lowercase().contains(&query))
+            .copied()
+            .collect()
+    }
+
+    /// A text input filtering the list names as you type, in place of a pick list:
+    /// there can be many more lists to pick from with custom categories.
+    /// Enter picks the highlighted match, the arrow keys move the highlight.
+    fn list_combo_box(&self) -> Element<Message, Renderer<Theme>> {
+        let selected = self.selected_list.unwrap_or_default().to_string();
+        let input = text_input(&selected, &self.list_query, Message::ListQueryChanged)
+            .on_submit(Message::ListQuerySubmitted)
+            .padding(5)
+            .width(Length::Units(120));
+        if self.list_query.is_empty() {
+            return input.into();
+        }
+        let matches = self.matching_lists();
+        let dropdown = if matches.is_empty() {
+            column![text("No matching list")
+                .size(13)
+                .style(style::Text::Commentary)]
+        } else {
+            matches
+                .iter()
+                .enumerate()
+                .fold(column![].spacing(2), |dropdown, (i, list)| {
+                    dropdown.push(
+                        button(text(list.to_string()).size(13))
+                            .padding([2, 6])
+                            .width(Length::Fill)
+                            .on_press(Message::ListSelected(*list))
+                            .style(if i == self.list_highlight {
+                                style::Button::SelectedPackage
+                            } else {
+                                style::Button::NormalPackage
+                            }),
+                    )
+                })
+        };
+        column![
+            input,
+            container(dropdown)
+                .padding(4)
+                .width(Length::Units(120))
+                .style(style::Container::Frame)
+        ]
+        .spacing(2)
+        .into()
+    }
+
+    fn selection_composition(&self) -> Vec<(Removal, usize)> {
+        let i_user = self.selected_user.unwrap_or(User::default()).index;
+        let mut counts: HashMap<Removal, usize> = HashMap::new();
+        for i in &self.selection.selected_packages {
+            *counts
+                .entry(self.phone_packages[i_user][*i].removal)
+                .or_default() += 1;
+        }
+        Removal::ALL
+            .iter()
+            .filter_map(|r| counts.get(r).map(|count| (*r, *count)))
+            .collect()
+    }
+
+    /// (category, packages no longer enabled, packages) for every removal category of
+    /// the selected user, whatever the filters
+    fn debloat_progress(&self) -> Vec<(Removal, usize, usize)> {
+        let i_user = self.selected_user.unwrap_or(User::default()).index;
+        let mut counts: HashMap<Removal, (usize, usize)> = HashMap::new();
+        for package in self.phone_packages.get(i_user).into_iter().flatten() {
+            let (removed, total) = counts.entry(package.removal).or_default();
+            if package.state != PackageState::Enabled {
+                *removed += 1;
+            }
+            *total += 1;
+        }
+        Removal::ALL
+            .iter()
+            .filter_map(|r| counts.get(r).map(|(removed, total)| (*r, *removed, *total)))
+            .collect()
+    }
+
+    /// Same rules as a selection by hand
+    fn select_all_filtered(&mut self, selected: bool, settings: &Settings) {
+        for i in self.filtered_packages.clone() {
+            self.set_package_selected(i, selected, settings);
+        }
+    }
+
+    /// Filtered packages "Select all" would select
+    fn restore_targets(&self, category: Category, device_id: &str) -> RestoreTargets {
+        let i_user = self.selected_user.unwrap_or(User::default()).index;
+        // Only what UAD removed, not what came that way with the device
+        let removed: HashSet<String> = load_history(device_id)
+            .into_iter()
+            .map(|entry| entry.package)
+            .collect();
+        let mut targets = RestoreTargets::default();
+        for (i, package) in self.phone_packages[i_user].iter().enumerate() {
+            if !removed.contains(&package.name)
+                || !category.contains(package)
+                || package.state == PackageState::Enabled
+                || package.locked()
+            {
+                continue;
+            }
+            if package.state == PackageState::Disabled {
+                targets.disabled.push(i);
+            } else if self.restore_scope == RestoreScope::DisabledOnly {
+                targets.left_uninstalled += 1;
+            } else if package.reversible {
+                targets.uninstalled.push(i);
+            } else {
+                targets.not_restorable.push(package.name.clone());
+            }
+        }
+        targets
+    }
+
+    /// Shown under the row. Entries depend on the package: e.g. "Restore" is only offered
+    /// for a removed one.
+    fn context_menu_view(
+        &self,
+        i_package: usize,
+        settings: &Settings,
+        selected_device: &Phone,
+    ) -> Element<Message, Renderer<Theme>> {
+        let i_user = self.selected_user.unwrap_or(User::default()).index;
+        let package = &self.phone_packages[i_user][i_package];
+        let mut entries = vec![
+            ("Copy id".to_string(), ContextAction::CopyId),
+            ("Open store page".to_string(), ContextAction::OpenStorePage),
+            (
+                if self.notes.contains_key(&package.name) {
+                    "Edit note"
+                } else {
+                    "Add note"
+                }
+                .to_string(),
+                ContextAction::AddNote,
+            ),
+            (
+                if settings.general.pinned_packages.contains(&package.name) {
+                    "Unpin"
+                } else {
+                    "Pin"
+                }
+                .to_string(),
+                ContextAction::TogglePin,
+            ),
+            (
+                if settings
+                    .general
+                    .allow_listed_packages
+                    .contains(&package.name)
+                {
+                    "Show in the list again"
+                } else {
+                    "Hide from the list"
+                }
+                .to_string(),
+                ContextAction::ToggleAllowList,
+            ),
+            (
+                "View permissions".to_string(),
+                ContextAction::ViewPermissions,
+            ),
+            ("Refresh state".to_string(), ContextAction::Refresh),
+        ];
+        // Only removals can be overridden
+        if package.state == PackageState::Enabled {
+            entries.push((
+                format!("Switch removal to {}", package.action_override.next()),
+                ContextAction::CycleActionOverride,
+            ));
+        }
+        // Same rule as the action button of the row
+        if !package.locked() {
+            match package.state {
+                PackageState::Enabled if settings.general.can_remove(package.removal) => {
+                    entries.push((
+                        package.action_label(&settings.device).to_string(),
+                        ContextAction::Act,
+                    ));
+                }
+                PackageState::Enabled => {}
+                _ => entries.push(("Restore".to_string(), ContextAction::Act)),
+            }
+        }
+        if selected_device.user_list.len() > 1 {
+            for user in &selected_device.user_list {
+                let state = self
+                    .phone_packages
+                    .get(user.index)
+                    .map_or(PackageState::Uninstalled, |packages| {
+                        packages[i_package].state
+                    });
+                entries.push((
+                    format!(
+                        "{} for user {} only",
+                        if state == PackageState::Enabled {
+                            "Remove"
+                        } else {
+                            "Restore"
+                        },
+                        user.id
+                    ),
+                    ContextAction::ActOnUser(*user),
+                ));
+            }
+        }
+
+        // Rows don't wrap: a few entries per line
+        let menu = entries.chunks(6).fold(column![].spacing(5), |menu, chunk| {
+            menu.push(chunk.iter().fold(
+                row![].spacing(5).align_items(Alignment::Center),
+                |line, (label, action)| {
+                    line.push(
+                        button(text(label).size(13))
+                            .padding([2, 6])
+                            .on_press(Message::ContextAction(i_package, *action))
+                            .style(style::Button::Primary),
+                    )
+                },
+            ))
+        });
+        container(
+            row![
+                menu,
+                Space::new(Length::Fill, Length::Shrink),
+                button(text("Close").size(13))
+                    .padding([2, 6])
+                    .on_press(Message::CloseContextMenu)
+                    .style(style::Button::Primary),
+            ]
+            .spacing(10),
+        )
+        .padding(5)
+        .width(Length::Fill)
+        .style(style::Container::Frame)
+        .into()
+    }
+
+    /// Layout for narrow windows, e.g. side by side with a guide
+    fn minimal_ui(&self) -> bool {
+        self.window_width != 0 && self.window_width < MINIMAL_UI_WIDTH
+    }
+
+    fn selectable_filtered_count(&self, settings: &Settings) -> usize {
+        let i_user = self.selected_user.unwrap_or(User::default()).index;
+        self.filtered_packages
+            .iter()
+            .map(|i| &self.phone_packages[i_user][*i])
+            .filter(|p| p.state != PackageState::Unknown && settings.general.can_remove(p.removal))
+            .count()
+    }
+
+    fn set_package_selected(&mut self, i_package: usize, selected: bool, settings: &Settings) {
+        let i_user = self.selected_user.unwrap_or(User::default()).index;
+        let package = &mut self.phone_packages[i_user][i_package];
+
+        package.selected = selected
+            && package.state != PackageState::Unknown
+            && settings.general.can_remove(package.removal);
+        self.selection.set(i_package, package.selected);
+    }
+
+    /// Applies `selected` to every visible package between `from` and `to` (both included)
+    fn select_range(&mut self, from: usize, to: usize, selected: bool, settings: &Settings) {
+        let position = |i_package| self.filtered_packages.iter().position(|i| *i == i_package);
+        match (position(from), position(to)) {
+            (Some(from), Some(to)) => {
+                let range = from.min(to)..=from.max(to);
+                for i_package in self.filtered_packages[range].to_vec() {
+                    self.set_package_selected(i_package, selected, settings);
+                }
+            }
+            // The start of the range is not visible anymore
+            _ => self.set_package_selected(to, selected, settings),
+        }
+    }
+
+    /// Asks the device for the actual state of a package and updates its row
+    fn refresh_package(
+        &self,
+        i_package: usize,
+        u_index: usize,
+        selected_device: &Phone,
+    ) -> Command<Message> {
+        let name = self.phone_packages[u_index][i_package].name.clone();
+        let serial = selected_device.adb_id.clone();
+        // Same as `load_packages`: no `--user` on single-user devices
+        let user = if selected_device.user_list.len() <= 1 {
+            None
+        } else {
+            selected_device
+                .user_list
+                .iter()
+                .find(|u| u.index == u_index)
+                .copied()
+        };
+        Command::perform(
+            async move { get_package_state(&name, user.as_ref(), &serial) },
+            move |state| Message::PackageRefreshed(i_package, u_index, state),
+        )
+    }
+
+    fn apply_package_action(
+        &mut self,
+        i_package: usize,
+        settings: &Settings,
+        selected_device: &Phone,
+    ) -> Command<Message> {
+        let i_user = self.selected_user.unwrap_or(User::default()).index;
+        let actions = self.package_actions(i_package, settings, selected_device);
+        let commands = build_package_commands(
+            actions,
+            i_package,
+            self.phone_packages[i_user][i_package].removal,
+            &selected_device.adb_id,
+        );
+        self.send_commands(commands)
+    }
+
+    /// Action on a package for a single user, whatever the selected user and the
+    /// multi-user settings
+    fn apply_user_package_action(
+        &mut self,
+        i_package: usize,
+        user: User,
+        settings: &Settings,
+        selected_device: &Phone,
+    ) -> Command<Message> {
+        let package = &self.phone_packages[user.index][i_package];
+        let device_settings = DeviceSettings {
+            multi_user_mode: false,
+            ..settings.device.clone()
+        };
+        let actions = action_handler(
+            &user,
+            &package.into(),
+            package.disables(&settings.device),
+            selected_device,
+            &device_settings,
+        );
+        let commands =
+            build_package_commands(actions, i_package, package.removal, &selected_device.adb_id);
+        self.send_commands(commands)
+    }
+
+    /// Selected packages an action would change for each user, from the same commands
+    /// as the ones that will be sent (the selection itself is shared by all users)
+    fn selection_per_user(
+        &self,
+        settings: &Settings,
+        selected_device: &Phone,
+    ) -> Vec<(User, usize)> {
+        let i_user = self.selected_user.unwrap_or(User::default()).index;
+        let mut counts: HashMap<usize, usize> = HashMap::new();
+        for i in &self.selection.selected_packages {
+            let mut users: Vec<usize> = self
+                .package_actions(*i, settings, selected_device)
+                .iter()
+                .map(|(u, _)| u.unwrap_or(i_user))
+                .collect();
+            users.sort_unstable();
+            users.dedup();
+            for u in users {
+                *counts.entry(u).or_default() += 1;
+            }
+        }
+        selected_device
+            .user_list
+            .iter()
+            .map(|user| (*user, counts.get(&user.index).copied().unwrap_or(0)))
+            .collect()
+    }
+
+    /// Shell commands the action button of a package runs, one per line,
+    /// as they could be typed by hand.
+    fn command_preview(
+        &self,
+        i_package: usize,
+        settings: &Settings,
+        selected_device: &Phone,
+    ) -> String {
+        self.package_actions(i_package, settings, selected_device)
+            .iter()
+            .map(|(_, command)| format!("adb shell {}", command))
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    /// Commands of the action on a package, for the users it applies to.
+    /// In "all users" mode, every user whose package is in the same situation as for
+    /// the selected user gets the commands matching its own state: removing a package
+    /// doesn't restore it for users who already removed it, and restoring it enables or
+    /// reinstalls it depending on the user.
+    /// Users the actions are sent to (see `package_actions`)
+    fn target_users(&self, settings: &Settings, selected_device: &Phone) -> Vec<User> {
+        let selected = self.selected_user.unwrap_or(User::default());
+        selected_device
+            .user_list
+            .iter()
+            .filter(|u| self.all_users || settings.device.multi_user_mode || u.id == selected.id)
+            .copied()
+            .collect()
+    }
+
+    fn package_actions(
+        &self,
+        i_package: usize,
+        settings: &Settings,
+        selected_device: &Phone,
+    ) -> Vec<(Option<usize>, String)> {
+        let i_user = self.selected_user.unwrap_or(User::default()).index;
+        let package = &self.phone_packages[i_user][i_package];
+        let disable = package.disables(&settings.device);
+
+        // Rows stand for every user when collapsed
+        let collapsed = settings.device.multi_user_mode && settings.device.collapse_multi_user;
+        let mut actions = if (self.all_users || collapsed) && selected_device.user_list.len() > 1 {
+            let removal = package.state == PackageState::Enabled;
+            // One user at a time, from their own state
+            let device_settings = DeviceSettings {
+                multi_user_mode: false,
+                ..settings.device.clone()
+            };
+            selected_device
+                .user_list
+                .iter()
+                .filter_map(|user| {
+                    self.phone_packages
+                        .get(user.index)
+                        .map(|packages| (user, &packages[i_package]))
+                })
+                .filter(|(_, p)| (p.state == PackageState::Enabled) == removal)
+                .flat_map(|(user, p)| {
+                    action_handler(user, &p.into(), disable, selected_device, &device_settings)
+                })
+                .collect()
+        } else {
+            action_handler(
+                &self.selected_user.unwrap(),
+                &package.into(),
+                disable,
+                selected_device,
+                &settings.device,
+            )
+        };
+        if settings.device.collapse_multi_user {
+            actions.retain(|(u, _)| {
+                u.map_or(true, |u| !self.user_exclusions.contains(&(i_package, u)))
+            });
+        }
+        actions
+    }
+
+    /// Before → after state of every package and user the actions would change,
+    /// from the same commands as the ones that will be sent.
+    fn action_preview(
+        &self,
+        packages: &[usize],
+        settings: &Settings,
+        selected_device: &Phone,
+    ) -> Vec<String> {
+        let i_user = self.selected_user.unwrap_or(User::default()).index;
+        let multi_user = selected_device.user_list.len() > 1;
+        let mut preview = vec![];
+        for i in packages {
+            let package = &self.phone_packages[i_user][*i];
+            let mut affected_users: Vec<usize> = self
+                .package_actions(*i, settings, selected_device)
+                .iter()
+                .map(|(u, _)| u.unwrap_or(i_user))
+                .collect();
+            affected_users.dedup();
+            for u in affected_users {
+                let before = self
+                    .phone_packages
+                    .get(u)
+                    .map_or(package.state, |packages| packages[*i].state);
+                let after = match before {
+                    PackageState::Enabled if package.disables(&settings.device) => {
+                        PackageState::Disabled
+                    }
+                    PackageState::Enabled => PackageState::Uninstalled,
+                    _ => PackageState::Enabled,
+                };
+                let user = match selected_device
+                    .user_list
+                    .iter()
+                    .find(|user| user.index == u)
+                {
+                    Some(user) if multi_user => format!(" (user {})", user.id),
+                    _ => String::new(),
+                };
+                preview.push(format!("{}{}: {} → {}", package.name, user, before, after));
+            }
+        }
+        preview
+    }
+
+    /// Restores (or enables) the package for every user of the device it is not enabled for,
+    /// whatever the multi-user mode. Useful for users created after a restoration.
+    fn enable_for_all_users(
+        &mut self,
+        i_package: usize,
+        selected_device: &Phone,
+    ) -> Command<Message> {
+        let mut commands = vec![];
+        for user in &selected_device.user_list {
+            let package = match self.phone_packages.get(user.index) {
+                Some(packages) => &packages[i_package],
+                None => continue,
+            };
+            let actions = apply_pkg_state_commands(
+                &CorePackage::from(package),
+                &PackageState::Enabled,
+                user,
+                selected_device,
+            )
+            .into_iter()
+            .map(|command| (Some(user.index), command))
+            .collect();
+            commands.extend(build_package_commands(
+                actions,
+                i_package,
+                package.removal,
+                &selected_device.adb_id,
+            ));
+        }
+        self.send_commands(commands)
+    }
+
+    /// Disables an enabled package, or enables (restores) a package that isn't.
+    /// Unlike the action button, the package is never uninstalled: the commands are
+    /// flagged so `ChangePackageState` expects a disabled package.
+    fn toggle_package(
+        &mut self,
+        i_package: usize,
+        settings: &Settings,
+        selected_device: &Phone,
+    ) -> Command<Message> {
+        let i_user = self.selected_user.unwrap_or(User::default()).index;
+        let package = &self.phone_packages[i_user][i_package];
+        let allowed = match package.state {
+            PackageState::Enabled => settings.general.can_remove(package.removal),
+            _ => !package.locked(),
+        };
+        if !allowed || selected_device.android_sdk < 23 {
+            return Command::none();
+        }
+        let actions = action_handler(
+            &self.selected_user.unwrap_or(User::default()),
+            &package.into(),
+            true,
+            selected_device,
+            &settings.device,
+        );
+        let mut jobs = build_package_jobs(actions, i_package, &package.name, package.removal);
+        for job in &mut jobs {
+            job.info.force_disable = true;
+        }
+        let commands = job_commands(jobs, &selected_device.adb_id);
+        self.send_commands(commands)
+    }
+
+    /// Suspends or unsuspends packages for the same users as a removal. Outside of the bulk
+    /// actions: the commands are flagged so `ChangePackageState` only updates `suspended`.
+    fn suspend_packages(
+        &mut self,
+        packages: &[usize],
+        suspend: bool,
+        settings: &Settings,
+        selected_device: &Phone,
+    ) -> Command<Message> {
+        let i_user = self.selected_user.unwrap_or(User::default()).index;
+        let mut commands = vec![];
+        for i_package in packages {
+            let package = &self.phone_packages[i_user][*i_package];
+            let actions = suspend_handler(
+                &self.selected_user.unwrap_or(User::default()),
+                &package.name,
+                suspend,
+                selected_device,
+                &settings.device,
+            );
+            let mut jobs = build_package_jobs(actions, *i_package, &package.name, package.removal);
+            for job in &mut jobs {
+                job.info.suspend = Some(suspend);
+            }
+            commands.extend(job_commands(jobs, &selected_device.adb_id));
+        }
+        self.send_commands(commands)
+    }
+
+    /// Warnings about the consequences of removing those of `packages` that are enabled
+    fn removal_warnings(&self, packages: &[usize], settings: &Settings) -> Vec<String> {
+        let i_user = self.selected_user.unwrap_or(User::default()).index;
+        let enabled: HashSet<&str> = self.phone_packages[i_user]
+            .iter()
+            .filter(|p| p.state == PackageState::Enabled)
+            .map(|p| p.name.as_str())
+            .collect();
+
+        let mut warnings = vec![];
+        if !packages.is_empty() && packages.len() >= settings.general.confirm_threshold {
+            warnings.push(format!(
+                "{} packages are about to be changed",
+                packages.len()
+            ));
+        }
+
+        warnings.extend(
+            packages
+                .iter()
+                .map(|i| &self.phone_packages[i_user][*i])
+                .filter(|p| p.state == PackageState::Enabled)
+                .flat_map(|p| {
+                    let mut warnings = dependency_warnings(&p.name, |d| enabled.contains(d));
+                    // Whatever the size of the batch
+                    if p.removal == Removal::Unsafe {
+                        warnings.push(format!("{} is marked as unsafe", p.name));
+                    }
+                    if !p.reversible && !p.disables(&settings.device) {
+                        warnings.push(format!(
+                            "{} cannot be restored once uninstalled, only installed again",
+                            p.name
+                        ));
+                    }
+                    warnings
+                }),
+        );
+        warnings
+    }
+
+    fn confirmation_view(confirmation: &Confirmation) -> Element<Message, Renderer<Theme>> {
+        let warnings = confirmation
+            .warnings
+            .iter()
+            .fold(column![].spacing(10), |col, warning| {
+                col.push(text(warning).style(style::Text::Danger))
+            });
+
+        let cancel_btn = button("Cancel")
+            .padding(5)
+            .on_press(Message::CancelAction)
+            .style(style::Button::Primary);
+
+        let confirm_btn = button("Continue anyway")
+            .padding(5)
+            .on_press(Message::ConfirmAction)
+            .style(style::Button::Primary);
+
+        let dialog = container(
+            column![
+                text("Are you sure?").size(25),
+                scrollable(warnings).height(Length::Shrink),
+                if confirmation.preview.is_empty() {
+                    column![]
+                } else {
+                    column![
+                        text(format!("{} changes:", confirmation.preview.len())),
+                        scrollable(
+                            confirmation
+                                .preview
+                                .iter()
+                                .fold(column![].spacing(2), |col, change| col
+                                    .push(text(change).size(14)))
+                        )
+                        .height(Length::Units(200))
+                        .style(style::Scrollable::Description),
+                    ]
+                    .spacing(5)
+                },
+                row![cancel_btn, confirm_btn].spacing(10),
+            ]
+            .spacing(20)
+            .align_items(Alignment::Center),
+        )
+        .padding(20)
+        .max_width(700)
+        .style(style::Container::Frame);
+
+        container(dialog)
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .center_x()
+            .center_y()
+            .into()
+    }
+
+    /// Whether a bulk action leaves this package out (already in the wanted state...)
+    fn skips(&self, i_package: usize, action: Action) -> bool {
+        let i_user = self.selected_user.unwrap_or(User::default()).index;
+        let package = &self.phone_packages[i_user][i_package];
+        match action {
+            Action::Remove => {
+                package.state != PackageState::Enabled
+                    || (package.protected && !self.include_protected)
+            }
+            Action::Restore => package.state == PackageState::Enabled || package.locked(),
+            // An unknown suspension is checked by the device
+            Action::Suspend => {
+                package.state != PackageState::Enabled
+                    || package.suspended == Some(true)
+                    || (package.protected && !self.include_protected)
+            }
+            Action::Unsuspend => {
+                matches!(
+                    package.state,
+                    PackageState::Uninstalled | PackageState::Unknown
+                ) || package.suspended == Some(false)
+            }
+        }
+    }
+
+    /// Applies a bulk action, or asks first if anything deserves a warning
+    fn start_batch(
+        &mut self,
+        packages: Vec<usize>,
+        skipped: usize,
+        force_stop: bool,
+        settings: &Settings,
+        selected_device: &Phone,
+    ) -> Command<Message> {
+        let mut warnings = self.removal_warnings(&packages, settings);
+        if packages.is_empty() {
+            return self.confirm_batch(
+                packages,
+                skipped,
+                force_stop,
+                warnings,
+                settings,
+                selected_device,
+            );
+        }
+        warnings.extend(paused_users_warning(
+            &self.target_users(settings, selected_device),
+        ));
+        // Asks the device: the confirmation waits for it
+        let generation = self.run_generation;
+        let serial = selected_device.adb_id.clone();
+        let battery_threshold = settings.general.battery_threshold;
+        Command::perform(
+            async move {
+                warnings.extend(device_lock_warning(&serial));
+                warnings.extend(battery_warning(&serial, battery_threshold));
+                (packages, warnings)
+            },
+            move |(packages, warnings)| {
+                Message::BatchChecked(generation, packages, skipped, force_stop, warnings)
+            },
+        )
+    }
+
+    /// Applies a checked bulk action, or shows its warnings first
+    fn confirm_batch(
+        &mut self,
+        packages: Vec<usize>,
+        skipped: usize,
+        force_stop: bool,
+        warnings: Vec<String>,
+        settings: &Settings,
+        selected_device: &Phone,
+    ) -> Command<Message> {
+        if warnings.is_empty() {
+            self.apply_batch(packages, skipped, force_stop, settings, selected_device)
+        } else {
+            let preview = self.action_preview(&packages, settings, selected_device);
+            self.confirmation = Some(Confirmation {
+                action: PendingAction::Batch {
+                    packages,
+                    skipped,
+                    force_stop,
+                },
+                warnings,
+                preview,
+            });
+            Command::none()
+        }
+    }
+
+    /// Staged actions, run one after the other in this order
+    fn queue_view(&self) -> Element<Message, Renderer<Theme>> {
+        if self.queue.is_empty() {
+            return row![].into();
+        }
+        let last = self.queue.len() - 1;
+        let entries = self.queue.iter().enumerate().fold(
+            column![].spacing(2),
+            |col, (pos, (name, action))| {
+                col.push(
+                    row![
+                        text(format!("{}. {} — {}", pos + 1, name, action))
+                            .size(14)
+                            .width(Length::Fill),
+                        if pos > 0 {
+                            button(text("Up").size(13))
+                                .padding([2, 6])
+                                .on_press(Message::MoveQueued(pos, true))
+                                .style(style::Button::Primary)
+                        } else {
+                            button(text("Up").size(13))
+                                .padding([2, 6])
+                                .style(style::Button::Primary)
+                        },
+                        if pos < last {
+                            button(text("Down").size(13))
+                                .padding([2, 6])
+                                .on_press(Message::MoveQueued(pos, false))
+                                .style(style::Button::Primary)
+                        } else {
+                            button(text("Down").size(13))
+                                .padding([2, 6])
+                                .style(style::Button::Primary)
+                        },
+                        button(text("Remove").size(13))
+                            .padding([2, 6])
+                            .on_press(Message::RemoveQueued(pos))
+                            .style(style::Button::Primary),
+                    ]
+                    .spacing(5)
+                    .align_items(Alignment::Center),
+                )
+            },
+        );
+        container(
+            column![
+                row![
+                    text(format!("Queue ({})", self.queue.len())),
+                    Space::new(Length::Fill, Length::Shrink),
+                    button(text("Clear").size(13))
+                        .padding([2, 6])
+                        .on_press(Message::ClearQueue)
+                        .style(style::Button::Primary),
+                    button(text("Run queue").size(13))
+                        .padding([2, 6])
+                        .on_press(Message::RunQueue)
+                        .style(style::Button::Primary),
+                ]
+                .spacing(10)
+                .align_items(Alignment::Center),
+                scrollable(entries)
+                    .height(Length::Units(120))
+                    .style(style::Scrollable::Description),
+            ]
+            .spacing(5),
+        )
+        .padding(5)
+        .width(Length::Fill)
+        .style(style::Container::Frame)
+        .into()
+    }
+
+    /// `force_stop`: restored packages are force-stopped right after
+    fn apply_batch(
+        &mut self,
+        packages: Vec<usize>,
+        skipped: usize,
+        force_stop: bool,
+        settings: &Settings,
+        selected_device: &Phone,
+    ) -> Command<Message> {
+        let i_user = self.selected_user.unwrap_or(User::default()).index;
+        let mut jobs = vec![];
+        let mut attempted = vec![];
+        for i in packages {
+            let package = &self.phone_packages[i_user][i];
+            attempted.push((i, package.action_label(&settings.device)));
+            let mut actions = self.package_actions(i, settings, selected_device);
+            if force_stop {
+                actions = with_force_stop(actions, &package.name, &selected_device.user_list);
+            }
+            jobs.extend(build_package_jobs(
+                actions,
+                i,
+                &package.name,
+                package.removal,
+            ));
+        }
+        allow_adb_restart();
+        self.batch = Some(BatchSummary {
+            pending: jobs.len(),
+            skipped,
+            attempted,
+            force_stop,
+            ..BatchSummary::default()
+        });
+        self.run_generation += 1;
+        let generation = self.run_generation;
+        // Measured before anything runs
+        let serial = selected_device.adb_id.clone();
+        Command::perform(
+            async move { (get_free_storage(&serial), jobs) },
+            move |(free_storage, jobs)| Message::RunReady(generation, free_storage, jobs),
+        )
+    }
+
+    /// Progress of the running bulk actions, the expiry of the undo toast
+    /// and the periodic save of the selection
+    pub fn subscription(&self) -> Subscription<Message> {
+        let recovery = match self.loading_state {
+            LoadingState::Ready(_) => {
+                time::every(Duration::from_secs(RECOVERY_SAVE_INTERVAL)).map(Message::SaveRecovery)
+            }
+            _ => Subscription::none(),
+        };
+        // Only ticks while there is a toast
+        let toast = match self.undo_toast {
+            Some(_) => time::every(Duration::from_secs(1)).map(Message::ToastTick),
+            None => Subscription::none(),
+        };
+        let runs = Subscription::batch(self.runs.iter().map(|(generation, receiver, _)| {
+            let generation = *generation;
+            let state = RunReceiver::Shared(receiver.clone());
+            subscription::unfold(generation, state, move |state| async move {
+                let mut receiver = match state {
+                    RunReceiver::Taken(receiver) => receiver,
+                    RunReceiver::Shared(shared) => {
+                        let taken = shared.lock().ok().and_then(|mut r| r.take());
+                        match taken {
+                            Some(receiver) => receiver,
+                            None => future::pending().await,
+                        }
+                    }
+                };
+                match receiver.next().await {
+                    Some(progress) => (
+                        Some(Message::RunProgress(generation, progress)),
+                        RunReceiver::Taken(receiver),
+                    ),
+                    // Closed: the subscription is dropped with the next update
+                    None => future::pending().await,
+                }
+            })
+        }));
+        Subscription::batch([runs, toast, recovery])
+    }
+
+    /// Every package command goes through here to be counted until it is resolved
+    /// (see `resolve_batch_command`)
+    fn send_commands(&mut self, commands: Vec<Command<Message>>) -> Command<Message> {
+        self.commands_status.pending += commands.len();
+        // Keep the running bulk action from completing before these commands
+        if let Some(batch) = &mut self.batch {
+            batch.pending += commands.len();
+        }
+        Command::batch(commands)
+    }
+
+    /// Records the outcome of a package command, and of the running bulk action if any.
+    /// Free storage is measured again once the last one resolved.
+    fn resolve_batch_command(
+        &mut self,
+        i_package: Option<usize>,
+        success: bool,
+        general: &GeneralSettings,
+        selected_device: &Phone,
+    ) -> Command<Message> {
+        self.commands_status.resolve(success);
+        if let Some(batch) = &mut self.batch {
+            batch.resolve(i_package, success);
+            // Nothing left to recover
+            if batch.pending == 0 && batch.failed.is_empty() && batch.cancelled.is_empty() {
+                clear_recovery(&selected_device.adb_id);
+                self.recovery_saved.clear();
+                // Shown once the batch summary is closed
+                if general.reboot_after_batch && !batch.attempted.is_empty() {
+                    self.ask_reboot();
+                }
+            }
+        }
+        if !general.report_path.is_empty() {
+            if let Some(batch) = self.batch.as_ref().filter(|b| b.pending == 0) {
+                self.write_batch_report(batch, &general.report_path, &selected_device.adb_id);
+            }
+        }
+        let verify_command = if general.verify_batch {
+            self.verify_batch(selected_device)
+        } else {
+            Command::none()
+        };
+        match &mut self.batch {
+            Some(batch) => {
+                if batch.pending == 0 && batch.free_storage_before.is_some() {
+                    let serial = selected_device.adb_id.clone();
+                    Command::batch([
+                        Command::perform(
+                            async move { get_free_storage(&serial) },
+                            Message::StorageMeasured,
+                        ),
+                        verify_command,
+                    ])
+                } else {
+                    verify_command
+                }
+            }
+            None => Command::none(),
+        }
+    }
+
+    /// Once the bulk action is over, reads the state of every package it changed again.
+    /// Commands can succeed without changing anything.
+    fn verify_batch(&mut self, selected_device: &Phone) -> Command<Message> {
+        let batch = match self.batch.as_mut().filter(|b| b.pending == 0) {
+            Some(batch) => batch,
+            None => return Command::none(),
+        };
+        let expected = std::mem::take(&mut batch.expected);
+        batch.verifying += expected.len();
+        let multi_user = selected_device.user_list.len() > 1;
+        let generation = self.run_generation;
+        Command::batch(expected.into_iter().map(|((i_package, u_index), state)| {
+            let name = self.phone_packages[u_index][i_package].name.clone();
+            let serial = selected_device.adb_id.clone();
+            let user = selected_device
+                .user_list
+                .iter()
+                .find(|u| u.index == u_index)
+                .copied()
+                .filter(|_| multi_user);
+            Command::perform(
+                async move { get_package_state(&name, user.as_ref(), &serial) },
+                move |actual| Message::BatchVerified(generation, i_package, u_index, state, actual),
+            )
+        }))
+    }
+
+    /// Queries the details that need a command per package, a few packages at a time,
+    /// so the list can be used in the meantime. A new load drops the previous one.
+    fn start_details_load(&mut self, selected_device: &Phone) -> Command<Message> {
+        self.details_generation += 1;
+        self.pending_details = self
+            .phone_packages
+            .iter()
+            .enumerate()
+            .flat_map(|(i_user, packages)| {
+                packages
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, p)| p.state == PackageState::Disabled && p.disabled_by.is_none())
+                    .map(move |(i_package, _)| (i_user, i_package))
+            })
+            .collect();
+        self.load_next_details(selected_device)
+    }
+
+    fn load_next_details(&mut self, selected_device: &Phone) -> Command<Message> {
+        if self.pending_details.is_empty() {
+            return Command::none();
+        }
+        let chunk: Vec<(usize, usize, String, Option<User>)> = self
+            .pending_details
+            .drain(..DETAILS_CHUNK_SIZE.min(self.pending_details.len()))
+            .filter_map(|(i_user, i_package)| {
+                let name = self
+                    .phone_packages
+                    .get(i_user)?
+                    .get(i_package)?
+                    .name
+                    .clone();
+                let user = selected_device
+                    .user_list
+                    .iter()
+                    .find(|u| u.index == i_user)
+                    .copied();
+                Some((i_user, i_package, name, user))
+            })
+            .collect();
+        let serial = selected_device.adb_id.clone();
+        let generation = self.details_generation;
+        Command::perform(
+            async move {
+                chunk
+                    .into_iter()
+                    .map(|(i_user, i_package, name, user)| {
+                        (
+                            i_user,
+                            i_package,
+                            get_disabled_by(&name, user.as_ref(), &serial),
+                        )
+                    })
+                    .collect()
+            },
+            move |details| Message::DetailsLoaded(generation, details),
+        )
+    }
+
+    /// A reboot interrupts whatever the phone is doing: it is always confirmed.
+    /// Returns false if there's nowhere to show the confirmation.
+    pub fn ask_reboot(&mut self) -> bool {
+        if !matches!(self.loading_state, LoadingState::Ready(_)) {
+            return false;
+        }
+        self.confirmation = Some(Confirmation {
+            action: PendingAction::Reboot,
+            warnings: vec![
+                "The device will reboot: anything running on it will be interrupted".to_string(),
+            ],
+            preview: vec![],
+        });
+        true
+    }
 
-                // let package_amount = text(format!("{} packages found", packages.len()));
+    /// Same outcome as the one shown in the batch summary
+    fn write_batch_report(&self, batch: &BatchSummary, path: &str, serial: &str) {
+        let i_user = self.selected_user.unwrap_or(User::default()).index;
+        let packages = batch
+            .attempted
+            .iter()
+            .map(|(i, action)| PackageReport {
+                name: self.phone_packages[i_user][*i].name.clone(),
+                action: action.to_string(),
+                success: !batch.failed.contains(i) && !batch.cancelled.contains(i),
+                error: if batch.cancelled.contains(i) {
+                    Some("Cancelled before it started".to_string())
+                } else {
+                    batch.errors.get(i).cloned()
+                },
+            })
+            .collect();
+        let report = RunReport::new(serial, packages, batch.skipped);
+        let path = artifact_path(&REPORT_DIR, path);
+        match write_report(&path, &report) {
+            Ok(_) => info!("[REPORT] Bulk action report written to {}", path.display()),
+            Err(e) => error!("[REPORT] Could not write {}: {}", path.display(), e),
+        }
+    }
 
-                let user_picklist = pick_list(
-                    selected_device.user_list.clone(),
-                    self.selected_user,
-                    Message::UserSelected,
-                )
-                .width(Length::Units(85));
+    fn batch_summary_view(&self, batch: &BatchSummary) -> Element<Message, Renderer<Theme>> {
+        let i_user = self.selected_user.unwrap_or(User::default()).index;
 
-                let divider = Space::new(Length::Fill, Length::Shrink);
+        let counts = column![
+            text(if batch.cancelling {
+                "Bulk action cancelled"
+            } else {
+                "Bulk action completed"
+            })
+            .size(25),
+            text(format!("{} succeeded", batch.succeeded.len())),
+            text(format!("{} failed", batch.failed.len())).style(if batch.failed.is_empty() {
+                style::Text::Default
+            } else {
+                style::Text::Danger
+            }),
+            text(format!(
+                "{} skipped (already in the wanted state)",
+                batch.skipped
+            ))
+            .style(style::Text::Commentary),
+        ]
+        .spacing(10);
 
-                let list_picklist =
-                    pick_list(&UadList::ALL[..], self.selected_list, Message::ListSelected);
-                let package_state_picklist = pick_list(
-                    &PackageState::ALL[..],
-                    self.selected_package_state,
-                    Message::PackageStateSelected,
-                );
+        let counts = if batch.verifying > 0 {
+            counts.push(
+                text(format!("Verifying {} packages...", batch.verifying))
+                    .style(style::Text::Commentary),
+            )
+        } else if !batch.unverified.is_empty() {
+            counts.push(
+                text(format!(
+                    "{} failed verification (counted as failed): {}",
+                    batch.unverified.len(),
+                    batch
+                        .unverified
+                        .iter()
+                        .map(|i| self.phone_packages[i_user][*i].name.as_str())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ))
+                .style(style::Text::Danger),
+            )
+        } else {
+            counts
+        };
 
-                let removal_picklist = pick_list(
-                    &Removal::ALL[..],
-                    self.selected_removal,
-                    Message::RemovalSelected,
-                );
+        let names = |packages: &[usize]| {
+            packages
+                .iter()
+                .map(|i| self.phone_packages[i_user][*i].name.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        let counts = if batch.cancelling {
+            counts
+                .push(
+                    text(format!("{} not run (cancelled)", batch.cancelled.len()))
+                        .style(style::Text::Commentary),
+                )
+                .push(
+                    scrollable(
+                        column![
+                            text(format!("Completed: {}", names(&batch.succeeded))).size(14),
+                            text(format!(
+                                "Mid-sequence when cancelled, completed anyway: {}",
+                                names(&batch.finished_after_cancel)
+                            ))
+                            .size(14),
+                            text(format!("Not run: {}", names(&batch.cancelled))).size(14),
+                        ]
+                        .spacing(5),
+                    )
+                    .height(Length::Units(150))
+                    .style(style::Scrollable::Description),
+                )
+        } else {
+            counts
+        };
 
-                let control_panel = row![
-                    search_packages,
-                    user_picklist,
-                    divider,
-                    removal_picklist,
-                    package_state_picklist,
-                    list_picklist,
-                ]
-                .width(Length::Fill)
-                .align_items(Alignment::Center)
-                .spacing(10)
-                .padding([0, 16, 0, 0]);
+        let counts = match batch.storage_summary() {
+            Some(summary) => counts.push(text(summary)),
+            None => counts,
+        };
 
-                let packages =
-                    self.filtered_packages
-                        .iter()
-                        .fold(column![].spacing(6), |col, i| {
-                            col.push(
-                                self.phone_packages[self.selected_user.unwrap().index][*i]
-                                    .view(settings, selected_device)
-                                    .map(move |msg| Message::List(*i, msg)),
-                            )
-                        });
+        let failures = if batch.show_failures {
+            batch.failed.iter().fold(column![].spacing(5), |col, i| {
+                col.push(text(&self.phone_packages[i_user][*i].name).style(style::Text::Danger))
+            })
+        } else {
+            column![]
+        };
 
-                let packages_scrollable = scrollable(packages)
-                    .scrollbar_margin(2)
-                    .height(Length::FillPortion(6))
-                    .style(style::Scrollable::Packages);
+        let toggle_failures_btn = button(text(if batch.show_failures {
+            "Hide failures"
+        } else {
+            "Show failures"
+        }))
+        .padding(5)
+        .style(style::Button::Primary);
 
-                // let mut packages_v: Vec<&str> = self.packages.lines().collect();
+        let retry_btn = button("Retry failed")
+            .padding(5)
+            .style(style::Button::Primary);
 
-                let description_scroll = scrollable(text(&self.description))
-                    .scrollbar_margin(7)
-                    .style(style::Scrollable::Description);
+        let (toggle_failures_btn, retry_btn) = if batch.failed.is_empty() {
+            (toggle_failures_btn, retry_btn)
+        } else {
+            (
+                toggle_failures_btn.on_press(Message::ToggleBatchFailures),
+                retry_btn.on_press(Message::RetryFailedPackages),
+            )
+        };
 
-                let description_panel = container(description_scroll)
-                    .height(Length::FillPortion(2))
-                    .width(Length::Fill)
-                    .style(style::Container::Frame);
+        let close_btn = button("Close")
+            .padding(5)
+            .on_press(Message::CloseBatchSummary)
+            .style(style::Button::Primary);
 
-                let restore_action = match settings.device.disable_mode {
-                    true => "Enable/Restore",
-                    false => "Restore",
-                };
-                let remove_action = match settings.device.disable_mode {
-                    true => "Disable",
-                    false => "Uninstall",
-                };
+        let buttons = row![toggle_failures_btn, retry_btn, close_btn].spacing(10);
 
-                let apply_restore_selection = button(text(format!(
-                    "{} selection ({})",
-                    restore_action,
-                    self.selection.uninstalled + self.selection.disabled
-                )))
-                .on_press(Message::ApplyActionOnSelection(Action::Restore))
-                .padding(5)
-                .style(style::Button::Primary);
+        let dialog = container(
+            column![
+                counts,
+                scrollable(failures).height(if batch.show_failures {
+                    Length::Units(200)
+                } else {
+                    Length::Shrink
+                }),
+                buttons
+            ]
+            .spacing(20)
+            .align_items(Alignment::Center),
+        )
+        .padding(20)
+        .style(style::Container::Frame);
 
-                let apply_remove_selection = button(text(format!(
-                    "{} selection ({})",
-                    remove_action, self.selection.enabled
-                )))
-                .on_press(Message::ApplyActionOnSelection(Action::Remove))
-                .padding(5)
-                .style(style::Button::Primary);
+        container(dialog)
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .center_x()
+            .center_y()
+            .into()
+    }
 
-                let select_all_btn = button("Select all")
-                    .padding(5)
-                    .on_press(Message::ToggleAllSelected(true))
-                    .style(style::Button::Primary);
+    fn undo_toast_view(&self, toast: &UndoToast) -> Element<Message, Renderer<Theme>> {
+        let toast_text = match toast.packages.as_slice() {
+            [(i_package, u_index)] => {
+                let package = &self.phone_packages[*u_index][*i_package];
+                format!("{} {}", package.state, package.name)
+            }
+            packages => format!("Removed {} packages", packages.len()),
+        };
+        let undo_btn = button("Undo")
+            .padding(5)
+            .on_press(Message::UndoRemoval)
+            .style(style::Button::Primary);
+        let dismiss_btn = button("Dismiss")
+            .padding(5)
+            .on_press(Message::DismissToast)
+            .style(style::Button::Primary);
 
-                let unselect_all_btn = button("Unselect all")
-                    .padding(5)
-                    .on_press(Message::ToggleAllSelected(false))
-                    .style(style::Button::Primary);
+        container(
+            row![
+                text(toast_text),
+                Space::new(Length::Fill, Length::Shrink),
+                undo_btn,
+                dismiss_btn,
+            ]
+            .spacing(10)
+            .align_items(Alignment::Center),
+        )
+        .padding(10)
+        .width(Length::Fill)
+        .style(style::Container::BorderedFrame)
+        .into()
+    }
 
-                let action_row = row![
-                    select_all_btn,
-                    unselect_all_btn,
-                    Space::new(Length::Fill, Length::Shrink),
-                    apply_restore_selection,
-                    apply_remove_selection,
-                ]
-                .width(Length::Fill)
-                .spacing(10)
-                .align_items(Alignment::Center);
+    fn user_chips(&self, phone: &Phone, i_package: usize) -> Vec<UserChip> {
+        phone
+            .user_list
+            .iter()
+            .filter_map(|u| {
+                self.phone_packages
+                    .get(u.index)
+                    .and_then(|packages| packages.get(i_package))
+                    .map(|p| UserChip {
+                        user: *u,
+                        state: p.state,
+                        included: !self.user_exclusions.contains(&(i_package, u.index)),
+                    })
+            })
+            .collect()
+    }
 
-                let content = column![
-                    control_panel,
-                    packages_scrollable,
-                    description_panel,
-                    action_row,
-                ]
-                .width(Length::Fill)
-                .spacing(10)
-                .align_items(Alignment::Center);
+    /// Identifies the current filter combination
+    fn filter_key(&self) -> String {
+        let removals: Vec<&Removal> = Removal::ALL
+            .iter()
+            .filter(|r| self.selected_removals.contains(r))
+            .collect();
+        format!(
+            "{:?}|{:?}|{:?}|{:?}|{:?}|{}|{}|{}",
+            self.selected_list,
+            self.selected_package_state,
+            removals,
+            self.categories
+                .labels
+                .iter()
+                .filter(|l| self.selected_custom.contains(*l))
+                .collect::<Vec<_>>(),
+            self.selected_restorability,
+            self.only_session_changes,
+            self.show_allow_listed,
+            self.input_value
+        )
+    }
 
-                container(content).height(Length::Fill).padding(10).into()
+    /// Applies a change of filters and brings the list back where it was last left
+    /// with the new filters (or to the top if they weren't used yet).
+    fn change_filters(
+        &mut self,
+        general: &GeneralSettings,
+        change: impl FnOnce(&mut Self),
+    ) -> Command<Message> {
+        self.scroll_offsets
+            .insert(self.filter_key(), self.scroll_offset);
+        change(self);
+        self.filter_package_lists(general);
+        self.scroll_offset = self
+            .scroll_offsets
+            .get(&self.filter_key())
+            .copied()
+            .unwrap_or(0.0);
+        scrollable::snap_to(PACKAGES_SCROLLABLE_ID.clone(), self.scroll_offset)
+    }
+
+    /// Removal filter, custom categories replacing the removal of the UAD lists
+    fn matches_removal(&self, package: &PackageRow) -> bool {
+        if self.selected_removals.is_empty() && self.selected_custom.is_empty() {
+            return true;
+        }
+        match &package.custom_category {
+            Some(label) => self.selected_custom.contains(label),
+            None => self.selected_removals.contains(&package.removal),
+        }
+    }
+
+    /// Assigns (or unassigns) the current package to a custom category, on every device
+    fn set_custom_category(&mut self, label: Option<String>, general: &GeneralSettings) {
+        let i_user = self.selected_user.unwrap_or(User::default()).index;
+        let name = self.phone_packages[i_user][self.current_package_index]
+            .name
+            .clone();
+        match &label {
+            Some(label) => self.categories.packages.insert(name, label.clone()),
+            None => self.categories.packages.remove(&name),
+        };
+        if let Err(e) = save_categories(&self.categories) {
+            error!("[CATEGORIES] Could not save categories: {}", e);
+        }
+        for packages in &mut self.phone_packages {
+            if let Some(package) = packages.get_mut(self.current_package_index) {
+                package.custom_category = label.clone();
             }
         }
+        self.filter_package_lists(general);
     }
 
-    fn filter_package_lists(&mut self) {
+    fn filter_package_lists(&mut self, general: &GeneralSettings) {
         let list_filter: UadList = self.selected_list.unwrap();
         let package_filter: PackageState = self.selected_package_state.unwrap();
-        let removal_filter: Removal = self.selected_removal.unwrap();
+        // Explicitly asking for uninstalled packages overrides the setting
+        let hide_uninstalled =
+            general.hide_uninstalled && package_filter != PackageState::Uninstalled;
+        let mut hidden_uninstalled = 0;
+        let mut search_matches = HashMap::new();
 
         self.filtered_packages = self.phone_packages[self.selected_user.unwrap().index]
             .iter()
             .enumerate()
-            .filter(|(_, p)| {
-                (list_filter == UadList::All || p.uad_list == list_filter)
+            .filter(|(i, p)| {
+                let matches = (list_filter == UadList::All || p.uad_list == list_filter)
                     && (package_filter == PackageState::All || p.state == package_filter)
-                    && (removal_filter == Removal::All || p.removal == removal_filter)
-                    && (self.input_value.is_empty() || p.name.contains(&self.input_value))
+                    && self.matches_removal(p)
+                    && self.selected_restorability.matches(p)
+                    && (!self.only_session_changes || self.session_changes.contains(&p.name))
+                    && (self.show_allow_listed || !general.allow_listed_packages.contains(&p.name))
+                    && (self.input_value.is_empty()
+                        || match search_match(
+                            &self.input_value,
+                            p,
+                            self.notes.get(&p.name),
+                            &self.search_fields,
+                        ) {
+                            Some(SearchField::Name) => true,
+                            Some(field) => {
+                                search_matches.insert(*i, field);
+                                true
+                            }
+                            None => false,
+                        });
+                if matches && hide_uninstalled && p.state == PackageState::Uninstalled {
+                    hidden_uninstalled += 1;
+                    return false;
+                }
+                matches
             })
             .map(|(i, _)| i)
             .collect();
+        self.hidden_uninstalled = hidden_uninstalled;
+        self.search_matches = search_matches;
+
+        let packages = &self.phone_packages[self.selected_user.unwrap().index];
+        if self.sort_by_last_update {
+            // Most recently updated first. Dates are only known for viewed packages,
+            // the others stay at the end.
+            self.filtered_packages.sort_by(|a, b| {
+                let last_update =
+                    |i: &usize| packages[*i].timestamps.as_ref().map(|t| &t.last_update);
+                last_update(b).cmp(&last_update(a))
+            });
+        }
+
+        // Stable sort: pinned packages keep their relative order,
+        // and so do selected packages within both groups
+        self.filtered_packages.sort_by_key(|i| {
+            (
+                !general.pinned_packages.contains(&packages[*i].name),
+                self.selected_first && !self.selection.selected_packages.contains(i),
+            )
+        });
+
+        if let Some(focused) = self.focused_index {
+            if !self.filtered_packages.contains(&focused) {
+                self.focused_index = None;
+            }
+        }
+    }
+
+    /// Tracks the connected devices, their packages are pulled with the next UAD lists
+    pub fn set_devices(&mut self, devices: &[Phone]) {
+        self.device_loads = devices
+            .iter()
+            .map(|phone| (phone.clone(), DeviceLoad::Finding))
+            .collect();
     }
 
+    /// Names of the selected packages, in the order they were selected
+    pub fn selected_names(&self) -> Vec<String> {
+        let i_user = self.selected_user.unwrap_or(User::default()).index;
+        self.selection
+            .selected_packages
+            .iter()
+            .filter_map(|i| self.phone_packages.get(i_user)?.get(*i))
+            .map(|p| p.name.clone())
+            .collect()
+    }
+
+    pub fn device_load(&self, adb_id: &str) -> Option<&DeviceLoad> {
+        self.device_loads
+            .iter()
+            .find(|(phone, _)| phone.adb_id == adb_id)
+            .map(|(_, load)| load)
+    }
+
+    /// Fails if the device is still offline after a few retries: its package lists
+    /// would be empty otherwise
     async fn load_packages(
         uad_list: HashMap<String, Package>,
-        user_list: Vec<User>,
-    ) -> Vec<Vec<PackageRow>> {
+        phone: Phone,
+    ) -> Result<Vec<Vec<PackageRow>>, String> {
+        if !phone.adb_id.is_empty() {
+            wait_for_device(&phone.adb_id, CONNECT_WAIT)?;
+        }
         let mut phone_packages = vec![];
 
-        if user_list.len() <= 1 {
-            phone_packages.push(fetch_packages(&uad_list, None))
+        if phone.user_list.len() <= 1 {
+            phone_packages.push(fetch_packages(&uad_list, None, &phone.adb_id))
         } else {
             phone_packages.extend(
-                user_list
+                phone
+                    .user_list
                     .iter()
-                    .map(|user| fetch_packages(&uad_list, Some(user))),
+                    .map(|user| fetch_packages(&uad_list, Some(user), &phone.adb_id)),
             )
         };
-        phone_packages
+        // Users don't always have the same packages: indexes must match between users
+        Ok(align_user_packages(phone_packages))
     }
 
     async fn init_apps_view(
         remote: bool,
+        local_list: Option<PathBuf>,
+        expected_hash: Option<String>,
         phone: Phone,
-    ) -> (HashMap<String, Package>, UadListState) {
-        let (uad_lists, _) = load_debloat_lists(remote);
+    ) -> (HashMap<String, Package>, UadListState, Option<ListChanges>) {
+        // The download replaces the cached lists
+        let previous_lists = if remote && local_list.is_none() {
+            read_cached_lists()
+        } else {
+            None
+        };
+        let (uad_lists, _) =
+            load_debloat_lists(remote, local_list.as_deref(), expected_hash.as_deref());
         match uad_lists {
             Ok(list) => {
-                env::set_var("ANDROID_SERIAL", phone.adb_id.clone());
                 if phone.adb_id.is_empty() {
                     error!("AppsView ready but no phone found");
                 }
-                (list, UadListState::Done)
+                let changes = previous_lists
+                    .map(|previous| diff_lists(&previous, &list))
+                    .filter(|changes| !changes.is_empty());
+                (list, UadListState::Done, changes)
             }
             Err(local_list) => {
                 error!("Error loading remote debloat list for the phone. Fallback to embedded (and outdated) list");
-                (local_list, UadListState::Failed)
+                (local_list, UadListState::Failed, None)
             }
         }
     }
 }
 
+const MIN_DESCRIPTION_HEIGHT: f32 = 40.0;
+const MAX_DESCRIPTION_HEIGHT: f32 = 600.0;
+
+/// Window width (logical pixels) under which the filters and actions take less room
+const MINIMAL_UI_WIDTH: u32 = 900;
+
+/// Characters of a description shown before "Show more"
+const DESCRIPTION_PREVIEW_LEN: usize = 2000;
+
+/// Number of seconds the undo toast stays on screen after the last removal
+const TOAST_DURATION: u64 = 6;
+
+/// Milliseconds without typing before the search is applied
+const SEARCH_DEBOUNCE_DELAY: u64 = 150;
+
+/// Number of seconds between two checks of the selection to keep in the recovery file
+const RECOVERY_SAVE_INTERVAL: u64 = 5;
+
+/// Packages queried per `DetailsLoaded` message while the list is in use
+const DETAILS_CHUNK_SIZE: usize = 20;
+
+fn build_package_jobs(
+    actions: Vec<(Option<usize>, String)>,
+    i_package: usize,
+    name: &str,
+    removal: Removal,
+) -> Vec<Job> {
+    let mut affected_users = HashSet::new();
+    actions
+        .into_iter()
+        .map(|(i_user, action)| Job {
+            package: name.to_string(),
+            info: PackageInfo {
+                i_user,
+                index: i_package,
+                removal: removal.to_string(),
+                force_disable: false,
+                suspend: None,
+                uninstalled_instead: false,
+            },
+            action,
+            // Only the first command of each user can change the package state
+            change_state: affected_users.insert(i_user),
+        })
+        .collect()
+}
+
+/// Adds a force-stop after the commands of each affected user. It comes after the
+/// command that changes the package state, so it doesn't change it (see `build_package_jobs`).
+fn with_force_stop(
+    mut actions: Vec<(Option<usize>, String)>,
+    package: &str,
+    users: &[User],
+) -> Vec<(Option<usize>, String)> {
+    let mut affected_users = vec![];
+    for (i_user, _) in &actions {
+        if !affected_users.contains(i_user) {
+            affected_users.push(*i_user);
+        }
+    }
+    for i_user in affected_users {
+        match i_user.and_then(|i| users.iter().find(|u| u.index == i)) {
+            Some(user) => actions.extend(request_builder(vec!["am force-stop"], package, &[*user])),
+            None => actions.extend(request_builder(vec!["am force-stop"], package, &[])),
+        }
+    }
+    actions
+}
+
+fn build_package_commands(
+    actions: Vec<(Option<usize>, String)>,
+    i_package: usize,
+    removal: Removal,
+    serial: &str,
+) -> Vec<Command<Message>> {
+    job_commands(build_package_jobs(actions, i_package, "", removal), serial)
+}
+
+/// Each job as a command of its own, outside of a bulk action
+fn job_commands(jobs: Vec<Job>, serial: &str) -> Vec<Command<Message>> {
+    jobs.into_iter()
+        .map(|job| {
+            Command::perform(
+                perform_adb_commands(
+                    job.action,
+                    CommandType::PackageManager(job.info),
+                    serial.to_string(),
+                ),
+                if job.change_state {
+                    Message::ChangePackageState
+                } else {
+                    |_| Message::Nothing
+                },
+            )
+        })
+        .collect()
+}
+
+/// An empty set means any removal
+fn default_removals(general: &GeneralSettings) -> HashSet<Removal> {
+    if general.safe_mode {
+        return HashSet::from([Removal::Recommended]);
+    }
+    match general.default_removal {
+        Removal::All => HashSet::new(),
+        removal => HashSet::from([removal]),
+    }
+}
+
+/// Commands sent to a locked device can fail without any error
+fn paused_users_warning(users: &[User]) -> Option<String> {
+    let paused: Vec<String> = users
+        .iter()
+        .filter(|u| u.paused)
+        .map(|u| u.id.to_string())
+        .collect();
+    match paused.len() {
+        0 => None,
+        _ => Some(format!(
+            "User {} is paused (e.g. a work profile turned off): turn it on or start it \
+            first, its actions may fail otherwise",
+            paused.join(", ")
+        )),
+    }
+}
+
+fn device_lock_warning(serial: &str) -> Option<String> {
+    match get_device_lock(serial) {
+        DeviceLock::Unlocked => None,
+        DeviceLock::LockScreen => Some(
+            "Your device is locked: unlock it first, some actions may fail otherwise".to_string(),
+        ),
+        DeviceLock::NotUnlockedSinceBoot => Some(
+            "Your device hasn't been unlocked since it booted: unlock it first, \
+            actions will likely fail otherwise"
+                .to_string(),
+        ),
+    }
+}
+
+/// Description of a package, with clickable links.
+/// Lines with links don't wrap: iced can't mix text and buttons in a paragraph.
+/// Long descriptions are cut (see `DESCRIPTION_PREVIEW_LEN`) unless `expanded`:
+/// laying out a huge text makes selecting the package sluggish
+fn description_view(description: &str, expanded: bool) -> Element<Message, Renderer<Theme>> {
+    let truncated = truncate_description(description);
+    let shown = match truncated {
+        Some(start) if !expanded => start,
+        _ => description,
+    };
+    let col = parse_description(shown)
+        .into_iter()
+        .fold(column![].spacing(2), |col, spans| {
+            let has_links = spans
+                .iter()
+                .any(|s| matches!(s, DescriptionSpan::Link { .. }));
+            if !has_links {
+                let line: String = spans
+                    .into_iter()
+                    .map(|s| match s {
+                        DescriptionSpan::Text(t) => t,
+                        DescriptionSpan::Link { label, .. } => label,
+                    })
+                    .collect();
+                return col.push(text(line));
+            }
+            col.push(spans.into_iter().fold(row![], |row, span| {
+                match span {
+                    DescriptionSpan::Text(t) => row.push(text(t)),
+                    DescriptionSpan::Link { label, url } => row.push(
+                        button(text(label))
+                            .padding(0)
+                            .on_press(Message::DescriptionLinkPressed(url))
+                            .style(style::Button::Link),
+                    ),
+                }
+            }))
+        });
+
+    let expander_label = match truncated {
+        None => return col.into(),
+        Some(_) if expanded => "Show less".to_string(),
+        Some(start) => format!(
+            "Show more ({} more characters)",
+            description[start.len()..].chars().count()
+        ),
+    };
+    col.push(
+        button(text(expander_label).size(13))
+            .padding([2, 6])
+            .on_press(Message::ToggleDescriptionExpanded)
+            .style(style::Button::Primary),
+    )
+    .into()
+}
+
+/// Start of a description longer than `DESCRIPTION_PREVIEW_LEN`, cut at the end of
+/// a line when that doesn't shorten it too much
+fn truncate_description(description: &str) -> Option<&str> {
+    let (end, _) = description.char_indices().nth(DESCRIPTION_PREVIEW_LEN)?;
+    let start = &description[..end];
+    Some(match start.rfind('\n') {
+        Some(newline) if newline > end / 2 => &start[..newline],
+        _ => start,
+    })
+}
+
+fn battery_warning(serial: &str, threshold: u8) -> Option<String> {
+    match get_battery(serial) {
+        Some(battery) if !battery.charging && battery.level < threshold => Some(format!(
+            "The battery of your device is at {}%: plug it in first, a device shutting down \
+            in the middle of a bulk action is left half debloated",
+            battery.level
+        )),
+        _ => None,
+    }
+}
+
+fn no_device_view<'a>(unauthorized: bool) -> Element<'a, Message, Renderer<Theme>> {
+    let (title, hint) = if unauthorized {
+        (
+            "Your device is not authorized",
+            "Unlock your phone and accept the \"Allow USB debugging\" prompt.\n\
+            If it doesn't show up, unplug and replug the USB cable.",
+        )
+    } else {
+        (
+            "No device found",
+            "Make sure USB debugging is enabled in the developer options of your phone \
+            and that the USB cable supports data transfer.",
+        )
+    };
+
+    let retry_btn = button("Retry")
+        .padding(5)
+        .on_press(Message::RetryDeviceScan)
+        .style(style::Button::Primary);
+
+    let restart_adb_btn = button("Restart adb")
+        .padding(5)
+        .on_press(Message::RestartAdb)
+        .style(style::Button::Primary);
+
+    let col = column![
+        text(title).size(20),
+        text(hint)
+            .style(style::Text::Commentary)
+            .horizontal_alignment(alignment::Horizontal::Center),
+        row![retry_btn, restart_adb_btn].spacing(10),
+    ]
+    .spacing(10)
+    .max_width(600)
+    .align_items(Alignment::Center);
+
+    container(col)
+        .width(Length::Fill)
+        .height(Length::Fill)
+        .center_y()
+        .center_x()
+        .into()
+}
+
+fn load_failed_view(err: &str) -> Element<Message, Renderer<Theme>> {
+    let retry_btn = button("Retry")
+        .padding(5)
+        .on_press(Message::RetryDeviceScan)
+        .style(style::Button::Primary);
+
+    let restart_adb_btn = button("Restart adb")
+        .padding(5)
+        .on_press(Message::RestartAdb)
+        .style(style::Button::Primary);
+
+    let col = column![
+        text("Could not load the packages of your device").size(20),
+        text(format!(
+            "The device didn't answer in time ({}).\n\
+            Unlock it, check the USB cable and try again.",
+            err
+        ))
+        .style(style::Text::Commentary)
+        .horizontal_alignment(alignment::Horizontal::Center),
+        row![retry_btn, restart_adb_btn].spacing(10),
+    ]
+    .spacing(10)
+    .max_width(600)
+    .align_items(Alignment::Center);
+
+    container(col)
+        .width(Length::Fill)
+        .height(Length::Fill)
+        .center_y()
+        .center_x()
+        .into()
+}
+
 fn waiting_view<'a>(
     _settings: &Settings,
     displayed_text: &str,
@@ -586,3 +6960,320 @@ fn waiting_view<'a>(
         .style(style::Container::default())
         .into()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings() -> Settings {
+        Settings {
+            general: GeneralSettings::default(),
+            device: DeviceSettings::default(),
+            adb_report: None,
+            orphaned_data: None,
+            profiles: vec![],
+            data_dir_input: String::new(),
+            data_dir_error: None,
+            status_path_input: String::new(),
+        }
+    }
+
+    fn package(name: &str) -> PackageRow {
+        PackageRow::new(
+            name,
+            PackageState::Enabled,
+            "",
+            UadList::Google,
+            Removal::Recommended,
+            false,
+            false,
+        )
+    }
+
+    /// Two users with the same three packages, all shown
+    fn list() -> List {
+        let packages = vec![package("com.a"), package("com.b"), package("com.c")];
+        List {
+            phone_packages: vec![packages.clone(), packages],
+            filtered_packages: vec![0, 1, 2],
+            selected_list: Some(UadList::All),
+            selected_package_state: Some(PackageState::All),
+            selected_user: Some(User::default()),
+            ..List::default()
+        }
+    }
+
+    fn send(list: &mut List, message: Message) {
+        send_with(list, &mut settings(), message);
+    }
+
+    fn send_with(list: &mut List, settings: &mut Settings, message: Message) {
+        let _ = list.update(
+            settings,
+            &mut Phone::default(),
+            &mut UadListState::default(),
+            message,
+        );
+    }
+
+    fn toggle(list: &mut List, i_package: usize, selected: bool) {
+        send(
+            list,
+            Message::List(i_package, RowMessage::ToggleSelection(selected)),
+        );
+    }
+
+    /// The selection matches the `selected` flags of the rows of the current user
+    fn assert_consistent(list: &List) {
+        let i_user = list.selected_user.unwrap().index;
+        let flagged: Vec<usize> = (0..list.phone_packages[i_user].len())
+            .filter(|i| list.phone_packages[i_user][*i].selected)
+            .collect();
+        let mut selected = list.selection.selected_packages.clone();
+        selected.sort_unstable();
+        // Also rules out duplicates: every index is flagged once
+        assert_eq!(selected, flagged);
+    }
+
+    #[test]
+    fn test_toggling_the_same_package_repeatedly() {
+        let mut list = list();
+        for _ in 0..3 {
+            toggle(&mut list, 1, true);
+        }
+        assert_eq!(list.selection.selected_packages, vec![1]);
+        assert_consistent(&list);
+
+        toggle(&mut list, 1, false);
+        toggle(&mut list, 1, false);
+        assert!(list.selection.selected_packages.is_empty());
+        assert_consistent(&list);
+    }
+
+    #[test]
+    fn test_toggle_all_after_single_toggles() {
+        let mut list = list();
+        toggle(&mut list, 0, true);
+        toggle(&mut list, 2, true);
+        send(&mut list, Message::ToggleAllSelected(true));
+        send(&mut list, Message::ToggleAllSelected(true));
+        assert_eq!(list.selection.selected_packages.len(), 3);
+        assert_eq!(list.selection_counts(&settings()).to_uninstall, 3);
+        assert_consistent(&list);
+
+        toggle(&mut list, 0, false);
+        assert_eq!(list.selection.selected_packages.len(), 2);
+        assert_consistent(&list);
+
+        send(&mut list, Message::ToggleAllSelected(false));
+        assert!(list.selection.selected_packages.is_empty());
+        assert_consistent(&list);
+    }
+
+    #[test]
+    fn test_unknown_packages_are_never_selected() {
+        let mut list = list();
+        list.phone_packages[0][1].state = PackageState::Unknown;
+        toggle(&mut list, 1, true);
+        send(&mut list, Message::ToggleAllSelected(true));
+        assert_eq!(list.selection.selected_packages.len(), 2);
+        assert!(!list.phone_packages[0][1].selected);
+        assert_consistent(&list);
+    }
+
+    #[test]
+    fn test_selecting_again_after_switching_users() {
+        let mut list = list();
+        toggle(&mut list, 1, true);
+        let user = User {
+            index: 1,
+            ..User::default()
+        };
+        send(&mut list, Message::UserSelected(user));
+        // The row of the other user was never flagged
+        toggle(&mut list, 1, true);
+        send(&mut list, Message::ToggleAllSelected(true));
+        assert_eq!(list.selection.selected_packages.len(), 3);
+        assert_consistent(&list);
+    }
+
+    #[test]
+    fn test_switching_to_a_user_missing_a_selected_package() {
+        let mut list = list();
+        list.phone_packages[1][2].state = PackageState::Uninstalled;
+        toggle(&mut list, 0, true);
+        toggle(&mut list, 2, true);
+        let user = User {
+            index: 1,
+            ..User::default()
+        };
+        send(&mut list, Message::UserSelected(user));
+        assert_eq!(list.missing_for_user, vec!["com.c".to_string()]);
+        // Not silently dropped
+        assert_eq!(list.selection.selected_packages.len(), 2);
+        assert_consistent(&list);
+
+        send(&mut list, Message::UserSelected(User::default()));
+        assert!(list.missing_for_user.is_empty());
+    }
+
+    #[test]
+    fn test_selection_after_a_successful_action() {
+        // Restorations: removals would write the debloat history
+        let restored = || {
+            Message::ChangePackageState(Ok(CommandType::PackageManager(PackageInfo {
+                i_user: None,
+                index: 1,
+                removal: Removal::Recommended.to_string(),
+                force_disable: false,
+                suspend: None,
+                uninstalled_instead: false,
+            })))
+        };
+        let mut settings = settings();
+        for keep in [false, true] {
+            let mut list = list();
+            list.phone_packages[0][1].state = PackageState::Uninstalled;
+            toggle(&mut list, 1, true);
+            settings.general.keep_selection_after_action = keep;
+            send_with(&mut list, &mut settings, restored());
+            assert_eq!(list.phone_packages[0][1].state, PackageState::Enabled);
+            assert_eq!(list.selection.selected_packages.len(), usize::from(keep));
+            assert_eq!(
+                list.selection_counts(&settings).to_uninstall,
+                usize::from(keep)
+            );
+            assert_consistent(&list);
+        }
+    }
+
+    #[test]
+    fn test_force_stop_does_not_change_the_state() {
+        let users = [
+            User::default(),
+            User {
+                id: 10,
+                index: 1,
+                ..User::default()
+            },
+        ];
+        let actions = vec![
+            (Some(0), "pm enable --user 0 com.a".to_string()),
+            (Some(1), "pm enable --user 10 com.a".to_string()),
+        ];
+        let jobs = build_package_jobs(
+            with_force_stop(actions, "com.a", &users),
+            0,
+            "com.a",
+            Removal::Recommended,
+        );
+        let commands: Vec<(&str, bool)> = jobs
+            .iter()
+            .map(|job| (job.action.as_str(), job.change_state))
+            .collect();
+        assert_eq!(
+            commands,
+            vec![
+                ("pm enable --user 0 com.a", true),
+                ("pm enable --user 10 com.a", true),
+                ("am force-stop --user 0 com.a", false),
+                ("am force-stop --user 10 com.a", false),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_collapsed_actions_follow_the_state_of_each_user() {
+        let mut settings = settings();
+        settings.device.multi_user_mode = true;
+        settings.device.collapse_multi_user = true;
+        let phone = Phone {
+            android_sdk: 30,
+            user_list: vec![
+                User::default(),
+                User {
+                    id: 10,
+                    index: 1,
+                    ..User::default()
+                },
+            ],
+            ..Phone::default()
+        };
+        let mut list = list();
+        list.phone_packages[1][0].state = PackageState::Uninstalled;
+
+        let actions = list.package_actions(0, &settings, &phone);
+        assert_eq!(
+            actions,
+            vec![(Some(0), "pm uninstall --user 0 com.a".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_selected_packages_move_up() {
+        let mut list = list();
+        send(&mut list, Message::SelectedFirstToggled(true));
+        toggle(&mut list, 2, true);
+        assert_eq!(list.filtered_packages, vec![2, 0, 1]);
+        toggle(&mut list, 1, true);
+        assert_eq!(list.filtered_packages, vec![1, 2, 0]);
+        toggle(&mut list, 2, false);
+        assert_eq!(list.filtered_packages, vec![1, 0, 2]);
+    }
+
+    #[test]
+    fn test_search_match() {
+        let mut package = package("com.example.gallery");
+        package.description = "Photo viewer of the OEM".to_string();
+        let note = "Breaks the CAMERA app".to_string();
+        let all = HashSet::from(SearchField::OPTIONAL);
+
+        assert_eq!(
+            search_match("gallery", &package, None, &HashSet::new()),
+            Some(SearchField::Name)
+        );
+        // Name only by default
+        assert_eq!(
+            search_match("photo", &package, Some(&note), &HashSet::new()),
+            None
+        );
+        assert_eq!(
+            search_match("photo", &package, Some(&note), &all),
+            Some(SearchField::Description)
+        );
+        assert_eq!(
+            search_match("camera", &package, Some(&note), &all),
+            Some(SearchField::Note)
+        );
+        assert_eq!(search_match("camera", &package, None, &all), None);
+    }
+
+    #[test]
+    fn test_sync_changes() {
+        let target = vec![package("com.a"), package("com.b"), package("com.c")];
+        let mut source = vec![package("com.b"), package("com.c"), package("com.d")];
+        source[0].state = PackageState::Uninstalled;
+        source[1].state = PackageState::Unknown;
+        // com.a is not on the source device and com.d not on the target
+        assert_eq!(
+            sync_changes(&source, &target),
+            vec![(1, PackageState::Uninstalled)]
+        );
+    }
+
+    #[test]
+    fn test_baseline_report() {
+        let mut packages = vec![package("com.a"), package("com.b"), package("com.c")];
+        packages.push(PackageRow {
+            removal: Removal::Advanced,
+            ..package("com.d")
+        });
+        packages[0].state = PackageState::Uninstalled;
+        packages[2].state = PackageState::Disabled;
+
+        let report = BaselineReport::new(&packages);
+        assert_eq!(report.remaining, vec![1]);
+        assert_eq!(report.summary(), "66% of recommended bloat removed (2/3)");
+        assert_eq!(BaselineReport::new(&packages[3..]).score(), 100);
+    }
+}
@@ -1,3 +1,5 @@
 pub mod about;
 pub mod list;
+pub mod palette;
 pub mod settings;
+pub mod wireless;
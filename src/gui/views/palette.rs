@@ -0,0 +1,185 @@
+use crate::core::theme::Theme;
+use crate::gui::style;
+use iced::widget::{button, column, container, scrollable, text, text_input};
+use iced::{Command, Element, Length, Renderer};
+use static_init::dynamic;
+
+#[dynamic]
+static INPUT_ID: text_input::Id = text_input::Id::new("palette");
+
+/// An action of the command palette, found by typing part of its label
+#[derive(Debug, Clone)]
+pub struct Entry<M> {
+    pub label: String,
+    pub message: M,
+}
+
+impl<M> Entry<M> {
+    pub fn new(label: impl Into<String>, message: M) -> Self {
+        Self {
+            label: label.into(),
+            message,
+        }
+    }
+}
+
+/// Command palette (Ctrl+K). The entries are given by `UadGui` each time they are needed,
+/// so they always match the current device and view.
+#[derive(Default, Debug, Clone)]
+pub struct Palette {
+    open: bool,
+    query: String,
+    highlight: usize, // position in the matching entries
+}
+
+#[derive(Debug, Clone)]
+pub enum Message {
+    QueryChanged(String),
+    Up,
+    Down,
+    Submitted,
+    EntryPressed(usize), // entries index
+    Close,
+}
+
+impl Palette {
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    pub fn open(&mut self) -> Command<Message> {
+        self.open = true;
+        self.query.clear();
+        self.highlight = 0;
+        text_input::focus(INPUT_ID.clone())
+    }
+
+    /// Returns the message of the picked entry, if any. The palette closes once one is picked.
+    pub fn update<M: Clone>(&mut self, msg: Message, entries: &[Entry<M>]) -> Option<M> {
+        let matching = matching_entries(entries, &self.query);
+        let picked = match msg {
+            Message::QueryChanged(query) => {
+                self.query = query;
+                self.highlight = 0;
+                None
+            }
+            Message::Up => {
+                self.highlight = self.highlight.saturating_sub(1);
+                None
+            }
+            Message::Down => {
+                self.highlight = (self.highlight + 1).min(matching.len().saturating_sub(1));
+                None
+            }
+            Message::Submitted => matching.get(self.highlight).copied(),
+            Message::EntryPressed(i) => Some(i),
+            Message::Close => {
+                self.open = false;
+                None
+            }
+        };
+        picked.and_then(|i| {
+            self.open = false;
+            entries.get(i).map(|entry| entry.message.clone())
+        })
+    }
+
+    pub fn view<M>(&self, entries: &[Entry<M>]) -> Element<Message, Renderer<Theme>> {
+        let matching = matching_entries(entries, &self.query);
+
+        let input = text_input("Type a command", &self.query, Message::QueryChanged)
+            .id(INPUT_ID.clone())
+            .on_submit(Message::Submitted)
+            .padding(6);
+
+        let list = matching
+            .iter()
+            .enumerate()
+            .fold(column![].spacing(2), |col, (pos, i)| {
+                col.push(
+                    button(text(entries[*i].label.clone()))
+                        .width(Length::Fill)
+                        .padding(5)
+                        .on_press(Message::EntryPressed(*i))
+                        .style(if pos == self.highlight {
+                            style::Button::SelectedPackage
+                        } else {
+                            style::Button::NormalPackage
+                        }),
+                )
+            });
+
+        let dialog = container(
+            column![
+                input,
+                if matching.is_empty() {
+                    Element::from(text("No matching command").style(style::Text::Commentary))
+                } else {
+                    scrollable(list).height(Length::Units(350)).into()
+                },
+                text("↑↓ to choose, Enter to run, Esc to close")
+                    .style(style::Text::Commentary)
+                    .size(14),
+            ]
+            .spacing(10),
+        )
+        .padding(20)
+        .max_width(600)
+        .style(style::Container::Frame);
+
+        container(dialog)
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .center_x()
+            .padding(60)
+            .into()
+    }
+}
+
+/// Indexes of the entries whose label contains every word of the query, whatever the case
+fn matching_entries<M>(entries: &[Entry<M>], query: &str) -> Vec<usize> {
+    let query = query.to_lowercase();
+    entries
+        .iter()
+        .enumerate()
+        .filter(|(_, entry)| {
+            let label = entry.label.to_lowercase();
+            query.split_whitespace().all(|word| label.contains(word))
+        })
+        .map(|(i, _)| i)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries() -> Vec<Entry<u8>> {
+        vec![
+            Entry::new("Select all recommended", 0),
+            Entry::new("Export selection", 1),
+            Entry::new("Switch device: Pixel 6", 2),
+        ]
+    }
+
+    #[test]
+    fn test_matching_entries() {
+        assert_eq!(matching_entries(&entries(), ""), vec![0, 1, 2]);
+        assert_eq!(matching_entries(&entries(), "SEL"), vec![0, 1]);
+        assert_eq!(matching_entries(&entries(), "sel exp"), vec![1]);
+        assert!(matching_entries(&entries(), "reboot").is_empty());
+    }
+
+    #[test]
+    fn test_submit_picks_the_highlighted_entry() {
+        let entries = entries();
+        let mut palette = Palette::default();
+        let _ = palette.open();
+        palette.update(Message::QueryChanged("sel".to_string()), &entries);
+        palette.update(Message::Down, &entries);
+        palette.update(Message::Down, &entries); // stays on the last match
+
+        assert_eq!(palette.update(Message::Submitted, &entries), Some(1));
+        assert!(!palette.is_open());
+    }
+}
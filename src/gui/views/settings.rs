@@ -1,15 +1,27 @@
-use crate::core::config::{BackupSettings, Config, DeviceSettings, GeneralSettings};
+use crate::core::config::{
+    BackupSettings, Config, DeviceSettings, FilterControl, GeneralSettings, ListDensity, LogLevel,
+};
+use crate::core::profiles::list_profiles;
 use crate::core::save::{
-    backup_phone, list_available_backup_user, list_available_backups, restore_backup, BACKUP_DIR,
+    backup_phone, data_dir, list_available_backup_user, list_available_backups, restore_backup,
+    BACKUP_DIR, DATA_DIR,
+};
+use crate::core::sync::{
+    delete_orphaned_dirs, find_orphaned_dirs, perform_adb_commands, test_adb, AdbError,
+    CommandType, OrphanedDir, Phone, RootEnvironment,
 };
-use crate::core::sync::{perform_adb_commands, CommandType, Phone};
 use crate::core::theme::Theme;
-use crate::core::utils::{open_url, string_to_theme, DisplayablePath};
+use crate::core::uad_lists::{PackageState, Removal, UadList};
+use crate::core::utils::{
+    check_writable_dir, format_bytes, format_count, open_url, string_to_theme, DisplayablePath,
+};
 use crate::gui::style;
 use crate::gui::views::list::PackageInfo;
 use crate::gui::widgets::package_row::PackageRow;
 
-use iced::widget::{button, checkbox, column, container, pick_list, radio, row, text, Space};
+use iced::widget::{
+    button, checkbox, column, container, pick_list, radio, row, text, text_input, Column, Space,
+};
 use iced::{alignment, Alignment, Command, Element, Length, Renderer};
 use std::path::PathBuf;
 
@@ -17,13 +29,35 @@ use std::path::PathBuf;
 pub struct Settings {
     pub general: GeneralSettings,
     pub device: DeviceSettings,
+    pub adb_report: Option<String>, // outcome of the last adb test
+    pub orphaned_data: Option<OrphanedData>,
+    pub profiles: Vec<String>, // saved profiles, listed again when the settings are opened
+    pub data_dir_input: String, // saved once it is applied and found writable
+    pub data_dir_error: Option<String>,
+    pub status_path_input: String, // saved once it is applied
+}
+
+/// App data directories left behind by uninstalled packages (rooted devices only)
+#[derive(Debug, Clone, Default)]
+pub struct OrphanedData {
+    pub dirs: Vec<(OrphanedDir, bool)>, // directory, selected for deletion
+    pub confirming: bool,
+    pub report: Option<String>,
 }
 
 impl Default for Settings {
     fn default() -> Self {
+        let general = Config::load_configuration_file().general;
+        general.apply_logging();
         Self {
-            general: Config::load_configuration_file().general,
+            data_dir_input: general.data_dir.clone(),
+            status_path_input: general.status_path.clone(),
+            general,
             device: DeviceSettings::default(),
+            adb_report: None,
+            orphaned_data: None,
+            profiles: list_profiles(),
+            data_dir_error: None,
         }
     }
 }
@@ -32,14 +66,57 @@ impl Default for Settings {
 pub enum Message {
     LoadDeviceSettings,
     ExpertMode(bool),
+    SafeMode(bool),
+    HideUninstalled(bool),
+    AutoSelectNewRecommended(bool),
+    ConnectProfileSelected(String), // none if empty
+    AutoApplyProfile(bool),
+    RebootAfterBatch(bool),
+    VerifyPackageState(bool),
+    VerifyBatch(bool),
+    HoverPreview(bool),
+    TestAdb,
+    FindOrphanedDirs,
+    OrphanedDirsFound(Result<Vec<OrphanedDir>, String>),
+    OrphanedDirToggled(usize, bool),
+    DeleteOrphanedDirs,
+    ConfirmDeleteOrphanedDirs,
+    CancelDeleteOrphanedDirs,
+    OrphanedDirsDeleted(Result<usize, String>),
+    AdbTested(String),
+    AlwaysOnTop(bool),
+    KeepSelectionAfterAction(bool),
+    ConfirmDeviceSwitch(bool),
+    CountDisabledAsUninstalled(bool),
+    LogLevelSelected(LogLevel),
+    LogAdbCommands(bool),
+    ConfirmThresholdChanged(String),
+    BatteryThresholdChanged(String),
+    DataDirChanged(String),
+    ApplyDataDir,
+    ReportPathChanged(String),
+    StatusSink(bool),
+    StatusPathChanged(String),
+    ApplyStatusPath,
+    LocalListPathChanged(String),
+    ListSha256Changed(String),
+    DefaultPackageStateSelected(PackageState),
+    DefaultListSelected(UadList),
+    DefaultRemovalSelected(Removal),
+    ListDensitySelected(ListDensity),
+    FilterControlToggled(FilterControl, bool),
+    MoveFilterControlUp(FilterControl),
+    MoveFilterControlDown(FilterControl),
     DisableMode(bool),
+    DisableRemoval(Removal, bool),
     MultiUserMode(bool),
+    CollapseMultiUser(bool),
     ApplyTheme(Theme),
     UrlPressed(PathBuf),
     BackupSelected(DisplayablePath),
     BackupDevice,
     RestoreDevice,
-    RestoringDevice(Result<CommandType, ()>),
+    RestoringDevice(Result<CommandType, AdbError>),
     DeviceBackedUp(Result<(), String>),
 }
 
@@ -53,11 +130,291 @@ impl Settings {
     ) -> Command<Message> {
         match msg {
             Message::ExpertMode(toggled) => {
+                if self.general.safe_mode {
+                    return Command::none();
+                }
                 self.general.expert_mode = toggled;
                 debug!("Config change: {:?}", self);
                 Config::save_changes(self, &phone.adb_id);
                 Command::none()
             }
+            Message::SafeMode(toggled) => {
+                self.general.safe_mode = toggled;
+                if toggled {
+                    self.general.expert_mode = false;
+                }
+                debug!("Config change: {:?}", self);
+                Config::save_changes(self, &phone.adb_id);
+                Command::none()
+            }
+            Message::RebootAfterBatch(toggled) => {
+                self.general.reboot_after_batch = toggled;
+                debug!("Config change: {:?}", self);
+                Config::save_changes(self, &phone.adb_id);
+                Command::none()
+            }
+            Message::VerifyPackageState(toggled) => {
+                self.general.verify_package_state = toggled;
+                debug!("Config change: {:?}", self);
+                Config::save_changes(self, &phone.adb_id);
+                Command::none()
+            }
+            Message::VerifyBatch(toggled) => {
+                self.general.verify_batch = toggled;
+                debug!("Config change: {:?}", self);
+                Config::save_changes(self, &phone.adb_id);
+                Command::none()
+            }
+            Message::HoverPreview(toggled) => {
+                self.general.hover_preview = toggled;
+                debug!("Config change: {:?}", self);
+                Config::save_changes(self, &phone.adb_id);
+                Command::none()
+            }
+            Message::TestAdb => {
+                self.adb_report = Some("Testing adb...".to_string());
+                Command::perform(test_adb(), Message::AdbTested)
+            }
+            Message::AdbTested(report) => {
+                self.adb_report = Some(report);
+                Command::none()
+            }
+            Message::FindOrphanedDirs => {
+                if phone.root == RootEnvironment::None {
+                    return Command::none();
+                }
+                self.orphaned_data = Some(OrphanedData {
+                    report: Some("Looking for orphaned data...".to_string()),
+                    ..OrphanedData::default()
+                });
+                Command::perform(
+                    find_orphaned_dirs(phone.adb_id.clone()),
+                    Message::OrphanedDirsFound,
+                )
+            }
+            Message::OrphanedDirsFound(result) => {
+                let data = self.orphaned_data.get_or_insert_with(OrphanedData::default);
+                match result {
+                    Ok(dirs) => {
+                        data.report = dirs
+                            .is_empty()
+                            .then(|| "No orphaned data directory".to_string());
+                        data.dirs = dirs.into_iter().map(|d| (d, false)).collect();
+                    }
+                    Err(e) => {
+                        error!("[ORPHANED DATA] {}", e);
+                        data.report = Some(format!("Could not list the data directories: {}", e));
+                    }
+                }
+                Command::none()
+            }
+            Message::OrphanedDirToggled(i, selected) => {
+                if let Some((_, s)) = self.orphaned_data.as_mut().and_then(|d| d.dirs.get_mut(i)) {
+                    *s = selected;
+                }
+                Command::none()
+            }
+            Message::DeleteOrphanedDirs => {
+                if let Some(data) = &mut self.orphaned_data {
+                    data.confirming = data.dirs.iter().any(|(_, selected)| *selected);
+                }
+                Command::none()
+            }
+            Message::CancelDeleteOrphanedDirs => {
+                if let Some(data) = &mut self.orphaned_data {
+                    data.confirming = false;
+                }
+                Command::none()
+            }
+            Message::ConfirmDeleteOrphanedDirs => {
+                let data = match &mut self.orphaned_data {
+                    Some(data) if data.confirming && phone.root != RootEnvironment::None => data,
+                    _ => return Command::none(),
+                };
+                data.confirming = false;
+                let paths = data
+                    .dirs
+                    .iter()
+                    .filter(|(_, selected)| *selected)
+                    .map(|(d, _)| d.path.clone())
+                    .collect();
+                Command::perform(
+                    delete_orphaned_dirs(phone.adb_id.clone(), paths),
+                    Message::OrphanedDirsDeleted,
+                )
+            }
+            Message::OrphanedDirsDeleted(result) => {
+                let report = match result {
+                    Ok(count) => format!("{} directories deleted", count),
+                    Err(e) => {
+                        error!("[ORPHANED DATA] {}", e);
+                        format!("Deletion failed: {}", e)
+                    }
+                };
+                self.orphaned_data = Some(OrphanedData {
+                    report: Some(report),
+                    ..OrphanedData::default()
+                });
+                // What's left
+                Command::perform(
+                    find_orphaned_dirs(phone.adb_id.clone()),
+                    Message::OrphanedDirsFound,
+                )
+            }
+            Message::KeepSelectionAfterAction(toggled) => {
+                self.general.keep_selection_after_action = toggled;
+                debug!("Config change: {:?}", self);
+                Config::save_changes(self, &phone.adb_id);
+                Command::none()
+            }
+            Message::ConfirmDeviceSwitch(toggled) => {
+                self.general.confirm_device_switch = toggled;
+                debug!("Config change: {:?}", self);
+                Config::save_changes(self, &phone.adb_id);
+                Command::none()
+            }
+            Message::AlwaysOnTop(toggled) => {
+                self.general.always_on_top = toggled;
+                debug!("Config change: {:?}", self);
+                Config::save_changes(self, &phone.adb_id);
+                Command::none()
+            }
+            Message::AutoSelectNewRecommended(toggled) => {
+                self.general.auto_select_new_recommended = toggled;
+                debug!("Config change: {:?}", self);
+                Config::save_changes(self, &phone.adb_id);
+                Command::none()
+            }
+            Message::ConnectProfileSelected(name) => {
+                self.general.connect_profile = name;
+                debug!("Config change: {:?}", self);
+                Config::save_changes(self, &phone.adb_id);
+                Command::none()
+            }
+            Message::AutoApplyProfile(toggled) => {
+                self.general.auto_apply_profile = toggled;
+                debug!("Config change: {:?}", self);
+                Config::save_changes(self, &phone.adb_id);
+                Command::none()
+            }
+            Message::HideUninstalled(toggled) => {
+                self.general.hide_uninstalled = toggled;
+                debug!("Config change: {:?}", self);
+                Config::save_changes(self, &phone.adb_id);
+                Command::none()
+            }
+            Message::CountDisabledAsUninstalled(toggled) => {
+                self.general.count_disabled_as_uninstalled = toggled;
+                debug!("Config change: {:?}", self);
+                Config::save_changes(self, &phone.adb_id);
+                Command::none()
+            }
+            Message::LogLevelSelected(level) => {
+                self.general.log_level = level;
+                self.general.apply_logging();
+                debug!("Config change: {:?}", self);
+                Config::save_changes(self, &phone.adb_id);
+                Command::none()
+            }
+            Message::LogAdbCommands(toggled) => {
+                self.general.log_adb_commands = toggled;
+                self.general.apply_logging();
+                debug!("Config change: {:?}", self);
+                Config::save_changes(self, &phone.adb_id);
+                Command::none()
+            }
+            Message::ConfirmThresholdChanged(input) => {
+                if input.is_empty() {
+                    self.general.confirm_threshold = 0;
+                } else if let Ok(threshold) = input.parse() {
+                    self.general.confirm_threshold = threshold;
+                } else {
+                    return Command::none();
+                }
+                debug!("Config change: {:?}", self);
+                Config::save_changes(self, &phone.adb_id);
+                Command::none()
+            }
+            Message::BatteryThresholdChanged(input) => {
+                if input.is_empty() {
+                    self.general.battery_threshold = 0;
+                } else if let Ok(threshold) = input.parse::<u8>() {
+                    self.general.battery_threshold = threshold.min(100);
+                } else {
+                    return Command::none();
+                }
+                debug!("Config change: {:?}", self);
+                Config::save_changes(self, &phone.adb_id);
+                Command::none()
+            }
+            Message::DataDirChanged(dir) => {
+                self.data_dir_input = dir;
+                self.data_dir_error = None;
+                Command::none()
+            }
+            Message::ApplyDataDir => {
+                let dir = self.data_dir_input.trim().to_string();
+                if let Err(e) = check_writable_dir(&data_dir(&dir)) {
+                    self.data_dir_error = Some(e);
+                    return Command::none();
+                }
+                self.general.data_dir = dir;
+                debug!("Config change: {:?}", self);
+                Config::save_changes(self, &phone.adb_id);
+                Command::none()
+            }
+            Message::ReportPathChanged(path) => {
+                self.general.report_path = path;
+                debug!("Config change: {:?}", self);
+                Config::save_changes(self, &phone.adb_id);
+                Command::none()
+            }
+            Message::StatusSink(toggled) => {
+                self.general.status_sink = toggled;
+                debug!("Config change: {:?}", self);
+                Config::save_changes(self, &phone.adb_id);
+                Command::none()
+            }
+            Message::StatusPathChanged(path) => {
+                self.status_path_input = path;
+                Command::none()
+            }
+            Message::ApplyStatusPath => {
+                self.general.status_path = self.status_path_input.trim().to_string();
+                debug!("Config change: {:?}", self);
+                Config::save_changes(self, &phone.adb_id);
+                Command::none()
+            }
+            Message::LocalListPathChanged(path) => {
+                self.general.local_list_path = path;
+                debug!("Config change: {:?}", self);
+                Config::save_changes(self, &phone.adb_id);
+                Command::none()
+            }
+            Message::ListSha256Changed(hash) => {
+                self.general.list_sha256 = hash;
+                debug!("Config change: {:?}", self);
+                Config::save_changes(self, &phone.adb_id);
+                Command::none()
+            }
+            Message::DefaultPackageStateSelected(state) => {
+                self.general.default_package_state = state;
+                debug!("Config change: {:?}", self);
+                Config::save_changes(self, &phone.adb_id);
+                Command::none()
+            }
+            Message::DefaultListSelected(list) => {
+                self.general.default_list = list;
+                debug!("Config change: {:?}", self);
+                Config::save_changes(self, &phone.adb_id);
+                Command::none()
+            }
+            Message::DefaultRemovalSelected(removal) => {
+                self.general.default_removal = removal;
+                debug!("Config change: {:?}", self);
+                Config::save_changes(self, &phone.adb_id);
+                Command::none()
+            }
             Message::DisableMode(toggled) => {
                 if phone.android_sdk >= 23 {
                     self.device.disable_mode = toggled;
@@ -66,16 +423,75 @@ impl Settings {
                 }
                 Command::none()
             }
+            Message::ListDensitySelected(density) => {
+                self.general.list_density = density;
+                debug!("Config change: {:?}", self);
+                Config::save_changes(self, &phone.adb_id);
+                Command::none()
+            }
+            Message::FilterControlToggled(control, toggled) => {
+                self.general.filter_controls.retain(|c| *c != control);
+                if toggled {
+                    self.general.filter_controls.push(control);
+                }
+                debug!("Config change: {:?}", self);
+                Config::save_changes(self, &phone.adb_id);
+                Command::none()
+            }
+            Message::MoveFilterControlUp(control) => {
+                let controls = &mut self.general.filter_controls;
+                if let Some(i) = controls
+                    .iter()
+                    .position(|c| *c == control)
+                    .filter(|i| *i > 0)
+                {
+                    controls.swap(i, i - 1);
+                    debug!("Config change: {:?}", self);
+                    Config::save_changes(self, &phone.adb_id);
+                }
+                Command::none()
+            }
+            Message::MoveFilterControlDown(control) => {
+                let controls = &mut self.general.filter_controls;
+                if let Some(i) = controls
+                    .iter()
+                    .position(|c| *c == control)
+                    .filter(|i| i + 1 < controls.len())
+                {
+                    controls.swap(i, i + 1);
+                    debug!("Config change: {:?}", self);
+                    Config::save_changes(self, &phone.adb_id);
+                }
+                Command::none()
+            }
+            Message::DisableRemoval(removal, toggled) => {
+                if phone.android_sdk >= 23 {
+                    self.device.disable_removals.retain(|r| *r != removal);
+                    if toggled {
+                        self.device.disable_removals.push(removal);
+                    }
+                    debug!("Config change: {:?}", self);
+                    Config::save_changes(self, &phone.adb_id);
+                }
+                Command::none()
+            }
             Message::MultiUserMode(toggled) => {
                 self.device.multi_user_mode = toggled;
                 debug!("Config change: {:?}", self);
                 Config::save_changes(self, &phone.adb_id);
                 Command::none()
             }
+            Message::CollapseMultiUser(toggled) => {
+                self.device.collapse_multi_user = toggled;
+                debug!("Config change: {:?}", self);
+                Config::save_changes(self, &phone.adb_id);
+                Command::none()
+            }
+            // Every style reads the theme when drawn: nothing else to refresh
             Message::ApplyTheme(theme) => {
                 self.general.theme = theme.to_string();
                 debug!("Config change: {:?}", self);
-                Config::save_changes(self, &phone.adb_id);
+                Config::save_general(&self.general);
                 Command::none()
             }
             Message::UrlPressed(url) => {
@@ -104,6 +520,9 @@ impl Settings {
                             device_id: phone.adb_id.clone(),
                             multi_user_mode: phone.android_sdk > 21,
                             disable_mode: false,
+                            collapse_multi_user: false,
+                            disable_removals: vec![],
+                            auto_apply_confirmed: false,
                             backup: BackupSettings {
                                 backups: backups.clone(),
                                 selected: backups.first().cloned(),
@@ -145,6 +564,9 @@ impl Settings {
                             i_user: None,
                             index: p.index,
                             removal: "RESTORE".to_string(),
+                            force_disable: false,
+                            suspend: None,
+                            uninstalled_instead: false,
                         };
                         for command in p.commands.clone() {
                             *nb_running_async_adb_commands += 1;
@@ -152,6 +574,7 @@ impl Settings {
                                 perform_adb_commands(
                                     command,
                                     CommandType::PackageManager(p_info.clone()),
+                                    phone.adb_id.clone(),
                                 ),
                                 Message::RestoringDevice,
                             ));
@@ -203,18 +626,516 @@ impl Settings {
             self.general.expert_mode,
             Message::ExpertMode,
         )
+        .style(if self.general.safe_mode {
+            style::CheckBox::SettingsDisabled
+        } else {
+            style::CheckBox::SettingsEnabled
+        });
+
+        let safe_mode_checkbox = checkbox(
+            "Safe mode: only allow the removal of recommended packages",
+            self.general.safe_mode,
+            Message::SafeMode,
+        )
         .style(style::CheckBox::SettingsEnabled);
 
+        let safe_mode_descr = text(
+            "The removal filter is locked to \"Recommended\" and the option above is turned off",
+        )
+        .style(style::Text::Commentary)
+        .size(15);
+
         let expert_mode_descr =
             text("Most of unsafe packages are known to bootloop the device if removed.")
                 .style(style::Text::Commentary)
                 .size(15);
 
-        let general_ctn = container(column![expert_mode_checkbox, expert_mode_descr].spacing(10))
-            .padding(10)
-            .width(Length::Fill)
-            .height(Length::Shrink)
-            .style(style::Container::Frame);
+        let reboot_after_batch_checkbox = checkbox(
+            "Offer to reboot the device after a successful bulk action",
+            self.general.reboot_after_batch,
+            Message::RebootAfterBatch,
+        )
+        .style(style::CheckBox::SettingsEnabled);
+
+        let reboot_after_batch_descr =
+            text("Some changes only fully take effect after a reboot. You're always asked first")
+                .style(style::Text::Commentary)
+                .size(15);
+
+        let verify_state_checkbox = checkbox(
+            "Wait for the device to confirm the new state of a package",
+            self.general.verify_package_state,
+            Message::VerifyPackageState,
+        )
+        .style(style::CheckBox::SettingsEnabled);
+
+        let verify_state_descr = text(
+            "Slower, but the list never shows a state the device didn't report (e.g. when a command silently did nothing)",
+        )
+        .style(style::Text::Commentary)
+        .size(15);
+
+        let verify_batch_checkbox = checkbox(
+            "Verify the packages of a bulk action once it is over",
+            self.general.verify_batch,
+            Message::VerifyBatch,
+        )
+        .style(style::CheckBox::SettingsEnabled);
+
+        let verify_batch_descr = text(
+            "The state of every changed package is read again from the device. Packages that didn't end up in the expected state are flagged and can be retried from the summary",
+        )
+        .style(style::Text::Commentary)
+        .size(15);
+
+        let keep_selection_checkbox = checkbox(
+            "Keep packages selected after their action succeeds",
+            self.general.keep_selection_after_action,
+            Message::KeepSelectionAfterAction,
+        )
+        .style(style::CheckBox::SettingsEnabled);
+
+        let keep_selection_descr = text(
+            "For selections reused across several actions. Applies to bulk actions too: running the same one again skips the packages already done. Restored packages you can't remove are unselected anyway",
+        )
+        .style(style::Text::Commentary)
+        .size(15);
+
+        let confirm_device_switch_checkbox = checkbox(
+            "Ask before switching devices while packages are selected",
+            self.general.confirm_device_switch,
+            Message::ConfirmDeviceSwitch,
+        )
+        .style(style::CheckBox::SettingsEnabled);
+
+        let confirm_device_switch_descr =
+            text("Offers to keep the selection in a recovery file or a profile before switching")
+                .style(style::Text::Commentary)
+                .size(15);
+
+        let auto_select_checkbox = checkbox(
+            "Select newly recommended packages after a list update",
+            self.general.auto_select_new_recommended,
+            Message::AutoSelectNewRecommended,
+        )
+        .style(style::CheckBox::SettingsEnabled);
+
+        let auto_select_descr = text(
+            "Packages added to the Recommended list and installed on the device are selected (nothing is removed until you apply the selection)",
+        )
+        .style(style::Text::Commentary)
+        .size(15);
+
+        let connect_profile_row = row![
+            text("Profile selected when a device is connected"),
+            pick_list(
+                self.profiles.clone(),
+                Some(self.general.connect_profile.clone()).filter(|name| !name.is_empty()),
+                Message::ConnectProfileSelected,
+            )
+            .placeholder("None")
+            .padding(6),
+            button(text("Clear").size(13))
+                .padding([2, 6])
+                .on_press(Message::ConnectProfileSelected(String::new()))
+                .style(style::Button::Primary),
+        ]
+        .spacing(10)
+        .align_items(Alignment::Center);
+
+        let auto_apply_profile_checkbox = checkbox(
+            "Apply it right away",
+            self.general.auto_apply_profile,
+            Message::AutoApplyProfile,
+        )
+        .style(style::CheckBox::SettingsEnabled);
+
+        let connect_profile_descr = text(
+            "Once per device and per session, the packages of the profile replace the selection. Packages of the profile the device doesn't have are reported. Applying it right away is confirmed the first time on each device",
+        )
+        .style(style::Text::Commentary)
+        .size(15);
+
+        let hide_uninstalled_checkbox = checkbox(
+            "Hide uninstalled packages",
+            self.general.hide_uninstalled,
+            Message::HideUninstalled,
+        )
+        .style(style::CheckBox::SettingsEnabled);
+
+        let hide_uninstalled_descr =
+            text("They are still listed when the \"Uninstalled\" state filter is selected")
+                .style(style::Text::Commentary)
+                .size(15);
+
+        let count_disabled_checkbox = checkbox(
+            "Treat disable as uninstall in the selection counts",
+            self.general.count_disabled_as_uninstalled,
+            Message::CountDisabledAsUninstalled,
+        )
+        .style(style::CheckBox::SettingsEnabled);
+
+        let count_disabled_descr = text(
+            "The selection buttons show a single count instead of one per action (uninstall/disable, restore/enable)",
+        )
+        .style(style::Text::Commentary)
+        .size(15);
+
+        let hover_preview_checkbox = checkbox(
+            "Preview the description of the hovered package",
+            self.general.hover_preview,
+            Message::HoverPreview,
+        )
+        .style(style::CheckBox::SettingsEnabled);
+
+        let hover_preview_descr = text(
+            "The description panel goes back to the clicked package when the cursor leaves the list",
+        )
+        .style(style::Text::Commentary)
+        .size(15);
+
+        let always_on_top_checkbox = checkbox(
+            "Keep UAD above the other windows",
+            self.general.always_on_top,
+            Message::AlwaysOnTop,
+        )
+        .style(style::CheckBox::SettingsEnabled);
+
+        let always_on_top_descr = text(
+            "Handy to follow a guide in a browser. Applied at the next launch: the window level can't change while it's open",
+        )
+        .style(style::Text::Commentary)
+        .size(15);
+
+        let list_density_row = row![
+            text("Package list density"),
+            pick_list(
+                &ListDensity::ALL[..],
+                Some(self.general.list_density),
+                Message::ListDensitySelected,
+            )
+            .padding(6),
+        ]
+        .spacing(10)
+        .align_items(Alignment::Center);
+
+        // Shown controls in their order, then the hidden ones
+        let hidden_controls = FilterControl::ALL
+            .into_iter()
+            .filter(|c| !self.general.filter_controls.contains(c));
+        let filter_controls = self
+            .general
+            .filter_controls
+            .iter()
+            .copied()
+            .chain(hidden_controls)
+            .fold(
+                column![text("Filter controls (shown from left to right)")].spacing(5),
+                |col, control| {
+                    let shown = self.general.filter_controls.contains(&control);
+                    let move_btn = |label, msg| {
+                        let btn = button(text(label).size(13)).padding([2, 6]);
+                        if shown {
+                            btn.on_press(msg).style(style::Button::Primary)
+                        } else {
+                            btn.style(style::Button::Primary)
+                        }
+                    };
+                    col.push(
+                        row![
+                            move_btn("Up", Message::MoveFilterControlUp(control)),
+                            move_btn("Down", Message::MoveFilterControlDown(control)),
+                            checkbox(control.to_string(), shown, move |toggled| {
+                                Message::FilterControlToggled(control, toggled)
+                            })
+                            .text_size(15)
+                            .style(style::CheckBox::SettingsEnabled),
+                        ]
+                        .spacing(10)
+                        .align_items(Alignment::Center),
+                    )
+                },
+            );
+
+        let data_dir_row = row![
+            text("Data directory"),
+            text_input(
+                "Backups, exports, snapshots, reports and logs (cache directory if empty)",
+                &self.data_dir_input,
+                Message::DataDirChanged,
+            )
+            .on_submit(Message::ApplyDataDir)
+            .padding(6),
+            button("Apply")
+                .padding(5)
+                .on_press(Message::ApplyDataDir)
+                .style(style::Button::Primary),
+        ]
+        .spacing(10)
+        .align_items(Alignment::Center);
+
+        let data_dir_descr = match &self.data_dir_error {
+            Some(e) => text(e).style(style::Text::Danger).size(15),
+            None if self.data_dir_input.trim() != self.general.data_dir => text("Not applied yet")
+                .style(style::Text::Commentary)
+                .size(15),
+            None => text(format!(
+                "Each kind of file has its own folder in there ({}). Used from the next start",
+                DATA_DIR.display()
+            ))
+            .style(style::Text::Commentary)
+            .size(15),
+        };
+
+        let report_path_row = row![
+            text("Bulk action report"),
+            text_input(
+                "Path of a JSON file, relative to the reports folder (no report if empty)",
+                &self.general.report_path,
+                Message::ReportPathChanged,
+            )
+            .padding(6),
+        ]
+        .spacing(10)
+        .align_items(Alignment::Center);
+
+        let status_sink_checkbox = checkbox(
+            "Write package state changes to a status file",
+            self.general.status_sink,
+            Message::StatusSink,
+        )
+        .style(style::CheckBox::SettingsEnabled);
+
+        let status_path_row = row![
+            text("Status file"),
+            text_input(
+                "Path of the file a JSON line is appended to",
+                &self.status_path_input,
+                Message::StatusPathChanged,
+            )
+            .on_submit(Message::ApplyStatusPath)
+            .padding(6),
+            button("Apply")
+                .padding(5)
+                .on_press(Message::ApplyStatusPath)
+                .style(style::Button::Primary),
+        ]
+        .spacing(10)
+        .align_items(Alignment::Center);
+
+        let status_sink_descr = if self.status_path_input.trim() != self.general.status_path {
+            text("Not applied yet")
+        } else {
+            text("Each line holds the device serial, the package and its new state, for scripts to react to the changes. The file is never read and failing to write it doesn't stop the actions")
+        }
+        .style(style::Text::Commentary)
+        .size(15);
+
+        let local_list_row = row![
+            text("Debloat list file"),
+            text_input(
+                "Path of a local uad_lists.json (downloaded if empty)",
+                &self.general.local_list_path,
+                Message::LocalListPathChanged,
+            )
+            .padding(6),
+        ]
+        .spacing(10)
+        .align_items(Alignment::Center);
+
+        let local_list_descr = text(
+            "For machines without internet access. An invalid file falls back to the list embedded in UAD. Used from the next list update",
+        )
+        .style(style::Text::Commentary)
+        .size(15);
+
+        let list_sha256_row = row![
+            text("Expected list checksum"),
+            text_input(
+                "SHA-256 of the downloaded list (optional)",
+                &self.general.list_sha256,
+                Message::ListSha256Changed,
+            )
+            .padding(6),
+        ]
+        .spacing(10)
+        .align_items(Alignment::Center);
+
+        let list_sha256_descr = text(
+            "A downloaded list that doesn't match is refused: the cached or embedded list is used instead",
+        )
+        .style(style::Text::Commentary)
+        .size(15);
+
+        let default_filters_row = row![
+            text("Default filters"),
+            pick_list(
+                &PackageState::ALL[..],
+                Some(self.general.default_package_state),
+                Message::DefaultPackageStateSelected,
+            )
+            .padding(6),
+            pick_list(
+                &UadList::ALL[..],
+                Some(self.general.default_list),
+                Message::DefaultListSelected,
+            )
+            .padding(6),
+            pick_list(
+                &Removal::ALL[..],
+                Some(self.general.default_removal),
+                Message::DefaultRemovalSelected,
+            )
+            .padding(6),
+        ]
+        .spacing(10)
+        .align_items(Alignment::Center);
+
+        let default_filters_descr = text(
+            "State, list and removal filters selected when the packages of a device are loaded",
+        )
+        .style(style::Text::Commentary)
+        .size(15);
+
+        let log_level_row = row![
+            text("Log level"),
+            pick_list(
+                &LogLevel::ALL[..],
+                Some(self.general.log_level),
+                Message::LogLevelSelected,
+            )
+            .padding(6),
+        ]
+        .spacing(10)
+        .align_items(Alignment::Center);
+
+        let log_adb_commands_checkbox = checkbox(
+            "Log the full adb command line of every action",
+            self.general.log_adb_commands,
+            Message::LogAdbCommands,
+        )
+        .style(style::CheckBox::SettingsEnabled);
+
+        let log_adb_commands_descr = text("Command lines are logged at the debug level")
+            .style(style::Text::Commentary)
+            .size(15);
+
+        let confirm_threshold_row = row![
+            text("Ask for confirmation from"),
+            text_input(
+                "0",
+                &self.general.confirm_threshold.to_string(),
+                Message::ConfirmThresholdChanged,
+            )
+            .padding(6)
+            .width(Length::Units(60)),
+            text("packages"),
+        ]
+        .spacing(10)
+        .align_items(Alignment::Center);
+
+        let confirm_threshold_descr = text(
+            "0 always asks. Unsafe packages and known issues (e.g. dependencies) always ask, whatever the number",
+        )
+        .style(style::Text::Commentary)
+        .size(15);
+
+        let battery_threshold_row = row![
+            text("Warn when the battery of the device is under"),
+            text_input(
+                "0",
+                &self.general.battery_threshold.to_string(),
+                Message::BatteryThresholdChanged,
+            )
+            .padding(6)
+            .width(Length::Units(60)),
+            text("% (and not charging)"),
+        ]
+        .spacing(10)
+        .align_items(Alignment::Center);
+
+        let battery_threshold_descr = text(
+            "A device dying in the middle of a bulk action is left half debloated. 0 never warns",
+        )
+        .style(style::Text::Commentary)
+        .size(15);
+
+        let test_adb_row = row![
+            button(text("Test adb").size(15))
+                .padding([4, 8])
+                .on_press(Message::TestAdb)
+                .style(style::Button::Primary),
+            text("Runs `adb version` and `adb devices` with the adb UAD uses")
+                .style(style::Text::Commentary)
+                .size(15),
+        ]
+        .spacing(10)
+        .align_items(Alignment::Center);
+
+        let adb_report = match &self.adb_report {
+            Some(report) => text(report).size(15),
+            None => text(""),
+        };
+
+        let general_ctn = container(
+            column![
+                safe_mode_checkbox,
+                safe_mode_descr,
+                expert_mode_checkbox,
+                expert_mode_descr,
+                reboot_after_batch_checkbox,
+                reboot_after_batch_descr,
+                verify_state_checkbox,
+                verify_state_descr,
+                verify_batch_checkbox,
+                verify_batch_descr,
+                keep_selection_checkbox,
+                keep_selection_descr,
+                confirm_device_switch_checkbox,
+                confirm_device_switch_descr,
+                auto_select_checkbox,
+                auto_select_descr,
+                connect_profile_row,
+                auto_apply_profile_checkbox,
+                connect_profile_descr,
+                hide_uninstalled_checkbox,
+                hide_uninstalled_descr,
+                count_disabled_checkbox,
+                count_disabled_descr,
+                confirm_threshold_row,
+                confirm_threshold_descr,
+                battery_threshold_row,
+                battery_threshold_descr,
+                default_filters_row,
+                default_filters_descr,
+                list_density_row,
+                hover_preview_checkbox,
+                hover_preview_descr,
+                always_on_top_checkbox,
+                always_on_top_descr,
+                filter_controls,
+                data_dir_row,
+                data_dir_descr,
+                report_path_row,
+                status_sink_checkbox,
+                status_path_row,
+                status_sink_descr,
+                local_list_row,
+                local_list_descr,
+                list_sha256_row,
+                list_sha256_descr,
+                log_level_row,
+                log_adb_commands_checkbox,
+                log_adb_commands_descr,
+                test_adb_row,
+                adb_report,
+            ]
+            .spacing(10),
+        )
+        .padding(10)
+        .width(Length::Fill)
+        .height(Length::Shrink)
+        .style(style::Container::Frame);
 
         let warning_ctn = container(
             row![
@@ -230,6 +1151,27 @@ impl Settings {
         .width(Length::Fill)
         .style(style::Container::BorderedFrame);
 
+        // Removals only use `pm` for the user: the system partition is never modified
+        let root_ctn = match &phone.root {
+            RootEnvironment::None => column![],
+            root => column![
+                text(root.to_string()),
+                text(
+                    "UAD doesn't use root: packages are removed for the user only and the \
+                    system partition isn't modified. A system update (OTA) can bring some of \
+                    them back. Magisk modules hiding system apps are not affected by UAD.",
+                )
+                .style(style::Text::Commentary)
+                .size(15),
+                button(text("Find orphaned app data").size(15))
+                    .padding([4, 8])
+                    .on_press(Message::FindOrphanedDirs)
+                    .style(style::Button::Primary),
+                self.orphaned_data_view(),
+            ]
+            .spacing(5),
+        };
+
         let multi_user_mode_descr =
             text("Disabling this setting will typically prevent affecting your work profile")
                 .style(style::Text::Commentary)
@@ -242,6 +1184,23 @@ impl Settings {
         )
         .style(style::CheckBox::SettingsEnabled);
 
+        let collapse_multi_user_checkbox = checkbox(
+            "Show a single row per package with a state chip for each user",
+            self.device.collapse_multi_user,
+            Message::CollapseMultiUser,
+        )
+        .style(if self.device.multi_user_mode {
+            style::CheckBox::SettingsEnabled
+        } else {
+            style::CheckBox::SettingsDisabled
+        });
+
+        let collapse_multi_user_descr = text(
+            "Actions apply to every user unless you click a chip to exclude that user (requires multi-user mode)",
+        )
+        .style(style::Text::Commentary)
+        .size(15);
+
         let disable_checkbox_style = if phone.android_sdk >= 23 {
             style::CheckBox::SettingsEnabled
         } else {
@@ -285,12 +1244,45 @@ impl Settings {
             .width(Length::Fill)
         };
 
+        let disable_removals_descr =
+            text("Disable packages of the checked categories even when the disable mode is off")
+                .style(style::Text::Commentary)
+                .size(15);
+
+        let disable_removal_checkboxes = Column::with_children(
+            Removal::ALL
+                .iter()
+                .filter(|r| **r != Removal::All)
+                .map(|r| {
+                    let removal = *r;
+                    let effective_action = if self.device.disables(removal) {
+                        "disable"
+                    } else {
+                        "uninstall"
+                    };
+                    checkbox(
+                        format!("{} ({})", removal, effective_action),
+                        self.device.disable_removals.contains(&removal),
+                        move |toggled| Message::DisableRemoval(removal, toggled),
+                    )
+                    .style(disable_checkbox_style)
+                    .into()
+                })
+                .collect(),
+        )
+        .spacing(6)
+        .padding([0, 0, 0, 20]);
+
         let device_specific_ctn = container(
             column![
                 multi_user_mode_checkbox,
                 multi_user_mode_descr,
+                collapse_multi_user_checkbox,
+                collapse_multi_user_descr,
                 disable_setting_row,
                 disable_mode_descr,
+                disable_removals_descr,
+                disable_removal_checkboxes,
             ]
             .spacing(10),
         )
@@ -382,6 +1374,7 @@ impl Settings {
                 general_ctn,
                 text("Current device").size(25),
                 warning_ctn,
+                root_ctn,
                 device_specific_ctn,
                 backup_restore_ctn,
             ]
@@ -408,4 +1401,71 @@ impl Settings {
             .height(Length::Fill)
             .into()
     }
+
+    fn orphaned_data_view(&self) -> Column<Message, Renderer<Theme>> {
+        let data = match &self.orphaned_data {
+            Some(data) => data,
+            None => return column![],
+        };
+        let dirs =
+            data.dirs
+                .iter()
+                .enumerate()
+                .fold(column![].spacing(2), |col, (i, (dir, selected))| {
+                    col.push(
+                        checkbox(
+                            format!("{} ({})", dir.path, format_bytes(dir.size_kb * 1024)),
+                            *selected,
+                            move |s| Message::OrphanedDirToggled(i, s),
+                        )
+                        .text_size(15)
+                        .style(style::CheckBox::SettingsEnabled),
+                    )
+                });
+        let selected: Vec<&OrphanedDir> = data
+            .dirs
+            .iter()
+            .filter(|(_, s)| *s)
+            .map(|(d, _)| d)
+            .collect();
+
+        let actions = if data.confirming {
+            row![
+                text(format!(
+                    "Delete {} directories ({})? This can't be undone",
+                    format_count(selected.len()),
+                    format_bytes(selected.iter().map(|d| d.size_kb).sum::<u64>() * 1024)
+                ))
+                .style(style::Text::Danger),
+                button(text("Delete").size(15))
+                    .padding([4, 8])
+                    .on_press(Message::ConfirmDeleteOrphanedDirs)
+                    .style(style::Button::UninstallPackage),
+                button(text("Cancel").size(15))
+                    .padding([4, 8])
+                    .on_press(Message::CancelDeleteOrphanedDirs)
+                    .style(style::Button::Primary),
+            ]
+        } else if !data.dirs.is_empty() {
+            let delete_btn = button(text(format!("Delete selected ({})", selected.len())).size(15))
+                .padding([4, 8])
+                .style(style::Button::UninstallPackage);
+            row![if selected.is_empty() {
+                delete_btn
+            } else {
+                delete_btn.on_press(Message::DeleteOrphanedDirs)
+            }]
+        } else {
+            row![]
+        };
+
+        column![
+            text(data.report.clone().unwrap_or_default())
+                .style(style::Text::Commentary)
+                .size(15),
+            dirs,
+            actions.spacing(10).align_items(Alignment::Center),
+        ]
+        .spacing(5)
+    }
 }
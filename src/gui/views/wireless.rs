@@ -0,0 +1,192 @@
+use crate::core::sync::{connect_device, discover_mdns_services, pair_device, MdnsService};
+use crate::core::theme::Theme;
+use crate::gui::style;
+use iced::widget::{button, column, container, row, scrollable, text, text_input, Space};
+use iced::{Alignment, Command, Element, Length, Renderer};
+
+/// Connection to devices with wireless debugging enabled (Android 11+)
+#[derive(Default, Debug, Clone)]
+pub struct Wireless {
+    services: Vec<MdnsService>,
+    scanning: bool,
+    address: String,      // typed by hand, when discovery isn't available
+    pairing_code: String, // shown by the device next to the pairing port
+    status: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Message {
+    Scan,
+    Scanned(Result<Vec<MdnsService>, String>),
+    AddressChanged(String),
+    PairingCodeChanged(String),
+    Connect(String),
+    Pair(String),
+    Paired(Result<String, String>),
+    Connected(Result<String, String>), // the device list is refreshed by UadGui update()
+}
+
+impl Wireless {
+    pub fn update(&mut self, msg: Message) -> Command<Message> {
+        match msg {
+            Message::Scan => {
+                self.scanning = true;
+                Command::perform(async { discover_mdns_services() }, Message::Scanned)
+            }
+            Message::Scanned(result) => {
+                self.scanning = false;
+                match result {
+                    Ok(services) => {
+                        self.status = if services.is_empty() {
+                            Some("No device found on the network".to_string())
+                        } else {
+                            None
+                        };
+                        self.services = services;
+                    }
+                    Err(e) => {
+                        warn!("mDNS discovery failed: {}", e);
+                        self.services.clear();
+                        self.status = Some(
+                            "Device discovery isn't supported by this adb version: \
+                            type the address of the device instead"
+                                .to_string(),
+                        );
+                    }
+                }
+                Command::none()
+            }
+            Message::AddressChanged(address) => {
+                self.address = address;
+                Command::none()
+            }
+            Message::PairingCodeChanged(code) => {
+                self.pairing_code = code;
+                Command::none()
+            }
+            Message::Connect(address) => {
+                self.status = Some(format!("Connecting to {}...", address));
+                Command::perform(async move { connect_device(&address) }, Message::Connected)
+            }
+            Message::Pair(address) => {
+                self.status = Some(format!("Pairing with {}...", address));
+                let code = self.pairing_code.trim().to_string();
+                Command::perform(async move { pair_device(&address, &code) }, Message::Paired)
+            }
+            Message::Paired(result) => {
+                match result {
+                    Ok(_) => {
+                        self.status = Some("Paired: the device can now be connected".to_string());
+                        self.pairing_code.clear();
+                        return self.update(Message::Scan);
+                    }
+                    Err(e) => self.status = Some(format!("Pairing failed: {}", e)),
+                }
+                Command::none()
+            }
+            Message::Connected(result) => {
+                self.status = Some(match result {
+                    Ok(output) => output,
+                    Err(e) => format!("Connection failed: {}", e),
+                });
+                Command::none()
+            }
+        }
+    }
+
+    pub fn view(&self) -> Element<Message, Renderer<Theme>> {
+        let scan_btn = button(if self.scanning { "Scanning..." } else { "Scan" })
+            .on_press(Message::Scan)
+            .padding(5)
+            .style(style::Button::Primary);
+
+        let services = self
+            .services
+            .iter()
+            .fold(column![].spacing(10), |col, service| {
+                let action_btn = if service.pairing {
+                    button(text("Pair").size(13))
+                        .padding([2, 6])
+                        .on_press(Message::Pair(service.address.clone()))
+                        .style(style::Button::Primary)
+                } else {
+                    button(text("Connect").size(13))
+                        .padding([2, 6])
+                        .on_press(Message::Connect(service.address.clone()))
+                        .style(style::Button::Primary)
+                };
+                col.push(
+                    row![
+                        text(&service.name).width(Length::Fill),
+                        text(&service.address).style(style::Text::Commentary),
+                        text(if service.pairing {
+                            "pairing"
+                        } else {
+                            "ready to connect"
+                        })
+                        .style(style::Text::Commentary),
+                        action_btn,
+                    ]
+                    .spacing(10)
+                    .align_items(Alignment::Center),
+                )
+            });
+
+        let pairing_code_row = if self.services.iter().any(|s| s.pairing) {
+            row![
+                text("Pairing code"),
+                text_input(
+                    "Shown on the device in Wireless debugging > Pair device with pairing code",
+                    &self.pairing_code,
+                    Message::PairingCodeChanged,
+                )
+                .padding(5),
+            ]
+            .spacing(10)
+            .align_items(Alignment::Center)
+        } else {
+            row![]
+        };
+
+        let manual_connect = row![
+            text_input("host:port", &self.address, Message::AddressChanged).padding(5),
+            button("Connect")
+                .on_press(Message::Connect(self.address.trim().to_string()))
+                .padding(5)
+                .style(style::Button::Primary),
+        ]
+        .spacing(10)
+        .align_items(Alignment::Center);
+
+        let status = match &self.status {
+            Some(status) => text(status).style(style::Text::Commentary),
+            None => text(""),
+        };
+
+        let content = column![
+            row![
+                text("Devices with wireless debugging enabled on this network").size(22),
+                Space::new(Length::Fill, Length::Shrink),
+                scan_btn,
+            ]
+            .align_items(Alignment::Center),
+            scrollable(services).height(Length::Shrink),
+            pairing_code_row,
+            text("Or connect by address").size(22),
+            manual_connect,
+            status,
+        ]
+        .spacing(20);
+
+        container(
+            container(content)
+                .padding(20)
+                .width(Length::Fill)
+                .style(style::Container::Frame),
+        )
+        .padding(10)
+        .width(Length::Fill)
+        .height(Length::Fill)
+        .into()
+    }
+}
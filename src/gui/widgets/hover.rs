@@ -0,0 +1,149 @@
+use iced_native::widget::{tree, Tree, Widget};
+use iced_native::{
+    event, layout, mouse, renderer, Clipboard, Element, Event, Layout, Length, Point, Rectangle,
+    Shell,
+};
+
+/// Wraps a widget and reports when the cursor enters and leaves it.
+///
+/// The content is laid out, drawn and receives its events as if it wasn't wrapped.
+pub struct Hover<'a, Message, Renderer> {
+    content: Element<'a, Message, Renderer>,
+    on_enter: Message,
+    on_exit: Message,
+}
+
+#[derive(Default)]
+struct State {
+    hovered: bool,
+}
+
+pub fn hover<'a, Message, Renderer>(
+    content: impl Into<Element<'a, Message, Renderer>>,
+    on_enter: Message,
+    on_exit: Message,
+) -> Hover<'a, Message, Renderer> {
+    Hover {
+        content: content.into(),
+        on_enter,
+        on_exit,
+    }
+}
+
+impl<'a, Message, Renderer> Widget<Message, Renderer> for Hover<'a, Message, Renderer>
+where
+    Message: Clone,
+    Renderer: iced_native::Renderer,
+{
+    fn width(&self) -> Length {
+        self.content.as_widget().width()
+    }
+
+    fn height(&self) -> Length {
+        self.content.as_widget().height()
+    }
+
+    fn tag(&self) -> tree::Tag {
+        tree::Tag::of::<State>()
+    }
+
+    fn state(&self) -> tree::State {
+        tree::State::new(State::default())
+    }
+
+    fn children(&self) -> Vec<Tree> {
+        vec![Tree::new(&self.content)]
+    }
+
+    fn diff(&self, tree: &mut Tree) {
+        tree.diff_children(std::slice::from_ref(&self.content));
+    }
+
+    fn layout(&self, renderer: &Renderer, limits: &layout::Limits) -> layout::Node {
+        self.content.as_widget().layout(renderer, limits)
+    }
+
+    fn on_event(
+        &mut self,
+        tree: &mut Tree,
+        event: Event,
+        layout: Layout<'_>,
+        cursor_position: Point,
+        renderer: &Renderer,
+        clipboard: &mut dyn Clipboard,
+        shell: &mut Shell<'_, Message>,
+    ) -> event::Status {
+        if let Event::Mouse(mouse::Event::CursorMoved { .. } | mouse::Event::CursorLeft) = event {
+            let state = tree.state.downcast_mut::<State>();
+            let hovered = matches!(event, Event::Mouse(mouse::Event::CursorMoved { .. }))
+                && layout.bounds().contains(cursor_position);
+
+            if hovered != state.hovered {
+                state.hovered = hovered;
+                shell.publish(if hovered {
+                    self.on_enter.clone()
+                } else {
+                    self.on_exit.clone()
+                });
+            }
+        }
+
+        self.content.as_widget_mut().on_event(
+            &mut tree.children[0],
+            event,
+            layout,
+            cursor_position,
+            renderer,
+            clipboard,
+            shell,
+        )
+    }
+
+    fn mouse_interaction(
+        &self,
+        tree: &Tree,
+        layout: Layout<'_>,
+        cursor_position: Point,
+        viewport: &Rectangle,
+        renderer: &Renderer,
+    ) -> mouse::Interaction {
+        self.content.as_widget().mouse_interaction(
+            &tree.children[0],
+            layout,
+            cursor_position,
+            viewport,
+            renderer,
+        )
+    }
+
+    fn draw(
+        &self,
+        tree: &Tree,
+        renderer: &mut Renderer,
+        theme: &Renderer::Theme,
+        style: &renderer::Style,
+        layout: Layout<'_>,
+        cursor_position: Point,
+        viewport: &Rectangle,
+    ) {
+        self.content.as_widget().draw(
+            &tree.children[0],
+            renderer,
+            theme,
+            style,
+            layout,
+            cursor_position,
+            viewport,
+        );
+    }
+}
+
+impl<'a, Message, Renderer> From<Hover<'a, Message, Renderer>> for Element<'a, Message, Renderer>
+where
+    Message: 'a + Clone,
+    Renderer: 'a + iced_native::Renderer,
+{
+    fn from(hover: Hover<'a, Message, Renderer>) -> Self {
+        Element::new(hover)
+    }
+}
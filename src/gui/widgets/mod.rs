@@ -1,2 +1,5 @@
+pub mod hover;
 pub mod navigation_menu;
 pub mod package_row;
+pub mod right_click;
+pub mod splitter;
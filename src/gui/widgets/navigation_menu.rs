@@ -1,8 +1,8 @@
-pub use crate::core::sync::Phone;
+pub use crate::core::sync::{Connection, Phone};
 use crate::core::theme::Theme;
 use crate::core::update::{SelfUpdateState, SelfUpdateStatus};
 pub use crate::gui::views::about::Message as AboutMessage;
-pub use crate::gui::views::list::{List as AppsView, LoadingState as ListLoadingState};
+pub use crate::gui::views::list::{DeviceLoad, List as AppsView, LoadingState as ListLoadingState};
 use crate::gui::{style, Message};
 use iced::widget::{button, container, pick_list, row, text, Space, Text};
 use iced::{alignment, Alignment, Element, Font, Length, Renderer};
@@ -12,6 +12,52 @@ pub const ICONS: Font = Font::External {
     bytes: include_bytes!("../../../resources/assets/icons.ttf"),
 };
 
+/// Device of the pick list, with its loading progress
+#[derive(Debug, Clone)]
+struct DeviceEntry {
+    phone: Phone,
+    status: Option<String>,       // None when its packages are ready
+    short_serial: Option<String>, // when another connected device has the same model
+}
+
+// The same device whatever was fetched since the last scan
+impl PartialEq for DeviceEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.phone.adb_id == other.phone.adb_id
+    }
+}
+
+impl Eq for DeviceEntry {}
+
+impl std::fmt::Display for DeviceEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.phone)?;
+        if let Some(short_serial) = &self.short_serial {
+            write!(f, " ({})", short_serial)?;
+        }
+        write!(f, " [{}]", self.phone.connection())?;
+        match &self.status {
+            Some(status) => write!(f, " ({}...)", status),
+            None => Ok(()),
+        }
+    }
+}
+
+fn device_entry(phone: &Phone, device_list: &[Phone], apps_view: &AppsView) -> DeviceEntry {
+    let same_model = device_list
+        .iter()
+        .any(|d| d.model == phone.model && d.adb_id != phone.adb_id);
+    DeviceEntry {
+        phone: phone.clone(),
+        short_serial: same_model.then(|| phone.short_serial()),
+        status: match apps_view.device_load(&phone.adb_id) {
+            Some(DeviceLoad::Ready(_)) => None,
+            Some(load) => Some(load.to_string()),
+            None => Some(DeviceLoad::Finding.to_string()),
+        },
+    }
+}
+
 pub fn nav_menu<'a>(
     device_list: &'a Vec<Phone>,
     selected_device: Option<Phone>,
@@ -70,19 +116,43 @@ pub fn nav_menu<'a>(
         .padding(5)
         .style(style::Button::Primary);
 
+    let wireless_btn = button("Wireless")
+        .on_press(Message::WirelessPressed)
+        .padding(5)
+        .style(style::Button::Primary);
+
     let device_list_text = match apps_view.loading_state {
         ListLoadingState::FindingPhones(_) => text("finding connected phone..."),
         _ => text("no devices/emulators found"),
     };
 
+    let disconnect_btn = match &selected_device {
+        Some(phone) if phone.connection() == Connection::Tcp => container(
+            button("Disconnect")
+                .on_press(Message::DisconnectPressed)
+                .padding(5)
+                .style(style::Button::Refresh),
+        ),
+        _ => container(Space::with_width(Length::Shrink)),
+    };
+
     let row = match selected_device {
         Some(phone) => row![
             apps_refresh_btn,
             reboot_btn,
-            pick_list(device_list, Some(phone), Message::DeviceSelected,),
+            disconnect_btn,
+            pick_list(
+                device_list
+                    .iter()
+                    .map(|d| device_entry(d, device_list, apps_view))
+                    .collect::<Vec<_>>(),
+                Some(device_entry(&phone, device_list, apps_view)),
+                |entry| Message::DeviceSelected(entry.phone),
+            ),
             Space::new(Length::Fill, Length::Shrink),
             uad_version_text,
             apps_btn,
+            wireless_btn,
             about_btn,
             settings_btn,
         ]
@@ -96,6 +166,7 @@ pub fn nav_menu<'a>(
             Space::new(Length::Fill, Length::Shrink),
             uad_version_text,
             apps_btn,
+            wireless_btn,
             about_btn,
             settings_btn,
         ]
@@ -1,10 +1,13 @@
-use crate::core::sync::Phone;
+use crate::core::config::DeviceSettings;
+use crate::core::sync::{DisabledBy, PackagePermission, PackageTimestamps, Phone, User};
 use crate::core::theme::Theme;
 use crate::core::uad_lists::{PackageState, Removal, UadList};
 use crate::gui::style;
 use crate::gui::views::settings::Settings;
+use crate::gui::widgets::hover::hover;
+use crate::gui::widgets::right_click::right_click;
 
-use iced::widget::{button, checkbox, row, text, Space};
+use iced::widget::{button, checkbox, container, row, text, tooltip, Space};
 use iced::{alignment, Alignment, Command, Element, Length, Renderer};
 
 #[derive(Clone, Debug)]
@@ -14,8 +17,68 @@ pub struct PackageRow {
     pub description: String,
     pub uad_list: UadList,
     pub removal: Removal,
+    pub reversible: bool, // false for apps installed by the user: no system APK to restore
     pub selected: bool,
     pub current: bool,
+    pub timestamps: Option<PackageTimestamps>, // fetched when the package is viewed
+    pub apk_count: Option<usize>, // more than 1 for split APKs, fetched when the package is viewed
+    pub permissions: Option<Vec<PackagePermission>>, // fetched when the package is viewed
+    pub action_override: ActionOverride,
+    pub protected: bool, // a removal failed because of a device admin
+    pub custom_category: Option<String>, // category of the user, replacing `removal` in the filters
+    pub disabled_by: Option<DisabledBy>, // only known for disabled packages
+    pub suspended: Option<bool>, // fetched when the package is viewed (Android 9+)
+    pub verification_failed: bool, // not in the expected state after the last bulk action
+}
+
+/// How to remove a package, whatever the settings say
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ActionOverride {
+    #[default]
+    Default,
+    ForceDisable,
+    ForceUninstall,
+}
+
+impl ActionOverride {
+    pub fn next(self) -> Self {
+        match self {
+            Self::Default => Self::ForceDisable,
+            Self::ForceDisable => Self::ForceUninstall,
+            Self::ForceUninstall => Self::Default,
+        }
+    }
+}
+
+impl std::fmt::Display for ActionOverride {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            Self::Default => "Auto",
+            Self::ForceDisable => "Force disable",
+            Self::ForceUninstall => "Force uninstall",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// State of the package for one user of the device, displayed when rows of several
+/// users are collapsed into a single one
+#[derive(Clone, Debug)]
+pub struct UserChip {
+    pub user: User,
+    pub state: PackageState,
+    pub included: bool,
+}
+
+/// What the list shows on a row besides the package itself
+#[derive(Clone, Debug, Default)]
+pub struct RowContext {
+    pub user_chips: Vec<UserChip>, // only when the rows of several users are collapsed
+    pub focused: bool,
+    pub snapshot_change: Option<String>, // compared to the selected snapshot
+    pub commands: String,                // commands of the action button, for its tooltip
+    pub has_note: bool,
+    pub search_match: Option<String>, // field the search matched, when it isn't the name
 }
 
 #[derive(Clone, Debug)]
@@ -23,6 +86,13 @@ pub enum Message {
     PackagePressed,
     ActionPressed,
     ToggleSelection(bool),
+    ToggleUserChip(usize),
+    TogglePin,
+    ToggleAllowList,
+    CycleActionOverride,
+    RefreshPressed,
+    Hovered(bool),
+    ContextMenuRequested, // right click
 }
 
 impl PackageRow {
@@ -41,16 +111,79 @@ impl PackageRow {
             description: description.to_string(),
             uad_list,
             removal,
+            reversible: true,
             selected,
             current,
+            timestamps: None,
+            apk_count: None,
+            permissions: None,
+            action_override: ActionOverride::Default,
+            protected: false,
+            custom_category: None,
+            disabled_by: None,
+            suspended: None,
+            verification_failed: false,
         }
     }
 
-    pub fn update(&mut self, _message: Message) -> Command<Message> {
+    /// Packages the OS disabled on purpose are left alone: enabling them could break something
+    pub fn system_disabled(&self) -> bool {
+        self.state == PackageState::Disabled && self.disabled_by == Some(DisabledBy::System)
+    }
+
+    /// UAD doesn't act on it: disabled on purpose by the OS, or in a state `pm` isn't sure of
+    pub fn locked(&self) -> bool {
+        self.system_disabled() || self.state == PackageState::Unknown
+    }
+
+    pub fn update(&mut self, message: Message) -> Command<Message> {
+        if let Message::CycleActionOverride = message {
+            self.action_override = self.action_override.next();
+        }
         Command::none()
     }
 
-    pub fn view(&self, settings: &Settings, _phone: &Phone) -> Element<Message, Renderer<Theme>> {
+    /// An unsafe package shown for audit: it can't be removed with these settings
+    pub fn read_only_unsafe(&self, settings: &Settings) -> bool {
+        self.state == PackageState::Enabled
+            && self.removal == Removal::Unsafe
+            && !settings.general.can_remove(self.removal)
+    }
+
+    /// Whether removing this package disables it rather than uninstalling it
+    pub fn disables(&self, settings: &DeviceSettings) -> bool {
+        match self.action_override {
+            ActionOverride::Default => settings.disables(self.removal),
+            ActionOverride::ForceDisable => true,
+            ActionOverride::ForceUninstall => false,
+        }
+    }
+
+    /// What an action on this package does, in a word
+    pub fn action_label(&self, settings: &DeviceSettings) -> &'static str {
+        match self.state {
+            PackageState::Enabled if self.disables(settings) => "disable",
+            PackageState::Enabled => "uninstall",
+            PackageState::Disabled => "enable",
+            PackageState::Uninstalled => "restore",
+            PackageState::All | PackageState::Unknown => "none",
+        }
+    }
+
+    pub fn view(
+        &self,
+        settings: &Settings,
+        _phone: &Phone,
+        context: RowContext,
+    ) -> Element<Message, Renderer<Theme>> {
+        let RowContext {
+            user_chips,
+            focused,
+            snapshot_change,
+            commands,
+            has_note,
+            search_match,
+        } = context;
         //let trash_svg = format!("{}/resources/assets/trash.svg", env!("CARGO_MANIFEST_DIR"));
         //let restore_svg = format!("{}/resources/assets/rotate.svg", env!("CARGO_MANIFEST_DIR"));
         let button_style;
@@ -60,7 +193,7 @@ impl PackageRow {
 
         match self.state {
             PackageState::Enabled => {
-                action_text = if settings.device.disable_mode {
+                action_text = if self.disables(&settings.device) {
                     "Disable"
                 } else {
                     "Uninstall"
@@ -75,6 +208,10 @@ impl PackageRow {
                 action_text = "Restore";
                 button_style = style::Button::RestorePackage;
             }
+            PackageState::Unknown => {
+                action_text = "Unknown";
+                button_style = style::Button::RestorePackage;
+            }
             PackageState::All => {
                 action_text = "Error";
                 button_style = style::Button::RestorePackage;
@@ -82,10 +219,19 @@ impl PackageRow {
             }
         }
         // Disable any removal action for unsafe packages if expert_mode is disabled
-        if self.removal != Removal::Unsafe
-            || self.state != PackageState::Enabled
-            || settings.general.expert_mode
-        {
+        // (and for anything but recommended packages in safe mode)
+        let actionable = !self.locked()
+            && (self.state != PackageState::Enabled || settings.general.can_remove(self.removal));
+        if self.locked() {
+            selection_checkbox = checkbox("", self.selected, Message::ToggleSelection)
+                .style(style::CheckBox::PackageDisabled);
+
+            action_btn = button(
+                text(action_text)
+                    .horizontal_alignment(alignment::Horizontal::Center)
+                    .width(Length::Units(100)),
+            );
+        } else if actionable {
             selection_checkbox = checkbox("", self.selected, Message::ToggleSelection)
                 .style(style::CheckBox::PackageEnabled);
 
@@ -106,26 +252,176 @@ impl PackageRow {
             );
         }
 
-        row![
+        let chips_row = user_chips.iter().fold(row![].spacing(5), |chips, chip| {
+            chips.push(
+                button(text(format!("{}: {}", chip.user.id, chip.state)).size(13))
+                    .padding([2, 5])
+                    .on_press(Message::ToggleUserChip(chip.user.index))
+                    .style(if chip.included {
+                        style::Button::UserChipIncluded
+                    } else {
+                        style::Button::UserChipExcluded
+                    }),
+            )
+        });
+
+        let package_btn =
             button(
                 row![
-                    selection_checkbox,
-                    text(&self.name).width(Length::FillPortion(8)),
-                    action_btn.style(button_style)
-                ]
-                .align_items(Alignment::Center)
+                selection_checkbox,
+                text(&self.name)
+                    .width(Length::FillPortion(8))
+                    .style(style::Text::PackageName(self.state)),
+                chips_row,
+                badge(
+                    self.apk_count
+                        .filter(|count| *count > 1)
+                        .map(|count| format!("{} splits", count)),
+                    style::Badge::Neutral,
+                    None,
+                ),
+                badge(has_note.then(|| "Note".to_string()), style::Badge::Info, None),
+                badge(
+                    search_match.map(|field| format!("Found in {}", field)),
+                    style::Badge::Info,
+                    None,
+                ),
+                badge(snapshot_change, style::Badge::Change, None),
+                badge(
+                    self.disabled_by
+                        .filter(|_| self.state == PackageState::Disabled)
+                        .map(|disabled_by| disabled_by.to_string()),
+                    match self.disabled_by {
+                        Some(DisabledBy::System) => style::Badge::Warning,
+                        _ => style::Badge::Neutral,
+                    },
+                    Some(match self.disabled_by {
+                        Some(DisabledBy::System) => {
+                            "Disabled on purpose by the OS or the app itself: UAD won't enable it"
+                        }
+                        _ => "Disabled by UAD or in the settings of the device",
+                    }),
+                ),
+                badge(
+                    self.verification_failed
+                        .then(|| "Verification failed".to_string()),
+                    style::Badge::Danger,
+                    Some(
+                        "The device didn't end up in the expected state after the bulk action: \
+                        it can be retried from the summary",
+                    ),
+                ),
+                badge(
+                    (self.state == PackageState::Unknown).then(|| "Unknown state".to_string()),
+                    style::Badge::Danger,
+                    Some(
+                        "The device reported contradictory states: refresh the list (F5) \
+                        before acting on it",
+                    ),
+                ),
+                badge(
+                    (self.suspended == Some(true)).then(|| "Suspended".to_string()),
+                    style::Badge::Warning,
+                    Some("Installed and enabled, but can't run until unsuspended"),
+                ),
+                badge(
+                    self.protected.then(|| "Protected".to_string()),
+                    style::Badge::Danger,
+                    Some(
+                        "Device admin app: deactivate it first in Settings > Security > \
+                        Device admin apps. Bulk removals skip it unless asked otherwise.",
+                    ),
+                ),
+                // Set from the context menu
+                badge(
+                    (self.state == PackageState::Enabled
+                        && self.action_override != ActionOverride::Default)
+                        .then(|| self.action_override.to_string()),
+                    style::Badge::Info,
+                    None,
+                ),
+                container(text(self.uad_list.to_string()).size(13))
+                    .padding([2, 6])
+                    .style(style::Container::ListBadge(self.uad_list)),
+                match (actionable, self.read_only_unsafe(settings)) {
+                    (true, _) if !commands.is_empty() => container(
+                        tooltip(
+                            action_btn.style(button_style),
+                            commands,
+                            tooltip::Position::Left,
+                        )
+                        .size(13)
+                        .padding(6)
+                        .style(style::Container::Frame),
+                    ),
+                    (false, true) => container(
+                        tooltip(
+                            action_btn.style(button_style),
+                            "Marked as unsafe: it can be inspected, but removing it needs \
+                            expert mode",
+                            tooltip::Position::Left,
+                        )
+                        .size(13)
+                        .padding(6)
+                        .style(style::Container::Frame),
+                    ),
+                    _ => container(action_btn.style(button_style)),
+                }
+            ]
+                .spacing(settings.general.list_density.row_spacing())
+                .align_items(Alignment::Center),
             )
-            .padding(8)
+            .padding(settings.general.list_density.row_padding())
             .style(if self.current {
                 style::Button::SelectedPackage
             } else {
                 style::Button::NormalPackage
             })
             .width(Length::Fill)
-            .on_press(Message::PackagePressed),
+            .on_press(Message::PackagePressed);
+
+        let package_row = row![
+            // The border is drawn behind the button, the padding keeps it visible
+            right_click(
+                container(package_btn).padding(2).width(Length::Fill).style(
+                    style::Container::PackageRow {
+                        state: self.state,
+                        focused,
+                    }
+                ),
+                Message::ContextMenuRequested
+            ),
             Space::with_width(Length::Units(15))
         ]
-        .align_items(Alignment::Center)
-        .into()
+        .align_items(Alignment::Center);
+
+        if settings.general.hover_preview {
+            hover(package_row, Message::Hovered(true), Message::Hovered(false)).into()
+        } else {
+            package_row.into()
+        }
+    }
+}
+
+/// Label next to the package name, explained in a tooltip if there is one.
+/// Nothing is shown without a label.
+fn badge<'a>(
+    label: Option<String>,
+    kind: style::Badge,
+    tip: Option<&str>,
+) -> Element<'a, Message, Renderer<Theme>> {
+    let label = match label {
+        Some(label) => container(text(label).size(13))
+            .padding([2, 6])
+            .style(style::Container::Badge(kind)),
+        None => return Space::with_width(Length::Shrink).into(),
+    };
+    match tip {
+        Some(tip) => tooltip(label, tip, tooltip::Position::Top)
+            .size(13)
+            .padding(6)
+            .style(style::Container::Frame)
+            .into(),
+        None => label.into(),
     }
 }
@@ -0,0 +1,126 @@
+use iced_native::widget::{Tree, Widget};
+use iced_native::{
+    event, layout, mouse, renderer, Clipboard, Element, Event, Layout, Length, Point, Rectangle,
+    Shell,
+};
+
+/// Wraps a widget and reports right clicks on it (buttons only handle the left one).
+///
+/// The content is laid out, drawn and receives its events as if it wasn't wrapped.
+pub struct RightClick<'a, Message, Renderer> {
+    content: Element<'a, Message, Renderer>,
+    on_right_click: Message,
+}
+
+pub fn right_click<'a, Message, Renderer>(
+    content: impl Into<Element<'a, Message, Renderer>>,
+    on_right_click: Message,
+) -> RightClick<'a, Message, Renderer> {
+    RightClick {
+        content: content.into(),
+        on_right_click,
+    }
+}
+
+impl<'a, Message, Renderer> Widget<Message, Renderer> for RightClick<'a, Message, Renderer>
+where
+    Message: Clone,
+    Renderer: iced_native::Renderer,
+{
+    fn width(&self) -> Length {
+        self.content.as_widget().width()
+    }
+
+    fn height(&self) -> Length {
+        self.content.as_widget().height()
+    }
+
+    fn children(&self) -> Vec<Tree> {
+        vec![Tree::new(&self.content)]
+    }
+
+    fn diff(&self, tree: &mut Tree) {
+        tree.diff_children(std::slice::from_ref(&self.content));
+    }
+
+    fn layout(&self, renderer: &Renderer, limits: &layout::Limits) -> layout::Node {
+        self.content.as_widget().layout(renderer, limits)
+    }
+
+    fn on_event(
+        &mut self,
+        tree: &mut Tree,
+        event: Event,
+        layout: Layout<'_>,
+        cursor_position: Point,
+        renderer: &Renderer,
+        clipboard: &mut dyn Clipboard,
+        shell: &mut Shell<'_, Message>,
+    ) -> event::Status {
+        if let Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Right)) = event {
+            if layout.bounds().contains(cursor_position) {
+                shell.publish(self.on_right_click.clone());
+                return event::Status::Captured;
+            }
+        }
+
+        self.content.as_widget_mut().on_event(
+            &mut tree.children[0],
+            event,
+            layout,
+            cursor_position,
+            renderer,
+            clipboard,
+            shell,
+        )
+    }
+
+    fn mouse_interaction(
+        &self,
+        tree: &Tree,
+        layout: Layout<'_>,
+        cursor_position: Point,
+        viewport: &Rectangle,
+        renderer: &Renderer,
+    ) -> mouse::Interaction {
+        self.content.as_widget().mouse_interaction(
+            &tree.children[0],
+            layout,
+            cursor_position,
+            viewport,
+            renderer,
+        )
+    }
+
+    fn draw(
+        &self,
+        tree: &Tree,
+        renderer: &mut Renderer,
+        theme: &Renderer::Theme,
+        style: &renderer::Style,
+        layout: Layout<'_>,
+        cursor_position: Point,
+        viewport: &Rectangle,
+    ) {
+        self.content.as_widget().draw(
+            &tree.children[0],
+            renderer,
+            theme,
+            style,
+            layout,
+            cursor_position,
+            viewport,
+        );
+    }
+}
+
+impl<'a, Message, Renderer> From<RightClick<'a, Message, Renderer>>
+    for Element<'a, Message, Renderer>
+where
+    Message: 'a + Clone,
+    Renderer: 'a + iced_native::Renderer,
+{
+    fn from(right_click: RightClick<'a, Message, Renderer>) -> Self {
+        Element::new(right_click)
+    }
+}
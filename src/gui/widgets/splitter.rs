@@ -0,0 +1,165 @@
+use crate::core::theme::Theme;
+use iced_native::widget::{tree, Tree, Widget};
+use iced_native::{
+    event, layout, mouse, renderer, Clipboard, Color, Element, Event, Layout, Length, Point,
+    Rectangle, Shell, Size,
+};
+
+/// Horizontal bar that can be dragged vertically to resize the widgets around it.
+///
+/// It only reports how far the cursor moved, the owner decides what to do with it.
+pub struct Splitter<Message> {
+    height: u16,
+    on_drag: Box<dyn Fn(f32) -> Message>,
+    on_release: Message,
+}
+
+#[derive(Default)]
+struct State {
+    last_cursor_y: Option<f32>,
+}
+
+pub fn splitter<Message>(
+    on_drag: impl Fn(f32) -> Message + 'static,
+    on_release: Message,
+) -> Splitter<Message> {
+    Splitter {
+        height: 6,
+        on_drag: Box::new(on_drag),
+        on_release,
+    }
+}
+
+impl<Message, Renderer> Widget<Message, Renderer> for Splitter<Message>
+where
+    Message: Clone,
+    Renderer: iced_native::Renderer<Theme = Theme>,
+{
+    fn width(&self) -> Length {
+        Length::Fill
+    }
+
+    fn height(&self) -> Length {
+        Length::Units(self.height)
+    }
+
+    fn tag(&self) -> tree::Tag {
+        tree::Tag::of::<State>()
+    }
+
+    fn state(&self) -> tree::State {
+        tree::State::new(State::default())
+    }
+
+    fn layout(&self, _renderer: &Renderer, limits: &layout::Limits) -> layout::Node {
+        let size = limits
+            .width(Length::Fill)
+            .height(Length::Units(self.height))
+            .resolve(Size::ZERO);
+        layout::Node::new(size)
+    }
+
+    fn on_event(
+        &mut self,
+        tree: &mut Tree,
+        event: Event,
+        layout: Layout<'_>,
+        cursor_position: Point,
+        _renderer: &Renderer,
+        _clipboard: &mut dyn Clipboard,
+        shell: &mut Shell<'_, Message>,
+    ) -> event::Status {
+        let state = tree.state.downcast_mut::<State>();
+
+        match event {
+            Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left))
+                if layout.bounds().contains(cursor_position) =>
+            {
+                state.last_cursor_y = Some(cursor_position.y);
+                event::Status::Captured
+            }
+            Event::Mouse(mouse::Event::CursorMoved { position }) => match state.last_cursor_y {
+                Some(last_y) => {
+                    shell.publish((self.on_drag)(position.y - last_y));
+                    state.last_cursor_y = Some(position.y);
+                    event::Status::Captured
+                }
+                None => event::Status::Ignored,
+            },
+            Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left))
+                if state.last_cursor_y.is_some() =>
+            {
+                state.last_cursor_y = None;
+                shell.publish(self.on_release.clone());
+                event::Status::Captured
+            }
+            _ => event::Status::Ignored,
+        }
+    }
+
+    fn mouse_interaction(
+        &self,
+        tree: &Tree,
+        layout: Layout<'_>,
+        cursor_position: Point,
+        _viewport: &Rectangle,
+        _renderer: &Renderer,
+    ) -> mouse::Interaction {
+        let state = tree.state.downcast_ref::<State>();
+
+        if state.last_cursor_y.is_some() || layout.bounds().contains(cursor_position) {
+            mouse::Interaction::ResizingVertically
+        } else {
+            mouse::Interaction::default()
+        }
+    }
+
+    fn draw(
+        &self,
+        tree: &Tree,
+        renderer: &mut Renderer,
+        theme: &Theme,
+        _style: &renderer::Style,
+        layout: Layout<'_>,
+        cursor_position: Point,
+        _viewport: &Rectangle,
+    ) {
+        let state = tree.state.downcast_ref::<State>();
+        let bounds = layout.bounds();
+        let p = theme.palette();
+
+        let color = if state.last_cursor_y.is_some() || bounds.contains(cursor_position) {
+            p.bright.primary
+        } else {
+            Color {
+                a: 0.5,
+                ..p.normal.primary
+            }
+        };
+
+        // Thin line centered in the draggable area
+        renderer.fill_quad(
+            renderer::Quad {
+                bounds: Rectangle {
+                    y: bounds.y + bounds.height / 2.0 - 1.0,
+                    height: 2.0,
+                    ..bounds
+                },
+                border_radius: 1.0,
+                border_width: 0.0,
+                border_color: Color::TRANSPARENT,
+            },
+            color,
+        );
+    }
+}
+
+impl<'a, Message, Renderer> From<Splitter<Message>> for Element<'a, Message, Renderer>
+where
+    Message: 'a + Clone,
+    Renderer: 'a + iced_native::Renderer<Theme = Theme>,
+{
+    fn from(splitter: Splitter<Message>) -> Self {
+        Element::new(splitter)
+    }
+}
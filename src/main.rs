@@ -3,6 +3,7 @@
 #[macro_use]
 extern crate log;
 
+use crate::core::save::LOG_DIR;
 use crate::core::utils::setup_uad_dir;
 use fern::{
     colors::{Color, ColoredLevelConfig},
@@ -10,9 +11,13 @@ use fern::{
 };
 use log::Record;
 use static_init::dynamic;
-use std::path::PathBuf;
-use std::{fmt::Arguments, fs::OpenOptions};
+use std::path::{Path, PathBuf};
+use std::{
+    fmt::Arguments,
+    fs::{File, OpenOptions},
+};
 
+mod cli;
 mod core;
 mod gui;
 
@@ -24,7 +29,18 @@ static CACHE_DIR: PathBuf = setup_uad_dir(dirs::cache_dir());
 
 fn main() {
     setup_logger().expect("setup logging");
-    gui::UadGui::start();
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.iter().any(|a| a == "--cli") {
+        std::process::exit(cli::run(&args));
+    }
+    // Without --cli, the serial only preselects the device
+    let serial = args
+        .iter()
+        .position(|a| a == "--serial")
+        .and_then(|i| args.get(i + 1))
+        .cloned();
+    gui::UadGui::start(serial);
 }
 
 pub fn setup_logger() -> Result<(), fern::InitError> {
@@ -48,12 +64,11 @@ pub fn setup_logger() -> Result<(), fern::InitError> {
     };
 
     let default_log_level = log::LevelFilter::Warn;
-    let log_file = OpenOptions::new()
-        .write(true)
-        .create(true)
-        .append(true)
-        .truncate(false)
-        .open(CACHE_DIR.join(format!("UAD_{}.log", chrono::Local::now().format("%Y%m%d"))))?;
+    // The data directory is set by the user and may not be writable
+    let (log_file, fallback) = match open_log_file(&LOG_DIR) {
+        Ok(file) => (file, None),
+        Err(e) => (open_log_file(&CACHE_DIR)?, Some(e)),
+    };
 
     let file_dispatcher = fern::Dispatch::new()
         .format(make_formatter(false))
@@ -72,5 +87,23 @@ pub fn setup_logger() -> Result<(), fern::InitError> {
         .chain(file_dispatcher)
         .apply()?;
 
+    if let Some(e) = fallback {
+        warn!(
+            "Cannot write logs in {}: {}. Logging in {} instead",
+            LOG_DIR.display(),
+            e,
+            CACHE_DIR.display()
+        );
+    }
     Ok(())
 }
+
+fn open_log_file(dir: &Path) -> std::io::Result<File> {
+    std::fs::create_dir_all(dir)?;
+    OpenOptions::new()
+        .write(true)
+        .create(true)
+        .append(true)
+        .truncate(false)
+        .open(dir.join(format!("UAD_{}.log", chrono::Local::now().format("%Y%m%d"))))
+}